@@ -0,0 +1,196 @@
+//! Benchmarks of the simulation kernels over canonical scenes and grid sizes.
+//!
+//! Run with `cargo bench`, optionally followed by `-- <filter>` to only run the benchmarks whose
+//! names contain the filter. Passing `--save-baseline <file>` records the results, and passing
+//! `--baseline <file>` compares the results against previously recorded ones.
+
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use bevy::ecs::schedule::ScheduleLabel;
+use bevy::prelude::*;
+
+use dust::sim::gas::{gas_bulk_flow, gas_dispersion};
+use dust::sim::gravity::{apply_gravity, Gravity};
+use dust::sim::movement::liquid_bulk_flow;
+use dust::sim::particle::defualts;
+use dust::sim::topology::Topology;
+use dust::sim::{Coords, Particle, PropertyGrid};
+
+const GRID_SIZES: [usize; 3] = [64, 128, 256];
+
+/// Ticks run before measuring, so the scenes are in motion
+const WARMUP_TICKS: usize = 10;
+/// Each benchmark runs for at least this long...
+const MIN_DURATION: Duration = Duration::from_millis(500);
+/// ...and at least this many ticks
+const MIN_TICKS: usize = 10;
+
+/// Relative slowdown compared to the baseline that is reported as a regression
+const REGRESSION_THRESHOLD: f64 = 0.1;
+
+struct Scene {
+    name: &'static str,
+    particle_at: fn(Coords, Coords) -> Particle,
+}
+
+const SCENES: [Scene; 4] = [
+    Scene { name: "air_box", particle_at: air_box },
+    Scene { name: "water_column", particle_at: water_column },
+    Scene { name: "dam_break", particle_at: dam_break },
+    Scene { name: "mostly_vacuum", particle_at: mostly_vacuum },
+];
+
+/// Air everywhere
+fn air_box(_coords: Coords, _dims: Coords) -> Particle {
+    defualts::AIR
+}
+
+/// A column of water a quarter of the width of the grid, surrounded by air
+fn water_column(coords: Coords, dims: Coords) -> Particle {
+    if (dims.x * 3 / 8..dims.x * 5 / 8).contains(&coords.x) {
+        defualts::WATER
+    } else {
+        defualts::AIR
+    }
+}
+
+/// A block of water filling the left third of the grid up to three quarters of its height,
+/// next to vacuum
+fn dam_break(coords: Coords, dims: Coords) -> Particle {
+    if coords.x < dims.x / 3 && coords.y < dims.y * 3 / 4 {
+        defualts::WATER
+    } else {
+        defualts::VACUUM
+    }
+}
+
+/// A small cloud of air and a few drops of water in a vacuum
+fn mostly_vacuum(coords: Coords, dims: Coords) -> Particle {
+    let center = Coords::new(dims.x / 2, dims.y / 2);
+    let radius = dims.x / 16;
+    if coords.x.abs_diff(center.x) < radius && coords.y.abs_diff(center.y) < radius {
+        defualts::AIR
+    } else if coords.y == dims.y * 3 / 4 && coords.x.is_multiple_of(8) {
+        defualts::WATER
+    } else {
+        defualts::VACUUM
+    }
+}
+
+#[derive(ScheduleLabel, Debug, Clone, PartialEq, Eq, Hash)]
+struct Tick;
+
+struct Kernel {
+    name: &'static str,
+    add_systems: fn(&mut Schedule),
+}
+
+const KERNELS: [Kernel; 6] = [
+    Kernel { name: "property_grid", add_systems: |schedule| { schedule.add_systems(property_grid_roundtrip); } },
+    Kernel { name: "gravity", add_systems: |schedule| { schedule.add_systems(apply_gravity); } },
+    Kernel { name: "gas_dispersion", add_systems: |schedule| { schedule.add_systems(gas_dispersion); } },
+    Kernel { name: "gas_bulk_flow", add_systems: |schedule| { schedule.add_systems(gas_bulk_flow); } },
+    Kernel { name: "liquid_bulk_flow", add_systems: |schedule| { schedule.add_systems(liquid_bulk_flow); } },
+    Kernel {
+        name: "tick",
+        add_systems: |schedule| {
+            schedule.add_systems((apply_gravity, liquid_bulk_flow, gas_dispersion, gas_bulk_flow).chain());
+        },
+    },
+];
+
+/// Reads and writes every cell, as a baseline for the cost of iterating over the grid
+fn property_grid_roundtrip(mut particles: Query<&mut PropertyGrid<Particle>>) {
+    let mut particles = particles.single_mut();
+    for coords in particles.coords() {
+        let particle = *particles.get(coords);
+        *particles.get_mut(coords) = std::hint::black_box(particle);
+    }
+}
+
+/// Runs the kernel on the scene and returns the number of ticks run per second
+fn bench(scene: &Scene, size: usize, kernel: &Kernel) -> f64 {
+    let dims = Coords::new(size, size);
+    let mut world = World::new();
+    world.init_resource::<Gravity>();
+    world.init_resource::<Topology>();
+    world.spawn(PropertyGrid::with_dims(dims, |coords| (scene.particle_at)(coords, dims)));
+
+    let mut schedule = Schedule::new(Tick);
+    (kernel.add_systems)(&mut schedule);
+
+    for _ in 0..WARMUP_TICKS {
+        schedule.run(&mut world);
+    }
+
+    let start = Instant::now();
+    let mut ticks = 0;
+    while ticks < MIN_TICKS || start.elapsed() < MIN_DURATION {
+        schedule.run(&mut world);
+        ticks += 1;
+    }
+    ticks as f64 / start.elapsed().as_secs_f64()
+}
+
+fn read_baseline(path: &str) -> HashMap<String, f64> {
+    let contents = std::fs::read_to_string(path).unwrap_or_else(|err| panic!("couldn't read baseline {path}: {err}"));
+    contents.lines()
+        .filter_map(|line| {
+            let (name, ticks_per_second) = line.split_once(' ')?;
+            Some((name.to_owned(), ticks_per_second.parse().ok()?))
+        })
+        .collect()
+}
+
+fn main() {
+    let mut filter = None;
+    let mut save_baseline = None;
+    let mut baseline = HashMap::new();
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--save-baseline" => save_baseline = Some(args.next().expect("missing baseline file")),
+            "--baseline" => baseline = read_baseline(&args.next().expect("missing baseline file")),
+            // passed by `cargo bench`
+            "--bench" => (),
+            _ => filter = Some(arg),
+        }
+    }
+
+    let mut results = String::new();
+    let mut n_regressions = 0;
+    for scene in &SCENES {
+        for size in GRID_SIZES {
+            for kernel in &KERNELS {
+                let name = format!("{}/{size}x{size}/{}", scene.name, kernel.name);
+                if filter.as_ref().is_some_and(|filter| !name.contains(filter.as_str())) {
+                    continue;
+                }
+
+                let ticks_per_second = bench(scene, size, kernel);
+                results.push_str(&format!("{name} {ticks_per_second}\n"));
+
+                let comparison = match baseline.get(&name) {
+                    Some(baseline) => {
+                        let change = ticks_per_second / baseline - 1.0;
+                        let regressed = change < -REGRESSION_THRESHOLD;
+                        n_regressions += regressed as usize;
+                        format!(" ({:+.1}%{})", 100.0 * change, if regressed { ", REGRESSED" } else { "" })
+                    },
+                    None => String::new(),
+                };
+                println!("{name:<44} {ticks_per_second:>10.1} ticks/s{comparison}");
+            }
+        }
+    }
+
+    if let Some(path) = save_baseline {
+        std::fs::write(&path, results).unwrap_or_else(|err| panic!("couldn't write baseline {path}: {err}"));
+    }
+    if n_regressions > 0 {
+        println!("\n{n_regressions} benchmark(s) regressed by more than {:.0}%", 100.0 * REGRESSION_THRESHOLD);
+        std::process::exit(1);
+    }
+}
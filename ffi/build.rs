@@ -0,0 +1,12 @@
+//! Regenerates `include/dust.h` from the types and functions exported by `src/lib.rs`, but only with the `header` feature,
+//! so that ordinary builds leave the checked-in header alone
+
+fn main() {
+    #[cfg(feature = "header")]
+    {
+        let crate_dir = std::env::var("CARGO_MANIFEST_DIR").unwrap();
+        cbindgen::generate(&crate_dir)
+            .expect("couldn't generate the C header")
+            .write_to_file(format!("{crate_dir}/include/dust.h"));
+    }
+}
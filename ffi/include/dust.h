@@ -0,0 +1,178 @@
+#ifndef DUST_H
+#define DUST_H
+
+/* Generated by cbindgen from src/lib.rs with `cargo build -p dust-ffi --features header`, so don't edit it by hand */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/**
+ * The scalar fields that can be copied out of the grid. Cells without physical properties read as 0.
+ */
+typedef enum DustField {
+  DUST_FIELD_MASS,
+  DUST_FIELD_MOMENTUM_X,
+  DUST_FIELD_MOMENTUM_Y,
+  DUST_FIELD_HEAT,
+  DUST_FIELD_TEMPERATURE,
+} DustField;
+
+typedef enum DustMaterial {
+  DUST_MATERIAL_VACUUM,
+  DUST_MATERIAL_AIR,
+  DUST_MATERIAL_WATER,
+  DUST_MATERIAL_ABSORPTIVE_WALL,
+  DUST_MATERIAL_REFLECTIVE_WALL,
+} DustMaterial;
+
+typedef enum DustStatus {
+  DUST_STATUS_OK,
+  DUST_STATUS_NULL_POINTER,
+  /**
+   * The cell is outside of the grid
+   */
+  DUST_STATUS_OUT_OF_BOUNDS,
+  /**
+   * The cell holds vacuum or a wall, which have no physical properties
+   */
+  DUST_STATUS_NO_PROPERTIES,
+  /**
+   * The buffer has fewer elements than the grid has cells
+   */
+  DUST_STATUS_BUFFER_TOO_SMALL,
+  /**
+   * The value isn't one of the enum's, or the physical properties can't be simulated
+   */
+  DUST_STATUS_INVALID_ARGUMENT,
+} DustStatus;
+
+/**
+ * A simulation and its grids
+ */
+typedef struct DustSim DustSim;
+
+/**
+ * The physical properties of the air or water in a cell
+ */
+typedef struct DustPhysicalProperties {
+  float mass;
+  float momentum_x;
+  float momentum_y;
+  float heat;
+  float specific_heat;
+  /**
+   * Position within the cell, from 0 to 1 along each axis
+   */
+  float internal_position_x;
+  float internal_position_y;
+} DustPhysicalProperties;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * Creates a `width` by `height` grid of vacuum, or returns null if either is 0
+ */
+struct DustSim *dust_sim_create(size_t width, size_t height);
+
+/**
+ * # Safety
+ * `sim` must be null or come from `dust_sim_create`, and must not be used again
+ */
+void dust_sim_destroy(struct DustSim *sim);
+
+/**
+ * Returns the width of the grid, or 0 if `sim` is null
+ *
+ * # Safety
+ * `sim` must be null or a live simulation
+ */
+size_t dust_sim_width(const struct DustSim *sim);
+
+/**
+ * Returns the height of the grid, or 0 if `sim` is null
+ *
+ * # Safety
+ * `sim` must be null or a live simulation
+ */
+size_t dust_sim_height(const struct DustSim *sim);
+
+/**
+ * Runs `ticks` ticks of the simulation
+ *
+ * # Safety
+ * `sim` must be null or a live simulation
+ */
+enum DustStatus dust_sim_step(struct DustSim *sim, size_t ticks);
+
+/**
+ * # Safety
+ * `sim` must be null or a live simulation, and `material` must be null or valid for writes
+ */
+enum DustStatus dust_sim_get_material(const struct DustSim *sim,
+                                      size_t x,
+                                      size_t y,
+                                      enum DustMaterial *material);
+
+/**
+ * Replaces the cell with `material`, one of the `DustMaterial`s, with its default physical properties
+ *
+ * # Safety
+ * `sim` must be null or a live simulation
+ */
+enum DustStatus dust_sim_set_material(struct DustSim *sim,
+                                      size_t x,
+                                      size_t y,
+                                      uint32_t material);
+
+/**
+ * # Safety
+ * `sim` must be null or a live simulation, and `properties` must be null or valid for writes
+ */
+enum DustStatus dust_sim_get_properties(const struct DustSim *sim,
+                                        size_t x,
+                                        size_t y,
+                                        struct DustPhysicalProperties *properties);
+
+/**
+ * Overwrites the physical properties of the air or water in the cell, unless any is NaN or infinite,
+ * the mass or specific heat isn't positive, the heat is negative, or the internal position isn't from 0 up to 1
+ *
+ * # Safety
+ * `sim` must be null or a live simulation, and `properties` must be null or valid for reads
+ */
+enum DustStatus dust_sim_set_properties(struct DustSim *sim,
+                                        size_t x,
+                                        size_t y,
+                                        const struct DustPhysicalProperties *properties);
+
+/**
+ * Copies `field`, one of the `DustField`s, of every cell into `buffer`, where cell `x`, `y` goes to `buffer[y * width + x]`
+ *
+ * # Safety
+ * `sim` must be null or a live simulation, and `buffer` must be null or valid for writing `len` floats
+ */
+enum DustStatus dust_sim_copy_field(const struct DustSim *sim,
+                                    uint32_t field,
+                                    float *buffer,
+                                    size_t len);
+
+/**
+ * Copies the material of every cell into `buffer`, where cell `x`, `y` goes to `buffer[y * width + x]`
+ *
+ * # Safety
+ * `sim` must be null or a live simulation, and `buffer` must be null or valid for writing `len` materials
+ */
+enum DustStatus dust_sim_copy_materials(const struct DustSim *sim,
+                                        enum DustMaterial *buffer,
+                                        size_t len);
+
+#ifdef __cplusplus
+} // extern "C"
+#endif // __cplusplus
+
+#endif /* DUST_H */
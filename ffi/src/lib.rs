@@ -0,0 +1,327 @@
+//! A C interface to the simulation, for running it inside engines that don't use Bevy.
+//!
+//! A `DustSim` is created by `dust_sim_create` and freed by `dust_sim_destroy`, and is only ever handled through a pointer.
+//! Cells are addressed by column `x` and row `y`, with row 0 at the bottom,
+//! and fields are copied into caller buffers a row at a time, from the bottom row up.
+//! A panic inside the simulation aborts the process, since it can't unwind into C.
+
+use dust::sim::particle::{defualts, Wall};
+use dust::sim::types::Vector;
+use dust::sim::{Coords, Particle, PhysicalProperties, Simulation};
+
+/// A simulation and its grids
+pub struct DustSim {
+    simulation: Simulation,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DustStatus {
+    Ok,
+    NullPointer,
+    /// The cell is outside of the grid
+    OutOfBounds,
+    /// The cell holds vacuum or a wall, which have no physical properties
+    NoProperties,
+    /// The buffer has fewer elements than the grid has cells
+    BufferTooSmall,
+    /// The value isn't one of the enum's, or the physical properties can't be simulated
+    InvalidArgument,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DustMaterial {
+    Vacuum,
+    Air,
+    Water,
+    AbsorptiveWall,
+    ReflectiveWall,
+}
+
+/// The scalar fields that can be copied out of the grid. Cells without physical properties read as 0.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DustField {
+    Mass,
+    MomentumX,
+    MomentumY,
+    Heat,
+    Temperature,
+}
+
+/// The physical properties of the air or water in a cell
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DustPhysicalProperties {
+    pub mass: f32,
+    pub momentum_x: f32,
+    pub momentum_y: f32,
+    pub heat: f32,
+    pub specific_heat: f32,
+    /// Position within the cell, from 0 to 1 along each axis
+    pub internal_position_x: f32,
+    pub internal_position_y: f32,
+}
+
+impl From<PhysicalProperties> for DustPhysicalProperties {
+    fn from(value: PhysicalProperties) -> Self {
+        Self {
+            mass: value.mass,
+            momentum_x: value.momentum.x,
+            momentum_y: value.momentum.y,
+            heat: value.heat,
+            specific_heat: value.specific_heat,
+            internal_position_x: value.internal_position.x,
+            internal_position_y: value.internal_position.y,
+        }
+    }
+}
+
+impl TryFrom<DustPhysicalProperties> for PhysicalProperties {
+    type Error = DustStatus;
+
+    fn try_from(value: DustPhysicalProperties) -> Result<Self, DustStatus> {
+        let physical_properties = Self {
+            mass: value.mass,
+            momentum: Vector::new(value.momentum_x, value.momentum_y),
+            heat: value.heat,
+            specific_heat: value.specific_heat,
+            internal_position: Vector::new(value.internal_position_x, value.internal_position_y),
+        };
+        match physical_properties.is_valid() {
+            true => Ok(physical_properties),
+            false => Err(DustStatus::InvalidArgument),
+        }
+    }
+}
+
+impl From<&Particle> for DustMaterial {
+    fn from(value: &Particle) -> Self {
+        match value {
+            Particle::Vacuum => Self::Vacuum,
+            Particle::Air { .. } => Self::Air,
+            Particle::Water { .. } => Self::Water,
+            Particle::Wall(Wall::Absorptive) => Self::AbsorptiveWall,
+            Particle::Wall(Wall::Reflective) => Self::ReflectiveWall,
+        }
+    }
+}
+
+impl TryFrom<u32> for DustMaterial {
+    type Error = DustStatus;
+
+    fn try_from(value: u32) -> Result<Self, DustStatus> {
+        [Self::Vacuum, Self::Air, Self::Water, Self::AbsorptiveWall, Self::ReflectiveWall]
+            .into_iter()
+            .find(|material| *material as u32 == value)
+            .ok_or(DustStatus::InvalidArgument)
+    }
+}
+
+impl DustMaterial {
+    /// A particle of this material with its default physical properties
+    fn to_particle(self) -> Particle {
+        match self {
+            Self::Vacuum => defualts::VACUUM,
+            Self::Air => defualts::AIR,
+            Self::Water => defualts::WATER,
+            Self::AbsorptiveWall => defualts::WALL_ABSORPTIVE,
+            Self::ReflectiveWall => defualts::WALL_REFLECTIVE,
+        }
+    }
+}
+
+impl TryFrom<u32> for DustField {
+    type Error = DustStatus;
+
+    fn try_from(value: u32) -> Result<Self, DustStatus> {
+        [Self::Mass, Self::MomentumX, Self::MomentumY, Self::Heat, Self::Temperature]
+            .into_iter()
+            .find(|field| *field as u32 == value)
+            .ok_or(DustStatus::InvalidArgument)
+    }
+}
+
+impl DustField {
+    fn get(self, physical_properties: &PhysicalProperties) -> f32 {
+        match self {
+            Self::Mass => physical_properties.mass,
+            Self::MomentumX => physical_properties.momentum.x,
+            Self::MomentumY => physical_properties.momentum.y,
+            Self::Heat => physical_properties.heat,
+            Self::Temperature => physical_properties.temperature(),
+        }
+    }
+}
+
+fn status(result: Result<(), DustStatus>) -> DustStatus {
+    result.err().unwrap_or(DustStatus::Ok)
+}
+
+unsafe fn get_cell<'a>(sim: *const DustSim, x: usize, y: usize) -> Result<&'a Particle, DustStatus> {
+    let sim = sim.as_ref().ok_or(DustStatus::NullPointer)?;
+    sim.simulation.particles().try_get(Coords::new(x, y)).ok_or(DustStatus::OutOfBounds)
+}
+
+unsafe fn get_cell_mut<'a>(sim: *mut DustSim, x: usize, y: usize) -> Result<&'a mut Particle, DustStatus> {
+    let sim = sim.as_mut().ok_or(DustStatus::NullPointer)?;
+    sim.simulation.particles_mut().try_get_mut(Coords::new(x, y)).ok_or(DustStatus::OutOfBounds)
+}
+
+/// Copies `value` of each cell into `buffer`, a row at a time from the bottom row up
+unsafe fn copy_cells<T>(
+    sim: *const DustSim,
+    buffer: *mut T,
+    len: usize,
+    value: impl Fn(&Particle) -> T,
+) -> Result<(), DustStatus> {
+    let sim = sim.as_ref().ok_or(DustStatus::NullPointer)?;
+    if buffer.is_null() {
+        return Err(DustStatus::NullPointer);
+    }
+    let particles = sim.simulation.particles();
+    let dims = particles.dims();
+    if dims.x.checked_mul(dims.y).is_none_or(|cells| len < cells) {
+        return Err(DustStatus::BufferTooSmall);
+    }
+
+    // written through the pointer, since C callers usually pass uninitialized buffers, which can't be made into slices
+    for coords in particles.coords() {
+        buffer.add(coords.y * dims.x + coords.x).write(value(particles.get(coords)));
+    }
+    Ok(())
+}
+
+/// Creates a `width` by `height` grid of vacuum, or returns null if either is 0
+#[no_mangle]
+pub extern "C" fn dust_sim_create(width: usize, height: usize) -> *mut DustSim {
+    if width == 0 || height == 0 {
+        return std::ptr::null_mut();
+    }
+    Box::into_raw(Box::new(DustSim { simulation: Simulation::new(Coords::new(width, height)) }))
+}
+
+/// # Safety
+/// `sim` must be null or come from `dust_sim_create`, and must not be used again
+#[no_mangle]
+pub unsafe extern "C" fn dust_sim_destroy(sim: *mut DustSim) {
+    if !sim.is_null() {
+        drop(Box::from_raw(sim));
+    }
+}
+
+/// Returns the width of the grid, or 0 if `sim` is null
+///
+/// # Safety
+/// `sim` must be null or a live simulation
+#[no_mangle]
+pub unsafe extern "C" fn dust_sim_width(sim: *const DustSim) -> usize {
+    sim.as_ref().map_or(0, |sim| sim.simulation.dims().x)
+}
+
+/// Returns the height of the grid, or 0 if `sim` is null
+///
+/// # Safety
+/// `sim` must be null or a live simulation
+#[no_mangle]
+pub unsafe extern "C" fn dust_sim_height(sim: *const DustSim) -> usize {
+    sim.as_ref().map_or(0, |sim| sim.simulation.dims().y)
+}
+
+/// Runs `ticks` ticks of the simulation
+///
+/// # Safety
+/// `sim` must be null or a live simulation
+#[no_mangle]
+pub unsafe extern "C" fn dust_sim_step(sim: *mut DustSim, ticks: usize) -> DustStatus {
+    let Some(sim) = sim.as_mut() else {
+        return DustStatus::NullPointer;
+    };
+    for _ in 0..ticks {
+        sim.simulation.step();
+    }
+    DustStatus::Ok
+}
+
+/// # Safety
+/// `sim` must be null or a live simulation, and `material` must be null or valid for writes
+#[no_mangle]
+pub unsafe extern "C" fn dust_sim_get_material(sim: *const DustSim, x: usize, y: usize, material: *mut DustMaterial) -> DustStatus {
+    status((|| {
+        let particle = get_cell(sim, x, y)?;
+        *material.as_mut().ok_or(DustStatus::NullPointer)? = particle.into();
+        Ok(())
+    })())
+}
+
+/// Replaces the cell with `material`, one of the `DustMaterial`s, with its default physical properties
+///
+/// # Safety
+/// `sim` must be null or a live simulation
+#[no_mangle]
+pub unsafe extern "C" fn dust_sim_set_material(sim: *mut DustSim, x: usize, y: usize, material: u32) -> DustStatus {
+    status((|| {
+        let material = DustMaterial::try_from(material)?;
+        *get_cell_mut(sim, x, y)? = material.to_particle();
+        Ok(())
+    })())
+}
+
+/// # Safety
+/// `sim` must be null or a live simulation, and `properties` must be null or valid for writes
+#[no_mangle]
+pub unsafe extern "C" fn dust_sim_get_properties(
+    sim: *const DustSim,
+    x: usize,
+    y: usize,
+    properties: *mut DustPhysicalProperties,
+) -> DustStatus {
+    status((|| {
+        let physical_properties = get_cell(sim, x, y)?.physical_properties().ok_or(DustStatus::NoProperties)?;
+        *properties.as_mut().ok_or(DustStatus::NullPointer)? = (*physical_properties).into();
+        Ok(())
+    })())
+}
+
+/// Overwrites the physical properties of the air or water in the cell, unless any is NaN or infinite,
+/// the mass or specific heat isn't positive, the heat is negative, or the internal position isn't from 0 up to 1
+///
+/// # Safety
+/// `sim` must be null or a live simulation, and `properties` must be null or valid for reads
+#[no_mangle]
+pub unsafe extern "C" fn dust_sim_set_properties(
+    sim: *mut DustSim,
+    x: usize,
+    y: usize,
+    properties: *const DustPhysicalProperties,
+) -> DustStatus {
+    status((|| {
+        let properties = PhysicalProperties::try_from(*properties.as_ref().ok_or(DustStatus::NullPointer)?)?;
+        *get_cell_mut(sim, x, y)?.physical_properties_mut().ok_or(DustStatus::NoProperties)? = properties;
+        Ok(())
+    })())
+}
+
+/// Copies `field`, one of the `DustField`s, of every cell into `buffer`, where cell `x`, `y` goes to `buffer[y * width + x]`
+///
+/// # Safety
+/// `sim` must be null or a live simulation, and `buffer` must be null or valid for writing `len` floats
+#[no_mangle]
+pub unsafe extern "C" fn dust_sim_copy_field(sim: *const DustSim, field: u32, buffer: *mut f32, len: usize) -> DustStatus {
+    status((|| {
+        let field = DustField::try_from(field)?;
+        copy_cells(sim, buffer, len, |particle| {
+            particle.physical_properties().map_or(0.0, |physical_properties| field.get(physical_properties))
+        })
+    })())
+}
+
+/// Copies the material of every cell into `buffer`, where cell `x`, `y` goes to `buffer[y * width + x]`
+///
+/// # Safety
+/// `sim` must be null or a live simulation, and `buffer` must be null or valid for writing `len` materials
+#[no_mangle]
+pub unsafe extern "C" fn dust_sim_copy_materials(sim: *const DustSim, buffer: *mut DustMaterial, len: usize) -> DustStatus {
+    status(copy_cells(sim, buffer, len, |particle| particle.into()))
+}
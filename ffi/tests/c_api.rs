@@ -0,0 +1,27 @@
+//! Builds `smoke.c` against the generated header and the static library, and runs it.
+
+use std::env;
+use std::path::PathBuf;
+use std::process::Command;
+
+#[test]
+fn c_smoke_test() {
+    let crate_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    // Cargo builds the static library into the same directory as the test binary
+    let deps_dir = env::current_exe().unwrap().parent().unwrap().to_path_buf();
+    let program = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("smoke");
+
+    let compiler = env::var("CC").unwrap_or_else(|_| "cc".to_owned());
+    let status = Command::new(compiler)
+        .arg(crate_dir.join("tests/smoke.c"))
+        .arg("-I").arg(crate_dir.join("include"))
+        .arg(deps_dir.join("libdust_ffi.a"))
+        .args(["-lm", "-lpthread", "-ldl", "-o"])
+        .arg(&program)
+        .status()
+        .expect("couldn't run the C compiler");
+    assert!(status.success(), "couldn't compile smoke.c");
+
+    let output = Command::new(&program).output().unwrap();
+    assert!(output.status.success(), "smoke.c failed: {}", String::from_utf8_lossy(&output.stderr));
+}
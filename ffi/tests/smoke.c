@@ -0,0 +1,118 @@
+/* Exercises the C interface the way an embedding program would. Exits with 1 on the first failed check. */
+
+#include <math.h>
+#include <stdio.h>
+#include <stdlib.h>
+
+#include "dust.h"
+
+#define CHECK(condition)                                                      \
+  do {                                                                        \
+    if (!(condition)) {                                                       \
+      fprintf(stderr, "%s:%d: check failed: %s\n", __FILE__, __LINE__, #condition); \
+      exit(1);                                                                \
+    }                                                                         \
+  } while (0)
+
+#define WIDTH 16
+#define HEIGHT 16
+
+static float total_mass(const DustSim *sim) {
+  float mass[WIDTH * HEIGHT];
+  CHECK(dust_sim_copy_field(sim, DUST_FIELD_MASS, mass, WIDTH * HEIGHT) == DUST_STATUS_OK);
+  float total = 0;
+  for (size_t i = 0; i < WIDTH * HEIGHT; i++) {
+    total += mass[i];
+  }
+  return total;
+}
+
+int main(void) {
+  CHECK(dust_sim_create(0, HEIGHT) == NULL);
+
+  DustSim *sim = dust_sim_create(WIDTH, HEIGHT);
+  CHECK(sim != NULL);
+  CHECK(dust_sim_width(sim) == WIDTH);
+  CHECK(dust_sim_height(sim) == HEIGHT);
+
+  /* Cells start as vacuum, which has no physical properties */
+  DustMaterial material;
+  DustPhysicalProperties properties;
+  CHECK(dust_sim_get_material(sim, 3, 3, &material) == DUST_STATUS_OK);
+  CHECK(material == DUST_MATERIAL_VACUUM);
+  CHECK(dust_sim_get_properties(sim, 3, 3, &properties) == DUST_STATUS_NO_PROPERTIES);
+
+  /* A wall along the bottom, with a block of water above it */
+  for (size_t x = 0; x < WIDTH; x++) {
+    CHECK(dust_sim_set_material(sim, x, 0, DUST_MATERIAL_REFLECTIVE_WALL) == DUST_STATUS_OK);
+  }
+  for (size_t x = 4; x < 8; x++) {
+    for (size_t y = 10; y < 12; y++) {
+      CHECK(dust_sim_set_material(sim, x, y, DUST_MATERIAL_WATER) == DUST_STATUS_OK);
+    }
+  }
+  CHECK(dust_sim_get_material(sim, 5, 11, &material) == DUST_STATUS_OK);
+  CHECK(material == DUST_MATERIAL_WATER);
+
+  CHECK(dust_sim_get_properties(sim, 5, 11, &properties) == DUST_STATUS_OK);
+  CHECK(properties.mass > 0);
+  properties.heat *= 2;
+  CHECK(dust_sim_set_properties(sim, 5, 11, &properties) == DUST_STATUS_OK);
+  DustPhysicalProperties written;
+  CHECK(dust_sim_get_properties(sim, 5, 11, &written) == DUST_STATUS_OK);
+  CHECK(written.heat == properties.heat);
+
+  /* Bad arguments are reported rather than crashing */
+  CHECK(dust_sim_get_material(sim, WIDTH, 0, &material) == DUST_STATUS_OUT_OF_BOUNDS);
+  CHECK(dust_sim_set_material(sim, 0, HEIGHT, DUST_MATERIAL_AIR) == DUST_STATUS_OUT_OF_BOUNDS);
+  CHECK(dust_sim_get_material(sim, 0, 0, NULL) == DUST_STATUS_NULL_POINTER);
+  CHECK(dust_sim_step(NULL, 1) == DUST_STATUS_NULL_POINTER);
+  float small[WIDTH];
+  CHECK(dust_sim_copy_field(sim, DUST_FIELD_MASS, small, WIDTH) == DUST_STATUS_BUFFER_TOO_SMALL);
+  CHECK(dust_sim_set_material(sim, 0, 0, 99) == DUST_STATUS_INVALID_ARGUMENT);
+  CHECK(dust_sim_get_material(sim, 0, 0, &material) == DUST_STATUS_OK);
+  CHECK(material == DUST_MATERIAL_REFLECTIVE_WALL);
+  float field[WIDTH * HEIGHT];
+  CHECK(dust_sim_copy_field(sim, 99, field, WIDTH * HEIGHT) == DUST_STATUS_INVALID_ARGUMENT);
+
+  /* So are properties that the simulation can't run with, which leave the cell as it was */
+  DustPhysicalProperties invalid = written;
+  invalid.mass = NAN;
+  CHECK(dust_sim_set_properties(sim, 5, 11, &invalid) == DUST_STATUS_INVALID_ARGUMENT);
+  invalid = written;
+  invalid.mass = -1;
+  CHECK(dust_sim_set_properties(sim, 5, 11, &invalid) == DUST_STATUS_INVALID_ARGUMENT);
+  invalid = written;
+  invalid.momentum_x = INFINITY;
+  CHECK(dust_sim_set_properties(sim, 5, 11, &invalid) == DUST_STATUS_INVALID_ARGUMENT);
+  invalid = written;
+  invalid.internal_position_y = 1;
+  CHECK(dust_sim_set_properties(sim, 5, 11, &invalid) == DUST_STATUS_INVALID_ARGUMENT);
+  CHECK(dust_sim_get_properties(sim, 5, 11, &invalid) == DUST_STATUS_OK);
+  CHECK(invalid.mass == written.mass && invalid.momentum_x == written.momentum_x && invalid.heat == written.heat);
+
+  /* The water falls onto the wall without losing mass */
+  float mass_before = total_mass(sim);
+  CHECK(dust_sim_step(sim, 100) == DUST_STATUS_OK);
+  float mass_after = total_mass(sim);
+  CHECK(fabsf(mass_after - mass_before) <= 1e-3f * mass_before);
+
+  DustMaterial materials[WIDTH * HEIGHT];
+  CHECK(dust_sim_copy_materials(sim, materials, WIDTH * HEIGHT) == DUST_STATUS_OK);
+  size_t water = 0;
+  for (size_t i = 0; i < WIDTH * HEIGHT; i++) {
+    water += materials[i] == DUST_MATERIAL_WATER;
+  }
+  CHECK(water > 0);
+  for (size_t x = 0; x < WIDTH; x++) {
+    CHECK(materials[x] == DUST_MATERIAL_REFLECTIVE_WALL);
+  }
+  for (size_t x = 0; x < WIDTH; x++) {
+    CHECK(materials[(HEIGHT - 1) * WIDTH + x] != DUST_MATERIAL_WATER);
+  }
+
+  dust_sim_destroy(sim);
+  dust_sim_destroy(NULL);
+  printf("ok\n");
+  return 0;
+}
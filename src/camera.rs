@@ -1,13 +1,36 @@
+use bevy::input::mouse::{MouseMotion, MouseScrollUnit, MouseWheel};
 use bevy::prelude::*;
+use bevy::window::WindowResized;
 
-use crate::sim::{Coords, GRID_CORNER, PIXEL_SIZE};
+use crate::sim::{RelCoords, GRID_CORNER, N_PIXELS, PIXEL_SIZE};
+use crate::sim::topology::Topology;
 use crate::sim::types::Vector;
 
+/// Factor by which one line of scrolling zooms in or out
+const ZOOM_STEP: f32 = 1.1;
+/// Pixel-based scrolling (e.g., from touchpads) zooms by one step per this many pixels
+const PIXELS_PER_LINE: f32 = 50.0;
+/// Bounds on the camera's scale, i.e., on the number of world units per screen pixel
+const MIN_SCALE: f32 = 1.0 / 32.0;
+const MAX_SCALE: f32 = 8.0;
+/// Keyboard panning speed, in screen pixels per second
+const PAN_SPEED: f32 = 500.0;
+/// Fraction of the window the grid fills when fit to it
+const FIT_FRACTION: f32 = 0.9;
+
 pub struct CameraPlugin;
 
 impl Plugin for CameraPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(Startup, spawn_camera);
+        app
+            .add_systems(Startup, spawn_camera)
+            .add_systems(Update, (
+                zoom_camera,
+                pan_camera,
+                handle_view_inputs,
+                fit_on_resize,
+            ))
+        ;
     }
 }
 
@@ -15,6 +38,122 @@ fn spawn_camera(mut commands: Commands) {
     commands.spawn(Camera2dBundle::default());
 }
 
+/// Scrolling zooms about the cursor, unless `Ctrl` is held, which resizes the brush instead
+fn zoom_camera(
+    mut camera: Query<&mut Transform, With<Camera>>,
+    mut scrolls: EventReader<MouseWheel>,
+    keys: Res<ButtonInput<KeyCode>>,
+    window: Query<&Window>,
+) {
+    let lines = scrolls.read()
+        .map(|scroll| match scroll.unit {
+            MouseScrollUnit::Line => scroll.y,
+            MouseScrollUnit::Pixel => scroll.y / PIXELS_PER_LINE,
+        })
+        .sum::<f32>();
+    if lines == 0.0 || keys.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]) {
+        return;
+    }
+
+    let window = window.single();
+    let mut camera = camera.single_mut();
+    let cursor_position = window.cursor_position().unwrap_or(Vec2::new(window.width(), window.height()) / 2.0);
+    let fixed_point = window_to_camera(cursor_position, window, &camera).xy();
+    zoom_about(&mut camera, fixed_point, ZOOM_STEP.powf(-lines));
+}
+
+/// Dragging with the middle mouse button or holding the arrow keys pans the camera
+fn pan_camera(
+    mut camera: Query<&mut Transform, With<Camera>>,
+    mut motions: EventReader<MouseMotion>,
+    cursor_input: Res<ButtonInput<MouseButton>>,
+    keys: Res<ButtonInput<KeyCode>>,
+    time: Res<Time<Real>>,
+) {
+    let mut camera = camera.single_mut();
+
+    // screen space is y-down, world space is y-up
+    let mut delta = Vec2::ZERO;
+    let drag = motions.read().map(|motion| motion.delta).sum::<Vec2>();
+    if cursor_input.pressed(MouseButton::Middle) {
+        delta += Vec2::new(-drag.x, drag.y);
+    }
+
+    let key_dirs = [
+        (KeyCode::ArrowLeft, Vec2::NEG_X),
+        (KeyCode::ArrowRight, Vec2::X),
+        (KeyCode::ArrowDown, Vec2::NEG_Y),
+        (KeyCode::ArrowUp, Vec2::Y),
+    ];
+    for (key, dir) in key_dirs {
+        if keys.pressed(key) {
+            delta += dir * PAN_SPEED * time.delta_seconds();
+        }
+    }
+
+    if delta != Vec2::ZERO {
+        let scale = camera.scale.x;
+        camera.translation += (delta * scale).extend(0.0);
+    }
+}
+
+/// - `F` fits the grid to the window
+/// - `X` resets the view, showing the grid at its native size
+fn handle_view_inputs(
+    mut camera: Query<&mut Transform, With<Camera>>,
+    keys: Res<ButtonInput<KeyCode>>,
+    window: Query<&Window>,
+    topology: Res<Topology>,
+) {
+    if keys.just_pressed(KeyCode::KeyF) {
+        fit_grid(&mut camera.single_mut(), window.single(), *topology);
+    }
+    if keys.just_pressed(KeyCode::KeyX) {
+        reset_view(&mut camera.single_mut());
+    }
+}
+
+fn fit_on_resize(
+    mut camera: Query<&mut Transform, With<Camera>>,
+    mut resizes: EventReader<WindowResized>,
+    window: Query<&Window>,
+    topology: Res<Topology>,
+) {
+    if resizes.read().count() > 0 {
+        fit_grid(&mut camera.single_mut(), window.single(), *topology);
+    }
+}
+
+/// Scales the camera by `factor` while keeping `fixed_point` at the same place on screen
+fn zoom_about(camera: &mut Transform, fixed_point: Vec2, factor: f32) {
+    let old_scale = camera.scale.x;
+    let new_scale = (old_scale * factor).clamp(MIN_SCALE, MAX_SCALE);
+    let ratio = new_scale / old_scale;
+
+    let translation = fixed_point + (camera.translation.xy() - fixed_point) * ratio;
+    camera.translation = translation.extend(camera.translation.z);
+    camera.scale = Vec3::new(new_scale, new_scale, 1.0);
+}
+
+/// Centers the grid and scales it to fill the window
+fn fit_grid(camera: &mut Transform, window: &Window, topology: Topology) {
+    let bounds = grid_bounds(topology);
+    let window_size = Vec2::new(window.width(), window.height()) * FIT_FRACTION;
+    if window_size.x <= 0.0 || window_size.y <= 0.0 {
+        // e.g., while minimized
+        return;
+    }
+
+    let scale = (bounds.size() / window_size).max_element().clamp(MIN_SCALE, MAX_SCALE);
+    camera.translation = bounds.center().extend(camera.translation.z);
+    camera.scale = Vec3::new(scale, scale, 1.0);
+}
+
+fn reset_view(camera: &mut Transform) {
+    camera.translation = Vec3::new(0.0, 0.0, camera.translation.z);
+    camera.scale = Vec3::ONE;
+}
+
 pub fn window_to_camera(window_pos: Vec2, window: &Window, camera: &Transform) -> Vec3 {
     let window_pos = Vec3::new(
         window_pos.x - window.width() / 2.,
@@ -25,14 +164,85 @@ pub fn window_to_camera(window_pos: Vec2, window: &Window, camera: &Transform) -
     *camera * window_pos
 }
 
-pub fn camera_to_grid(camera_pos: Vec3) -> Vector {
-    (camera_pos.xy() - GRID_CORNER) / PIXEL_SIZE
+/// Coordinates whose floor is the cell at `camera_pos`
+pub fn camera_to_grid(camera_pos: Vec3, topology: Topology) -> Vector {
+    topology.grid_position((camera_pos.xy() - GRID_CORNER) / PIXEL_SIZE)
+}
+
+/// The center of the cell, in camera coordinates
+pub fn grid_to_camera(grid_coords: impl Into<RelCoords>, topology: Topology) -> Vec3 {
+    (GRID_CORNER + topology.cell_position(grid_coords.into()) * PIXEL_SIZE).extend(1.0)
+}
+
+/// The size of the bounding box of a cell, in camera coordinates
+pub fn cell_size(topology: Topology) -> Vec2 {
+    PIXEL_SIZE * Vec2::new(1.0, topology.row_height())
 }
 
-pub fn grid_to_camera(grid_coords: Coords) -> Vec3 {
-    Vec3::new(
-        GRID_CORNER.x + grid_coords.x as f32 * PIXEL_SIZE.x,
-        GRID_CORNER.y + grid_coords.y as f32 * PIXEL_SIZE.y,
-        1.0,
-    )
+/// The area covered by the grid, including the half cells that shifted rows stick out by
+pub fn grid_bounds(topology: Topology) -> Rect {
+    let min = GRID_CORNER - cell_size(topology) / 2.0;
+    let max_offset = topology.row_offset(0).max(topology.row_offset(1));
+    let size = Vec2::new(N_PIXELS.x as f32 + max_offset, N_PIXELS.y as f32) * cell_size(topology);
+    Rect::from_corners(min, min + size)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_float_eq::*;
+
+    #[test]
+    fn zoom_keeps_fixed_point() {
+        let mut camera = Transform::from_xyz(10.0, -5.0, 100.0);
+        let fixed_point = Vec2::new(30.0, 20.0);
+        let screen_offset = (fixed_point - camera.translation.xy()) / camera.scale.x;
+
+        zoom_about(&mut camera, fixed_point, 0.5);
+
+        let after = camera.translation.xy() + screen_offset * camera.scale.x;
+        assert_f32_near!(after.x, fixed_point.x);
+        assert_f32_near!(after.y, fixed_point.y);
+        assert_f32_near!(camera.scale.x, 0.5);
+        assert_f32_near!(camera.translation.z, 100.0);
+    }
+
+    #[test]
+    fn zoom_is_clamped() {
+        let mut camera = Transform::IDENTITY;
+        zoom_about(&mut camera, Vec2::ZERO, 1e6);
+        assert_f32_near!(camera.scale.x, MAX_SCALE);
+        zoom_about(&mut camera, Vec2::ZERO, 1e-9);
+        assert_f32_near!(camera.scale.x, MIN_SCALE);
+    }
+
+    #[test]
+    fn cell_centers_map_to_their_cells() {
+        for topology in [Topology::VonNeumann, Topology::Hexagonal] {
+            for coords in [RelCoords::new(0, 0), RelCoords::new(5, 6), RelCoords::new(5, 7)] {
+                let grid_pos = camera_to_grid(grid_to_camera(coords, topology), topology);
+                assert_eq!(RelCoords::from(grid_pos), coords);
+            }
+        }
+    }
+
+    #[test]
+    fn hex_grid_sticks_out_by_half_a_cell() {
+        let square = grid_bounds(Topology::VonNeumann);
+        let hex = grid_bounds(Topology::Hexagonal);
+        assert_f32_near!(square.width(), N_PIXELS.x as f32 * PIXEL_SIZE.x);
+        assert_f32_near!(hex.width(), square.width() + PIXEL_SIZE.x / 2.0);
+        assert!(hex.height() < square.height());
+    }
+
+    #[test]
+    fn window_center_is_camera_translation() {
+        let window = Window { resolution: (800.0, 600.0).into(), ..default() };
+        let mut camera = Transform::from_xyz(12.0, 34.0, 0.0);
+        camera.scale = Vec3::new(2.0, 2.0, 1.0);
+
+        let camera_pos = window_to_camera(Vec2::new(400.0, 300.0), &window, &camera);
+        assert_f32_near!(camera_pos.x, 12.0);
+        assert_f32_near!(camera_pos.y, 34.0);
+    }
 }
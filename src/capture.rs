@@ -0,0 +1,323 @@
+//! Capturing frames of the grid every few ticks, either as numbered PNGs or as an animated GIF, for docs and demos.
+//!
+//! Frames are rendered from the grid itself rather than read back from the screen, so capturing works without a window
+//! or a GPU, including while replaying a recording headlessly.
+
+use std::error::Error;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use bevy::ecs::system::RunSystemOnce;
+use bevy::prelude::*;
+
+use crate::color::{self, ColorRange, RenderMode, Visualization};
+use crate::schedule::{SimSet, SimState, TickRate};
+use crate::sim::topology::Topology;
+use crate::sim::types::Scalar;
+use crate::sim::{Coords, Particle, PropertyGrid};
+
+/// What the grid is drawn over, which shows through vacuum and thin air like in the window
+const BACKGROUND: [u8; 3] = [43, 44, 47];
+/// GIF viewers show frames that are any shorter for longer, in hundredths of a second
+const MIN_GIF_DELAY: u16 = 2;
+/// How coarsely colors are sampled when picking each GIF frame's palette, from 1 (finest) to 30
+const GIF_QUANTIZER_SPEED: i32 = 10;
+
+/// Starts capturing with `capture` if set, in addition to letting captures be started and stopped
+pub struct CapturePlugin {
+    pub capture: Option<CaptureSettings>,
+}
+
+impl Plugin for CapturePlugin {
+    fn build(&self, app: &mut App) {
+        if let Some(settings) = &self.capture {
+            app
+                .insert_resource(StartCapture(settings.clone()))
+                // after the grids are spawned and any scenario has been loaded onto them
+                .add_systems(PostStartup, start_capture.after(crate::scenario::load_initial_scenario))
+            ;
+        }
+        app
+            .add_systems(Update, handle_capture_inputs)
+            .add_systems(
+                FixedUpdate,
+                capture_frames
+                    .after(SimSet::Gas)
+                    .run_if(resource_exists::<FrameCapture>)
+                    .run_if(in_state(SimState::Playing).or_else(in_state(SimState::Stepping))),
+            )
+        ;
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct CaptureSettings {
+    /// A `.gif` file to write an animation to, or otherwise a directory to write numbered PNGs to
+    pub path: PathBuf,
+    /// Ticks from one frame to the next
+    pub every: u64,
+    /// Width and height of each cell, in pixels
+    pub scale: usize,
+    pub mode: RenderMode,
+    /// Whether the colormap spans the range of the field in each frame instead of a fixed range
+    pub auto_scale: bool,
+}
+
+impl Default for CaptureSettings {
+    fn default() -> Self {
+        Self {
+            path: PathBuf::new(),
+            every: 2,
+            scale: 4,
+            mode: RenderMode::Material,
+            auto_scale: false,
+        }
+    }
+}
+
+/// A capture in progress
+#[derive(Resource)]
+pub struct FrameCapture {
+    settings: CaptureSettings,
+    sink: Sink,
+    /// Ticks simulated per second at normal speed, which animations play back at
+    ticks_per_second: f64,
+    /// Ticks observed so far
+    ticks: u64,
+    /// Frames written so far
+    frames: usize,
+}
+
+enum Sink {
+    Png,
+    /// Created along with the first frame, since the size of the frames isn't known before
+    Gif(Option<GifSink>),
+}
+
+struct GifSink {
+    encoder: gif::Encoder<BufWriter<File>>,
+    /// In hundredths of a second
+    delay: u16,
+}
+
+impl FrameCapture {
+    /// Starts capturing, with animations played back at `ticks_per_second`
+    pub fn start(settings: CaptureSettings, ticks_per_second: f64) -> Result<Self, Box<dyn Error>> {
+        let sink = if settings.path.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("gif")) {
+            Sink::Gif(None)
+        } else {
+            std::fs::create_dir_all(&settings.path)?;
+            Sink::Png
+        };
+        Ok(Self { settings, sink, ticks_per_second, ticks: 0, frames: 0 })
+    }
+
+    pub fn path(&self) -> &PathBuf {
+        &self.settings.path
+    }
+
+    pub fn frames(&self) -> usize {
+        self.frames
+    }
+
+    /// Takes the grid as of each tick, starting with the one that capturing started at, and writes every `every`th one
+    pub fn observe(&mut self, particles: &PropertyGrid<Particle>, topology: Topology) -> Result<(), Box<dyn Error>> {
+        let tick = self.ticks;
+        self.ticks += 1;
+        if !tick.is_multiple_of(self.settings.every.max(1)) {
+            return Ok(());
+        }
+
+        let frame = Frame::render(particles, topology, &self.settings);
+        match &mut self.sink {
+            Sink::Png => {
+                let path = self.settings.path.join(format!("frame-{:05}.png", self.frames));
+                let mut encoder = png::Encoder::new(BufWriter::new(File::create(path)?), frame.width as u32, frame.height as u32);
+                encoder.set_color(png::ColorType::Rgb);
+                encoder.set_depth(png::BitDepth::Eight);
+                encoder.write_header()?.write_image_data(&frame.rgb)?;
+            },
+            Sink::Gif(encoder) => {
+                let (width, height) = (u16::try_from(frame.width)?, u16::try_from(frame.height)?);
+                let GifSink { encoder, delay } = match encoder {
+                    Some(encoder) => encoder,
+                    None => {
+                        let seconds = self.settings.every as f64 / self.ticks_per_second;
+                        let delay = ((seconds * 100.0).round() as u16).max(MIN_GIF_DELAY);
+                        let file = BufWriter::new(File::create(&self.settings.path)?);
+                        let mut gif = gif::Encoder::new(file, width, height, &[])?;
+                        gif.set_repeat(gif::Repeat::Infinite)?;
+                        encoder.insert(GifSink { encoder: gif, delay })
+                    },
+                };
+                let mut gif_frame = gif::Frame::from_rgb_speed(width, height, &frame.rgb, GIF_QUANTIZER_SPEED);
+                gif_frame.delay = *delay;
+                encoder.write_frame(&gif_frame)?;
+            },
+        }
+        self.frames += 1;
+        Ok(())
+    }
+}
+
+/// A picture of the grid, with each cell as a square of `scale` pixels
+struct Frame {
+    width: usize,
+    height: usize,
+    /// RGB bytes, row by row from the top
+    rgb: Vec<u8>,
+}
+
+impl Frame {
+    fn render(particles: &PropertyGrid<Particle>, topology: Topology, settings: &CaptureSettings) -> Self {
+        let CaptureSettings { mode, auto_scale, scale, .. } = *settings;
+        let range = ColorRange::new(mode, auto_scale, particles);
+        let dims = particles.dims();
+        // shifted rows stick out past the others
+        let width = ((dims.x as Scalar + topology.row_offset(1)) * scale as Scalar).ceil() as usize;
+        let height = dims.y * scale;
+
+        let mut rgb = BACKGROUND.repeat(width * height);
+        for coords in particles.coords() {
+            let [r, g, b, a] = color::get_field_color(particles.get(coords), mode, &range).as_rgba_f32();
+            let a = a.clamp(0.0, 1.0);
+            let blend = |channel: f32, background: u8| (channel * a * 255.0 + background as f32 * (1.0 - a)).round() as u8;
+            let color = [blend(r, BACKGROUND[0]), blend(g, BACKGROUND[1]), blend(b, BACKGROUND[2])];
+
+            let Coords { x, y } = coords;
+            let left = ((x as Scalar + topology.row_offset(y as isize)) * scale as Scalar).round() as usize;
+            let top = (dims.y - 1 - y) * scale;
+            for row in top..top + scale {
+                let start = 3 * (row * width + left);
+                for pixel in rgb[start..start + 3 * scale].chunks_exact_mut(3) {
+                    pixel.copy_from_slice(&color);
+                }
+            }
+        }
+        Self { width, height, rgb }
+    }
+}
+
+#[derive(Resource)]
+struct StartCapture(CaptureSettings);
+
+fn start_capture(world: &mut World) {
+    let Some(StartCapture(settings)) = world.remove_resource::<StartCapture>() else {
+        return;
+    };
+    let ticks_per_second = world.resource::<TickRate>().ticks_per_second;
+    match FrameCapture::start(settings.clone(), ticks_per_second) {
+        Ok(capture) => {
+            info!("capturing frames to {}", capture.path().display());
+            world.insert_resource(capture);
+            world.run_system_once(capture_frames);
+        },
+        Err(err) => error!("couldn't capture frames to {}: {err}", settings.path.display()),
+    }
+}
+
+/// `F10` starts capturing an animation of the grid as it's shown, and stops capturing and saves it to a `.gif` file
+fn handle_capture_inputs(
+    mut commands: Commands,
+    capture: Option<Res<FrameCapture>>,
+    visualization: Option<Res<Visualization>>,
+    keys: Res<ButtonInput<KeyCode>>,
+) {
+    if !keys.just_pressed(KeyCode::F10) {
+        return;
+    }
+
+    if let Some(capture) = capture {
+        info!("saved {} frames to {}", capture.frames(), capture.path().display());
+        commands.remove_resource::<FrameCapture>();
+        return;
+    }
+
+    let seconds = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+    let (mode, auto_scale) = visualization.map_or((RenderMode::Material, false), |visualization| (visualization.mode, visualization.auto_scale));
+    commands.insert_resource(StartCapture(CaptureSettings {
+        path: format!("capture-{seconds}.gif").into(),
+        mode,
+        auto_scale,
+        ..default()
+    }));
+    commands.add(start_capture);
+}
+
+fn capture_frames(
+    mut commands: Commands,
+    mut capture: ResMut<FrameCapture>,
+    particles: Query<&PropertyGrid<Particle>>,
+    topology: Res<Topology>,
+) {
+    if let Err(err) = capture.observe(particles.single(), *topology) {
+        error!("stopped capturing frames to {}: {err}", capture.path().display());
+        commands.remove_resource::<FrameCapture>();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sim::particle::defualts;
+
+    fn settings(path: PathBuf) -> CaptureSettings {
+        CaptureSettings { path, every: 3, scale: 2, ..default() }
+    }
+
+    #[test]
+    fn cells_are_drawn_from_the_bottom_up() {
+        let mut particles = PropertyGrid::with_dims(Coords::new(3, 2), |_| defualts::VACUUM);
+        *particles.get_mut(Coords::new(0, 0)) = defualts::WALL_REFLECTIVE;
+
+        let frame = Frame::render(&particles, Topology::VonNeumann, &settings(PathBuf::new()));
+        assert_eq!((frame.width, frame.height), (6, 4));
+        let pixel = |x: usize, y: usize| &frame.rgb[3 * (y * frame.width + x)..3 * (y * frame.width + x) + 3];
+        let wall = color::get_color(&defualts::WALL_REFLECTIVE).as_rgba_f32().map(|channel| (channel * 255.0).round() as u8);
+        assert_eq!(pixel(1, 3), &wall[..3]);
+        assert_eq!(pixel(0, 0), &BACKGROUND);
+
+        let frame = Frame::render(&particles, Topology::Hexagonal, &settings(PathBuf::new()));
+        assert_eq!(frame.width, 7);
+    }
+
+    #[test]
+    fn every_few_ticks_are_written() {
+        let dir = std::env::temp_dir().join(format!("dust-capture-{}", std::process::id()));
+        let particles = PropertyGrid::with_dims(Coords::new(4, 4), |_| defualts::AIR);
+        let mut capture = FrameCapture::start(settings(dir.clone()), 60.0).unwrap();
+        for _ in 0..7 {
+            capture.observe(&particles, Topology::VonNeumann).unwrap();
+        }
+        assert_eq!(capture.frames(), 3);
+
+        let decoder = png::Decoder::new(File::open(dir.join("frame-00002.png")).unwrap());
+        let info = decoder.read_info().unwrap().info().clone();
+        assert_eq!((info.width, info.height), (8, 8));
+
+        let gif = dir.join("capture.gif");
+        let mut capture = FrameCapture::start(settings(gif.clone()), 60.0).unwrap();
+        for _ in 0..7 {
+            capture.observe(&particles, Topology::VonNeumann).unwrap();
+        }
+        drop(capture);
+        let mut options = gif::DecodeOptions::new();
+        options.set_color_output(gif::ColorOutput::RGBA);
+        let mut decoder = options.read_info(File::open(&gif).unwrap()).unwrap();
+        assert_eq!((decoder.width(), decoder.height()), (8, 8));
+        // frames with few enough colors get an exact palette
+        let rendered = Frame::render(&particles, Topology::VonNeumann, &settings(gif.clone()));
+        let mut frames = 0;
+        while let Some(frame) = decoder.read_next_frame().unwrap() {
+            assert_eq!(frame.delay, 5);
+            let rgb = frame.buffer.chunks_exact(4).flat_map(|pixel| &pixel[..3]).copied().collect::<Vec<_>>();
+            assert_eq!(rgb, rendered.rgb);
+            frames += 1;
+        }
+        assert_eq!(frames, 3);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -1,38 +1,322 @@
+pub(crate) mod arrows;
+mod colormap;
+mod legend;
+mod velocity_overlay;
+
 use bevy::prelude::*;
+use bevy::render::render_asset::RenderAssetUsages;
+use bevy::render::render_resource::{Extent3d, TextureDimension, TextureFormat};
+use bevy::render::texture::ImageSampler;
+use const_soft_float::soft_f32::SoftF32;
 
+use crate::camera::grid_bounds;
 use crate::schedule::SimSet;
 use crate::sim::gravity::GRAVITY_ACCELERATION;
+use crate::sim::topology::Topology;
 use crate::sim::types::Scalar;
-use crate::sim::{Coords, Particle, PropertyGrid, N_PIXELS, physical_properties};
+use crate::sim::{Coords, Particle, PhysicalProperties, PropertyGrid, N_PIXELS, physical_properties};
+use colormap::Colormap;
 
 pub struct ColorPlugin;
 
 impl Plugin for ColorPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(Update, update_colors.in_set(SimSet::Recolor));
+        app
+            .init_resource::<Visualization>()
+            .init_resource::<ColorRange>()
+            .add_plugins(legend::LegendPlugin)
+            .add_plugins(velocity_overlay::VelocityOverlayPlugin)
+            .add_systems(Startup, spawn_grid_image)
+            .add_systems(Update, (
+                handle_visualization_inputs.before(SimSet::Recolor),
+                update_colors.in_set(SimSet::Recolor),
+            ))
+        ;
+    }
+}
+
+/// What the color of each cell shows
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum RenderMode {
+    /// Each kind of particle has its own color, with air tinted by temperature
+    #[default]
+    Material,
+    Temperature,
+    Mass,
+    Speed,
+    KineticEnergy,
+    Pressure,
+}
+
+impl RenderMode {
+    const ALL: [Self; 6] = [
+        Self::Material,
+        Self::Temperature,
+        Self::Mass,
+        Self::Speed,
+        Self::KineticEnergy,
+        Self::Pressure,
+    ];
+
+    pub fn next(self) -> Self {
+        let i = Self::ALL.iter().position(|mode| *mode == self).unwrap();
+        Self::ALL[(i + 1) % Self::ALL.len()]
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|mode| mode.name().eq_ignore_ascii_case(name))
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Material => "Material",
+            Self::Temperature => "Temperature",
+            Self::Mass => "Mass",
+            Self::Speed => "Speed",
+            Self::KineticEnergy => "Kinetic Energy",
+            Self::Pressure => "Pressure",
+        }
+    }
+
+    pub fn unit(&self) -> &'static str {
+        match self {
+            Self::Material => "",
+            Self::Temperature => "K",
+            Self::Mass => "kg",
+            Self::Speed => "m/s",
+            Self::KineticEnergy => "J",
+            Self::Pressure => "Pa",
+        }
+    }
+
+    /// The value of the field shown, or `None` in `Material` mode
+    fn value(&self, properties: &PhysicalProperties) -> Option<Scalar> {
+        match self {
+            Self::Material => None,
+            Self::Temperature => Some(properties.temperature()),
+            Self::Mass => Some(properties.mass),
+            Self::Speed => Some(properties.velocity().length()),
+            Self::KineticEnergy => Some(properties.kinetic_energy()),
+            Self::Pressure => Some(properties.pressure()),
+        }
+    }
+
+    /// The range spanned by the colormap when not auto-scaling
+    fn fixed_range(&self) -> ColorRange {
+        let max = match self {
+            Self::Material => 1.0,
+            Self::Temperature => HIGH_AIR_TEMPERATURE,
+            Self::Mass => physical_properties::defaults::WATER.mass,
+            Self::Speed => AIR_FALLING_SPEED,
+            Self::KineticEnergy => KE_FROM_AIR_FALLING,
+            Self::Pressure => HIGH_AIR_PRESSURE,
+        };
+        ColorRange { min: 0.0, max }
+    }
+
+    fn colormap(&self) -> &'static Colormap {
+        match self {
+            Self::Temperature | Self::KineticEnergy => &colormap::INFERNO,
+            Self::Pressure => &colormap::PLASMA,
+            Self::Material | Self::Mass | Self::Speed => &colormap::VIRIDIS,
+        }
+    }
+}
+
+#[derive(Resource, Debug, Default)]
+pub struct Visualization {
+    pub mode: RenderMode,
+    /// Whether the colormap spans the current min/max of the field instead of a fixed range
+    pub auto_scale: bool,
+}
+
+/// The values mapped to the ends of the colormap as of the last recolor
+#[derive(Resource, Debug, Default, Clone, Copy, PartialEq)]
+pub struct ColorRange {
+    pub min: Scalar,
+    pub max: Scalar,
+}
+
+impl ColorRange {
+    /// The range the colormap spans for `mode`, which is the current range of its field over the grid when auto-scaling
+    pub fn new(mode: RenderMode, auto_scale: bool, particle_grid: &PropertyGrid<Particle>) -> Self {
+        auto_scale
+            .then(|| Self::of_grid(mode, particle_grid))
+            .flatten()
+            .unwrap_or_else(|| mode.fixed_range())
+    }
+
+    /// The range of `mode`'s field over the grid, or `None` if no cell has physical properties
+    fn of_grid(mode: RenderMode, particle_grid: &PropertyGrid<Particle>) -> Option<Self> {
+        particle_grid.coords()
+            .filter_map(|coords| get_physical_properties(particle_grid.get(coords)))
+            .filter_map(|properties| mode.value(properties))
+            .fold(None, |range: Option<Self>, value| Some(match range {
+                None => Self { min: value, max: value },
+                Some(Self { min, max }) => Self { min: min.min(value), max: max.max(value) },
+            }))
+    }
+
+    fn normalize(&self, value: Scalar) -> Scalar {
+        if self.max > self.min {
+            (value - self.min) / (self.max - self.min)
+        } else {
+            0.5
+        }
     }
 }
 
+/// - `V` cycles through the render modes
+/// - `A` toggles auto-scaling the colormap to the current range of the field
+fn handle_visualization_inputs(
+    mut visualization: ResMut<Visualization>,
+    keys: Res<ButtonInput<KeyCode>>,
+) {
+    if keys.just_pressed(KeyCode::KeyV) {
+        visualization.mode = visualization.mode.next();
+    }
+    if keys.just_pressed(KeyCode::KeyA) {
+        visualization.auto_scale = !visualization.auto_scale;
+    }
+}
+
+/// The texture the grid is rendered to, one pixel per cell, or two side by side when rows are shifted by half a cell
+#[derive(Component)]
+struct GridImage {
+    handle: Handle<Image>,
+    /// What the image currently shows, so that only the pixels that changed are redrawn
+    rendered: Option<RenderedGrid>,
+}
+
+struct RenderedGrid {
+    particles: PropertyGrid<Particle>,
+    mode: RenderMode,
+    range: ColorRange,
+    topology: Topology,
+}
+
+const BYTES_PER_PIXEL: usize = 4;
+
+fn spawn_grid_image(
+    mut commands: Commands,
+    mut images: ResMut<Assets<Image>>,
+    topology: Res<Topology>,
+) {
+    let mut image = Image::new_fill(
+        image_size(*topology),
+        TextureDimension::D2,
+        &[0; BYTES_PER_PIXEL],
+        TextureFormat::Rgba8UnormSrgb,
+        RenderAssetUsages::default(),
+    );
+    image.sampler = ImageSampler::nearest();
+    let handle = images.add(image);
+
+    let bounds = grid_bounds(*topology);
+    commands.spawn((
+        GridImage { handle: handle.clone(), rendered: None },
+        SpriteBundle {
+            texture: handle,
+            sprite: Sprite {
+                custom_size: Some(bounds.size()),
+                ..default()
+            },
+            transform: Transform::from_translation(bounds.center().extend(1.0)),
+            ..default()
+        },
+    ));
+}
 
 fn update_colors(
-    particle_grid: Query<&PropertyGrid<Particle>, Changed<PropertyGrid<Particle>>>,
-    mut coords: Query<(&Coords, &mut Sprite)>,
+    particle_grid: Query<Ref<PropertyGrid<Particle>>>,
+    visualization: Res<Visualization>,
+    topology: Res<Topology>,
+    mut color_range: ResMut<ColorRange>,
+    mut grid_image: Query<(&mut GridImage, &mut Sprite, &mut Transform)>,
+    mut images: ResMut<Assets<Image>>,
 ) {
     let Ok(particle_grid) = particle_grid.get_single() else {
         return;
     };
+    if !particle_grid.is_changed() && !visualization.is_changed() && !topology.is_changed() {
+        return;
+    }
+
+    let mode = visualization.mode;
+    let topology = *topology;
+    let range = ColorRange::new(mode, visualization.auto_scale, &particle_grid);
+    color_range.set_if_neq(range);
+
+    let (mut grid_image, mut sprite, mut transform) = grid_image.single_mut();
+    let GridImage { handle, rendered } = &mut *grid_image;
+    let image = images.get_mut(handle.id()).unwrap();
 
-    for (coords, mut sprite) in coords.iter_mut() {
-        sprite.color = get_color(particle_grid.get(*coords));
+    if rendered.as_ref().is_some_and(|rendered| rendered.topology != topology) {
+        image.resize(image_size(topology));
+        image.data.fill(0);
+        let bounds = grid_bounds(topology);
+        sprite.custom_size = Some(bounds.size());
+        transform.translation = bounds.center().extend(transform.translation.z);
+        *rendered = None;
     }
+
+    let changed: Vec<Coords> = match rendered {
+        Some(rendered) if rendered.mode == mode && rendered.range == range => particle_grid.coords()
+            .filter(|coords| rendered.particles.get(*coords) != particle_grid.get(*coords))
+            .collect(),
+        _ => particle_grid.coords().collect(),
+    };
+    if changed.is_empty() {
+        return;
+    }
+
+    for coords in changed {
+        let color = get_field_color(particle_grid.get(coords), mode, &range).as_rgba_u8();
+        for pixel in image.data[cell_bytes(coords, topology)].chunks_exact_mut(BYTES_PER_PIXEL) {
+            pixel.copy_from_slice(&color);
+        }
+    }
+    *rendered = Some(RenderedGrid { particles: PropertyGrid::clone(&particle_grid), mode, range, topology });
+}
+
+/// Shifted rows are drawn shifted by one pixel, so each cell is two pixels wide
+fn pixels_per_cell(topology: Topology) -> usize {
+    if topology.row_offset(1) == 0.0 { 1 } else { 2 }
+}
+
+fn image_width(topology: Topology) -> usize {
+    let pixels_per_cell = pixels_per_cell(topology);
+    N_PIXELS.x * pixels_per_cell + (topology.row_offset(1) * pixels_per_cell as Scalar) as usize
+}
+
+fn image_size(topology: Topology) -> Extent3d {
+    Extent3d {
+        width: image_width(topology) as u32,
+        height: N_PIXELS.y as u32,
+        depth_or_array_layers: 1,
+    }
+}
+
+/// Indices of the bytes of the cell's pixels.
+/// Rows of the image go from top to bottom, while the grid's y-axis points up.
+fn cell_bytes(coords: Coords, topology: Topology) -> std::ops::Range<usize> {
+    let pixels_per_cell = pixels_per_cell(topology);
+    let column = ((coords.x as Scalar + topology.row_offset(coords.y as isize)) * pixels_per_cell as Scalar) as usize;
+    let start = BYTES_PER_PIXEL * ((N_PIXELS.y - 1 - coords.y) * image_width(topology) + column);
+    start..start + BYTES_PER_PIXEL * pixels_per_cell
 }
 
-const KE_FROM_AIR_FALLING: Scalar = N_PIXELS.y as Scalar * physical_properties::defaults::AIR.mass * -GRAVITY_ACCELERATION.y;
+/// Acceleration of the air falling the height of the grid that sets the fixed color scales.
+/// It's only a reference point for the colors, so it's the default gravity and doesn't follow the `Gravity` resource.
+const REFERENCE_FALL_ACCELERATION: Scalar = -GRAVITY_ACCELERATION.y;
+const KE_FROM_AIR_FALLING: Scalar = N_PIXELS.y as Scalar * physical_properties::defaults::AIR.mass * REFERENCE_FALL_ACCELERATION;
 const HIGH_AIR_TEMPERATURE: Scalar = physical_properties::calc::temperature_const(
     physical_properties::defaults::AIR.heat + KE_FROM_AIR_FALLING,
     physical_properties::defaults::AIR.mass,
     physical_properties::defaults::AIR.specific_heat
 );
+const HIGH_AIR_PRESSURE: Scalar = physical_properties::calc::pressure_const(physical_properties::defaults::AIR.heat + KE_FROM_AIR_FALLING);
+const AIR_FALLING_SPEED: Scalar = SoftF32(2.0 * KE_FROM_AIR_FALLING / physical_properties::defaults::AIR.mass).sqrt().to_f32();
 
 pub fn get_color(particle: &Particle) -> Color {
     match particle {
@@ -53,6 +337,73 @@ pub fn get_color(particle: &Particle) -> Color {
     }
 }
 
+/// Colors particles with physical properties by the field shown in `mode`, scaled to `range`.
+/// Other particles keep their material color.
+pub fn get_field_color(particle: &Particle, mode: RenderMode, range: &ColorRange) -> Color {
+    get_physical_properties(particle)
+        .and_then(|properties| mode.value(properties))
+        .map(|value| mode.colormap().sample(range.normalize(value)))
+        .unwrap_or_else(|| get_color(particle))
+}
+
+fn get_physical_properties(particle: &Particle) -> Option<&PhysicalProperties> {
+    match particle {
+        Particle::Air { physical_properties } | Particle::Water { physical_properties } => Some(physical_properties),
+        _ => None,
+    }
+}
+
 fn sigmoid(x: f32) -> f32 {
     (x.tanh() + 1.0) / 2.0
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sim::particle::defualts;
+
+    #[test]
+    fn modes_cycle() {
+        let mut mode = RenderMode::Material;
+        for _ in 0..RenderMode::ALL.len() {
+            mode = mode.next();
+        }
+        assert_eq!(mode, RenderMode::Material);
+    }
+
+    #[test]
+    fn range_of_grid() {
+        let mut grid = PropertyGrid::<Particle>::default();
+        *grid.get_mut(Coords::new(0, 0)) = defualts::AIR;
+        *grid.get_mut(Coords::new(1, 0)) = defualts::WATER;
+
+        let range = ColorRange::of_grid(RenderMode::Mass, &grid).unwrap();
+        assert_eq!(range, ColorRange { min: physical_properties::defaults::AIR.mass, max: physical_properties::defaults::WATER.mass });
+    }
+
+    #[test]
+    fn pixels_are_flipped_vertically() {
+        let topology = Topology::VonNeumann;
+        assert_eq!(cell_bytes(Coords::new(0, N_PIXELS.y - 1), topology), 0..BYTES_PER_PIXEL);
+        assert_eq!(cell_bytes(Coords::new(1, N_PIXELS.y - 1), topology).start, BYTES_PER_PIXEL);
+        assert_eq!(cell_bytes(Coords::new(0, 0), topology).start, BYTES_PER_PIXEL * N_PIXELS.x * (N_PIXELS.y - 1));
+    }
+
+    #[test]
+    fn shifted_rows_are_shifted_by_a_pixel() {
+        let topology = Topology::Hexagonal;
+        let row_bytes = BYTES_PER_PIXEL * image_width(topology);
+        assert_eq!(image_width(topology), 2 * N_PIXELS.x + 1);
+
+        let even_row = cell_bytes(Coords::new(1, N_PIXELS.y - 2), topology);
+        let odd_row = cell_bytes(Coords::new(1, N_PIXELS.y - 1), topology);
+        assert_eq!(even_row, row_bytes + 2 * BYTES_PER_PIXEL..row_bytes + 4 * BYTES_PER_PIXEL);
+        assert_eq!(odd_row, 3 * BYTES_PER_PIXEL..5 * BYTES_PER_PIXEL);
+    }
+
+    #[test]
+    fn range_of_empty_grid() {
+        let grid = PropertyGrid::<Particle>::default();
+        assert_eq!(ColorRange::of_grid(RenderMode::Temperature, &grid), None);
+    }
+}
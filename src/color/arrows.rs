@@ -0,0 +1,55 @@
+use bevy::prelude::*;
+
+use crate::camera::{cell_size, grid_to_camera};
+use crate::sim::topology::Topology;
+use crate::sim::types::{Scalar, Vector};
+use crate::sim::{Coords, PIXEL_SIZE};
+
+/// Arrows shorter than this many cells aren't drawn
+const MIN_ARROW_LENGTH: Scalar = 0.1;
+
+/// A lattice of arrows over a grid, each one showing the weighted mean of a vector over a square block of cells
+pub struct BlockArrows {
+    /// Width of the blocks, in cells
+    pub spacing: usize,
+    /// Length of an arrow, in cells, per unit of the mean
+    pub scale: Scalar,
+    pub color: Color,
+}
+
+impl BlockArrows {
+    /// Draws an arrow centered on each block of a grid with `dims`, for the mean of the vectors of the block's cells
+    /// weighted by their weights, both given by `weighted_vector`. Blocks without any weight get no arrow.
+    pub fn draw(&self, gizmos: &mut Gizmos, dims: Coords, topology: Topology, weighted_vector: impl Fn(Coords) -> (Vector, Scalar)) {
+        let n_blocks = Coords::new(dims.x.div_ceil(self.spacing), dims.y.div_ceil(self.spacing));
+
+        for block in Coords::ZERO.to(n_blocks) {
+            let lower = Coords::new(block.x * self.spacing, block.y * self.spacing);
+            let upper = Coords::new((lower.x + self.spacing).min(dims.x), (lower.y + self.spacing).min(dims.y));
+            let Some(mean) = weighted_mean(lower, upper, &weighted_vector) else {
+                continue;
+            };
+
+            let arrow = mean * self.scale;
+            if arrow.length() < MIN_ARROW_LENGTH {
+                continue;
+            }
+
+            // the center of the block, which is between cells when the spacing is even
+            let center = (grid_to_camera(lower, topology).xy() + grid_to_camera(upper, topology).xy() - cell_size(topology)) / 2.0;
+            let half_arrow = arrow * PIXEL_SIZE / 2.0;
+            gizmos.arrow_2d(center - half_arrow, center + half_arrow, self.color)
+                .with_tip_length(arrow.length().min(self.spacing as Scalar) * PIXEL_SIZE.x / 3.0);
+        }
+    }
+}
+
+/// The mean of the vectors of the cells from `lower` up to `upper` weighted by their weights,
+/// both given by `weighted_vector`, or `None` if the cells have no weight
+pub fn weighted_mean(lower: Coords, upper: Coords, weighted_vector: impl Fn(Coords) -> (Vector, Scalar)) -> Option<Vector> {
+    let (total, weight) = lower.to(upper)
+        .map(weighted_vector)
+        .fold((Vector::ZERO, 0.0), |(total, weight), (vector, cell_weight)| (total + vector * cell_weight, weight + cell_weight));
+
+    (weight > 0.0).then(|| total / weight)
+}
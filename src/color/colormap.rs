@@ -0,0 +1,69 @@
+use bevy::prelude::*;
+
+/// A sequence of colors, evenly spaced over `[0, 1]` and linearly interpolated between
+pub struct Colormap(&'static [Color]);
+
+impl Colormap {
+    /// Returns the color at `t`, which is clamped to `[0, 1]`
+    pub fn sample(&self, t: f32) -> Color {
+        let stops = self.0;
+        let t = if t.is_nan() { 0.0 } else { t.clamp(0.0, 1.0) };
+        let position = t * (stops.len() - 1) as f32;
+        let i = (position.floor() as usize).min(stops.len() - 2);
+        let frac = position - i as f32;
+
+        let [r0, g0, b0, _] = stops[i].as_rgba_f32();
+        let [r1, g1, b1, _] = stops[i + 1].as_rgba_f32();
+        let lerp = |a: f32, b: f32| a * (1.0 - frac) + b * frac;
+        Color::rgb(lerp(r0, r1), lerp(g0, g1), lerp(b0, b1))
+    }
+}
+
+pub const VIRIDIS: Colormap = Colormap(&[
+    Color::rgb(0.267, 0.005, 0.329),
+    Color::rgb(0.231, 0.322, 0.545),
+    Color::rgb(0.129, 0.569, 0.549),
+    Color::rgb(0.369, 0.788, 0.384),
+    Color::rgb(0.993, 0.906, 0.144),
+]);
+
+pub const INFERNO: Colormap = Colormap(&[
+    Color::rgb(0.001, 0.000, 0.014),
+    Color::rgb(0.258, 0.039, 0.406),
+    Color::rgb(0.578, 0.148, 0.404),
+    Color::rgb(0.865, 0.317, 0.226),
+    Color::rgb(0.988, 0.645, 0.040),
+    Color::rgb(0.988, 0.998, 0.645),
+]);
+
+pub const PLASMA: Colormap = Colormap(&[
+    Color::rgb(0.050, 0.030, 0.528),
+    Color::rgb(0.494, 0.012, 0.658),
+    Color::rgb(0.798, 0.280, 0.470),
+    Color::rgb(0.973, 0.585, 0.254),
+    Color::rgb(0.940, 0.975, 0.131),
+]);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn endpoints_are_first_and_last_stops() {
+        assert_eq!(VIRIDIS.sample(0.0), VIRIDIS.0[0]);
+        assert_eq!(VIRIDIS.sample(1.0), VIRIDIS.0[VIRIDIS.0.len() - 1]);
+    }
+
+    #[test]
+    fn out_of_range_is_clamped() {
+        assert_eq!(INFERNO.sample(-3.0), INFERNO.sample(0.0));
+        assert_eq!(INFERNO.sample(7.0), INFERNO.sample(1.0));
+        assert_eq!(INFERNO.sample(f32::NAN), INFERNO.sample(0.0));
+    }
+
+    #[test]
+    fn interpolates_between_stops() {
+        const COLORMAP: Colormap = Colormap(&[Color::rgb(0.0, 0.0, 0.0), Color::rgb(1.0, 0.5, 0.0)]);
+        assert_eq!(COLORMAP.sample(0.5), Color::rgb(0.5, 0.25, 0.0));
+    }
+}
@@ -0,0 +1,122 @@
+use bevy::prelude::*;
+
+use super::{ColorRange, RenderMode, Visualization};
+
+/// Number of swatches making up the colorbar
+const N_SWATCHES: usize = 32;
+const SWATCH_SIZE: Vec2 = Vec2::new(6.0, 12.0);
+
+pub struct LegendPlugin;
+
+impl Plugin for LegendPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_systems(Startup, setup_legend)
+            .add_systems(Update, update_legend.after(super::update_colors))
+        ;
+    }
+}
+
+#[derive(Component)]
+struct LegendRoot;
+
+#[derive(Component)]
+struct LegendTitle;
+
+/// The fraction of the colormap shown by a swatch of the colorbar
+#[derive(Component)]
+struct Swatch(f32);
+
+#[derive(Component)]
+struct LegendMin;
+
+#[derive(Component)]
+struct LegendMax;
+
+fn get_style() -> TextStyle {
+    TextStyle {
+        font_size: 16.0,
+        color: Color::WHITE,
+        ..default()
+    }
+}
+
+fn setup_legend(mut commands: Commands) {
+    commands.spawn((
+        LegendRoot,
+        NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                left: Val::Percent(1.0),
+                top: Val::Percent(15.0),
+                padding: UiRect::all(Val::Px(4.0)),
+                flex_direction: FlexDirection::Column,
+                ..default()
+            },
+            background_color: BackgroundColor(Color::BLACK.with_a(0.5)),
+            visibility: Visibility::Hidden,
+            ..default()
+        },
+    )).with_children(|legend| {
+        legend.spawn((LegendTitle, TextBundle::from_section("", get_style())));
+
+        legend.spawn(NodeBundle {
+            style: Style { margin: UiRect::vertical(Val::Px(4.0)), ..default() },
+            ..default()
+        }).with_children(|colorbar| {
+            for i in 0..N_SWATCHES {
+                colorbar.spawn((
+                    Swatch(i as f32 / (N_SWATCHES - 1) as f32),
+                    NodeBundle {
+                        style: Style {
+                            width: Val::Px(SWATCH_SIZE.x),
+                            height: Val::Px(SWATCH_SIZE.y),
+                            ..default()
+                        },
+                        ..default()
+                    },
+                ));
+            }
+        });
+
+        legend.spawn(NodeBundle {
+            style: Style { justify_content: JustifyContent::SpaceBetween, ..default() },
+            ..default()
+        }).with_children(|labels| {
+            labels.spawn((LegendMin, TextBundle::from_section("", get_style())));
+            labels.spawn((LegendMax, TextBundle::from_section("", get_style())));
+        });
+    });
+}
+
+/// Shows the colormap and the range of values it spans, except in `Material` mode
+fn update_legend(
+    visualization: Res<Visualization>,
+    color_range: Res<ColorRange>,
+    mut root: Query<&mut Visibility, With<LegendRoot>>,
+    mut title: Query<&mut Text, (With<LegendTitle>, Without<LegendMin>, Without<LegendMax>)>,
+    mut min: Query<&mut Text, (With<LegendMin>, Without<LegendMax>)>,
+    mut max: Query<&mut Text, (With<LegendMax>, Without<LegendMin>)>,
+    mut swatches: Query<(&Swatch, &mut BackgroundColor)>,
+) {
+    if !visualization.is_changed() && !color_range.is_changed() {
+        return;
+    }
+
+    let mode = visualization.mode;
+    *root.single_mut() = match mode {
+        RenderMode::Material => Visibility::Hidden,
+        _ => Visibility::Visible,
+    };
+
+    let scaling = if visualization.auto_scale { "auto" } else { "fixed" };
+    title.single_mut().sections[0].value = format!("{} ({scaling})", mode.name());
+
+    let unit = mode.unit();
+    min.single_mut().sections[0].value = format!("{:.3} {unit}", color_range.min);
+    max.single_mut().sections[0].value = format!("{:.3} {unit}", color_range.max);
+
+    for (swatch, mut color) in swatches.iter_mut() {
+        color.0 = mode.colormap().sample(swatch.0);
+    }
+}
@@ -0,0 +1,114 @@
+use bevy::prelude::*;
+
+use crate::sim::topology::Topology;
+use crate::sim::types::{Scalar, Vector};
+use crate::sim::{Coords, Particle, PropertyGrid};
+use super::arrows::BlockArrows;
+use super::get_physical_properties;
+
+const DEFAULT_SPACING: usize = 4;
+const MAX_SPACING: usize = 32;
+/// Length of an arrow, in cells, per unit of speed
+const DEFAULT_SCALE: Scalar = 4.0;
+
+const ARROW_COLOR: Color = Color::rgba(1.0, 1.0, 1.0, 0.8);
+
+pub struct VelocityOverlayPlugin;
+
+impl Plugin for VelocityOverlayPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<VelocityOverlay>()
+            .add_systems(Update, (handle_overlay_inputs, draw_velocity_overlay).chain())
+        ;
+    }
+}
+
+/// Arrows showing the velocity of the particles, each one averaged over a square block of cells
+#[derive(Resource, Debug)]
+pub struct VelocityOverlay {
+    pub enabled: bool,
+    /// Width of the blocks, in cells
+    pub spacing: usize,
+    /// Length of an arrow, in cells, per unit of speed
+    pub scale: Scalar,
+}
+
+impl Default for VelocityOverlay {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            spacing: DEFAULT_SPACING,
+            scale: DEFAULT_SCALE,
+        }
+    }
+}
+
+/// - `L` toggles the velocity overlay
+/// - `;`/`'` make the arrows sparser/denser
+fn handle_overlay_inputs(
+    mut overlay: ResMut<VelocityOverlay>,
+    keys: Res<ButtonInput<KeyCode>>,
+) {
+    if keys.just_pressed(KeyCode::KeyL) {
+        overlay.enabled = !overlay.enabled;
+    }
+    if keys.just_pressed(KeyCode::Semicolon) {
+        overlay.spacing = (overlay.spacing * 2).min(MAX_SPACING);
+    }
+    if keys.just_pressed(KeyCode::Quote) {
+        overlay.spacing = (overlay.spacing / 2).max(1);
+    }
+}
+
+/// Drawn every frame, so it also works while paused
+fn draw_velocity_overlay(
+    overlay: Res<VelocityOverlay>,
+    particle_grid: Query<&PropertyGrid<Particle>>,
+    topology: Res<Topology>,
+    mut gizmos: Gizmos,
+) {
+    if !overlay.enabled {
+        return;
+    }
+    let Ok(particle_grid) = particle_grid.get_single() else {
+        return;
+    };
+
+    let arrows = BlockArrows { spacing: overlay.spacing, scale: overlay.scale, color: ARROW_COLOR };
+    arrows.draw(&mut gizmos, particle_grid.dims(), *topology, |coords| mass_weighted_velocity(particle_grid, coords));
+}
+
+/// The velocity of the particle in the cell, weighted by its mass so that blocks average to their momentum over their mass
+fn mass_weighted_velocity(particle_grid: &PropertyGrid<Particle>, coords: Coords) -> (Vector, Scalar) {
+    get_physical_properties(particle_grid.get(coords))
+        .map_or((Vector::ZERO, 0.0), |properties| (properties.velocity(), properties.mass))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::arrows::weighted_mean;
+    use crate::sim::particle::defualts;
+
+    #[test]
+    fn average_velocity_is_mass_weighted() {
+        let mut grid = PropertyGrid::<Particle>::default();
+        let mut air = defualts::AIR;
+        air.physical_properties_mut().unwrap().set_velocity(Vector::new(1.0, 0.0));
+        let mut heavy_air = defualts::AIR;
+        heavy_air.physical_properties_mut().unwrap().set_mass(3.0);
+        heavy_air.physical_properties_mut().unwrap().set_velocity(Vector::new(0.0, 1.0));
+        *grid.get_mut(Coords::new(0, 0)) = air;
+        *grid.get_mut(Coords::new(1, 1)) = heavy_air;
+
+        let velocity = weighted_mean(Coords::new(0, 0), Coords::new(2, 2), |coords| mass_weighted_velocity(&grid, coords)).unwrap();
+        assert!((velocity - Vector::new(0.25, 0.75)).length() < 1e-6);
+    }
+
+    #[test]
+    fn empty_block_has_no_velocity() {
+        let grid = PropertyGrid::<Particle>::default();
+        assert_eq!(weighted_mean(Coords::new(4, 4), Coords::new(8, 8), |coords| mass_weighted_velocity(&grid, coords)), None);
+    }
+}
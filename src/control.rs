@@ -0,0 +1,324 @@
+//! A server on a localhost TCP port that lets scripts and other tools drive the simulation,
+//! with the protocol described in `protocol`.
+//!
+//! Each client is served on threads of its own, which pass the commands on to be run between frames
+//! and send back the responses, along with the statistics of each tick for subscribed clients.
+//! Cells written through the server are not part of the undo history or of recordings, so writing them stops any recording.
+
+pub mod protocol;
+
+use std::error::Error;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{Ipv4Addr, SocketAddr, TcpListener, TcpStream};
+use std::path::Path;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Mutex;
+use std::thread;
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::export;
+#[cfg(feature = "gui")]
+use crate::recording::UnrecordedChange;
+use crate::schedule::{self, SimSet, SimState, TickCount};
+use crate::sim::topology::Topology;
+use crate::sim::types::Vector;
+use crate::sim::{self, Coords, Particle, PropertyGrid};
+pub use protocol::{Command, ExportFormat, ParticleSpec, Response, TickStats};
+
+/// Serves the clients that connect to a listener bound by `bind`
+pub struct ControlPlugin {
+    listener: TcpListener,
+}
+
+impl ControlPlugin {
+    /// Listens on `port` of localhost, or on any free port if `port` is 0
+    pub fn bind(port: u16) -> io::Result<Self> {
+        Ok(Self { listener: TcpListener::bind((Ipv4Addr::LOCALHOST, port))? })
+    }
+}
+
+impl Plugin for ControlPlugin {
+    fn build(&self, app: &mut App) {
+        let listener = self.listener.try_clone().expect("couldn't take the control listener");
+        let local_addr = listener.local_addr().expect("a bound listener has an address");
+        let (commands, received) = mpsc::channel();
+        thread::spawn(move || accept_clients(listener, commands));
+        info!("listening for control commands on {local_addr}");
+
+        // for headless apps, which don't record anything
+        #[cfg(feature = "gui")]
+        app.add_event::<UnrecordedChange>();
+        app
+            .insert_resource(ControlServer {
+                local_addr,
+                commands: Mutex::new(received),
+                subscribers: vec![],
+            })
+            .add_systems(Update, handle_commands.in_set(SimSet::Draw))
+            .add_systems(
+                FixedUpdate,
+                send_tick_stats
+                    .after(schedule::count_tick)
+                    .run_if(in_state(SimState::Playing).or_else(in_state(SimState::Stepping))),
+            )
+        ;
+    }
+}
+
+#[derive(Resource)]
+pub struct ControlServer {
+    local_addr: SocketAddr,
+    commands: Mutex<Receiver<(Client, Command)>>,
+    subscribers: Vec<Client>,
+}
+
+impl ControlServer {
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+}
+
+/// A connected client, identified by the order it connected in
+#[derive(Clone)]
+struct Client {
+    id: usize,
+    responses: Sender<String>,
+}
+
+impl Client {
+    /// Returns whether the client is still connected
+    fn send(&self, response: &Response) -> bool {
+        let line = serde_json::to_string(response).expect("responses can always be serialized");
+        self.responses.send(line).is_ok()
+    }
+}
+
+/// The particles and force field, as saved and loaded by the `SaveScene` and `LoadScene` commands
+#[derive(Serialize, Deserialize)]
+struct Scene {
+    particles: PropertyGrid<Particle>,
+    force_field: PropertyGrid<Vector>,
+}
+
+impl Scene {
+    fn load(path: &str) -> Result<Self, Box<dyn Error>> {
+        Ok(ron::from_str(&std::fs::read_to_string(path)?)?)
+    }
+
+    fn save(&self, path: &str) -> Result<(), Box<dyn Error>> {
+        std::fs::write(path, ron::to_string(self)?)?;
+        Ok(())
+    }
+
+    /// Whether the simulation can run with every particle and impulse in the scene
+    fn is_valid(&self) -> bool {
+        self.particles.coords().all(|coords| self.particles.get(coords).is_valid())
+            && self.force_field.coords().all(|coords| self.force_field.get(coords).is_finite())
+    }
+}
+
+fn accept_clients(listener: TcpListener, commands: Sender<(Client, Command)>) {
+    for (id, stream) in listener.incoming().enumerate() {
+        let Ok(stream) = stream else {
+            continue;
+        };
+        let commands = commands.clone();
+        thread::spawn(move || serve_client(id, stream, commands));
+    }
+}
+
+/// Reads commands from the client until it disconnects, while a second thread writes the responses to it
+fn serve_client(id: usize, stream: TcpStream, commands: Sender<(Client, Command)>) -> io::Result<()> {
+    let (responses, outgoing) = mpsc::channel::<String>();
+    let mut writer = stream.try_clone()?;
+    thread::spawn(move || {
+        for line in outgoing {
+            if writeln!(writer, "{line}").is_err() {
+                break;
+            }
+        }
+    });
+
+    let client = Client { id, responses };
+    for line in BufReader::new(stream).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        match serde_json::from_str(&line) {
+            Ok(command) => if commands.send((client.clone(), command)).is_err() {
+                break;
+            },
+            Err(err) => {
+                client.send(&Response::error(format!("invalid command: {err}")));
+            },
+        }
+    }
+    Ok(())
+}
+
+fn handle_commands(world: &mut World) {
+    let commands = world.resource::<ControlServer>().commands.lock().unwrap().try_iter().collect::<Vec<_>>();
+    for (client, command) in commands {
+        let response = execute(world, &client, command).unwrap_or_else(Response::error);
+        client.send(&response);
+    }
+}
+
+fn execute(world: &mut World, client: &Client, command: Command) -> Result<Response, String> {
+    match command {
+        Command::Pause => world.resource_mut::<NextState<SimState>>().set(SimState::Paused),
+        Command::Play => world.resource_mut::<NextState<SimState>>().set(SimState::Playing),
+        Command::Step => world.resource_mut::<NextState<SimState>>().set(SimState::Stepping),
+        Command::Read { x, y, width, height } => {
+            let particles = world.query::<&PropertyGrid<Particle>>().single(world);
+            let (lower, upper) = rectangle(particles.dims(), x, y, width, height)?;
+            let cells = (lower.x..upper.x)
+                .map(|x| (lower.y..upper.y).map(|y| *particles.get(Coords::new(x, y))).collect())
+                .collect();
+            return Ok(Response::Cells { cells });
+        },
+        Command::Write { x, y, width, height, particle } => {
+            let particle = particle.resolve()?;
+            let mut particles = world.query::<&mut PropertyGrid<Particle>>().single_mut(world);
+            let (lower, upper) = rectangle(particles.dims(), x, y, width, height)?;
+            for coords in lower.to(upper) {
+                *particles.get_mut(coords) = particle;
+            }
+            #[cfg(feature = "gui")]
+            world.send_event(UnrecordedChange("a control command"));
+        },
+        Command::LoadScene { path } => {
+            let scene = Scene::load(&path).map_err(|err| format!("couldn't load scene {path}: {err}"))?;
+            let mut particles = world.query::<&mut PropertyGrid<Particle>>().single_mut(world);
+            if scene.particles.dims() != particles.dims() || scene.force_field.dims() != particles.dims() {
+                let dims = particles.dims();
+                return Err(format!("scene {path} doesn't fit the {}x{} grid", dims.x, dims.y));
+            }
+            if !scene.is_valid() {
+                return Err(format!("scene {path} has cells with values that can't be simulated"));
+            }
+            *particles = scene.particles;
+            *world.query::<&mut PropertyGrid<Vector>>().single_mut(world) = scene.force_field;
+            #[cfg(feature = "gui")]
+            world.send_event(UnrecordedChange("a control command"));
+        },
+        Command::SaveScene { path } => {
+            let scene = Scene {
+                particles: world.query::<&PropertyGrid<Particle>>().single(world).clone(),
+                force_field: world.query::<&PropertyGrid<Vector>>().single(world).clone(),
+            };
+            scene.save(&path).map_err(|err| format!("couldn't save scene {path}: {err}"))?;
+        },
+        Command::ExportFields { path, format } => {
+            let tick = world.resource::<TickCount>().0;
+            let topology = *world.resource::<Topology>();
+            let particles = world.query::<&PropertyGrid<Particle>>().single(world);
+            export::export_fields(particles, topology, tick, Path::new(&path), format)
+                .map_err(|err| format!("couldn't export the fields to {path}: {err}"))?;
+        },
+        Command::Subscribe => {
+            let mut server = world.resource_mut::<ControlServer>();
+            server.subscribers.retain(|subscriber| subscriber.id != client.id);
+            server.subscribers.push(client.clone());
+        },
+        Command::Unsubscribe => {
+            world.resource_mut::<ControlServer>().subscribers.retain(|subscriber| subscriber.id != client.id);
+        },
+    }
+    Ok(Response::Ok)
+}
+
+/// The lower corner and the corner past the upper one of the rectangle, if it is nonempty and fits in a grid of size `dims`
+fn rectangle(dims: Coords, x: usize, y: usize, width: usize, height: usize) -> Result<(Coords, Coords), String> {
+    let upper = Coords::new(x.saturating_add(width), y.saturating_add(height));
+    if width == 0 || height == 0 || upper.x > dims.x || upper.y > dims.y {
+        return Err(format!("the {width}x{height} rectangle at ({x}, {y}) isn't within the {}x{} grid", dims.x, dims.y));
+    }
+    Ok((Coords::new(x, y), upper))
+}
+
+fn send_tick_stats(
+    mut server: ResMut<ControlServer>,
+    particles: Query<&PropertyGrid<Particle>>,
+    tick_count: Res<TickCount>,
+) {
+    if server.subscribers.is_empty() {
+        return;
+    }
+
+    let particles = particles.single();
+    let (mut air_mass, mut water_mass) = (0.0, 0.0);
+    for coords in particles.coords() {
+        match particles.get(coords) {
+            Particle::Air { physical_properties } => air_mass += physical_properties.mass,
+            Particle::Water { physical_properties } => water_mass += physical_properties.mass,
+            Particle::Vacuum | Particle::Wall(_) => (),
+        }
+    }
+
+    let response = Response::Tick(TickStats {
+        tick: tick_count.0,
+        cell_counts: sim::cell_counts(particles),
+        air_mass,
+        water_mass,
+    });
+    server.subscribers.retain(|subscriber| subscriber.send(&response));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rectangle_must_fit_in_grid() {
+        let dims = Coords::new(8, 8);
+        assert_eq!(rectangle(dims, 2, 3, 6, 5), Ok((Coords::new(2, 3), Coords::new(8, 8))));
+        assert!(rectangle(dims, 2, 3, 7, 5).is_err());
+        assert!(rectangle(dims, 2, 3, 0, 5).is_err());
+        assert!(rectangle(dims, usize::MAX, 3, 2, 5).is_err());
+    }
+
+    #[test]
+    fn particles_are_written_by_name_or_in_full() {
+        let by_name: Command = serde_json::from_str(r#"{"command": "write", "x": 1, "y": 2, "particle": "water"}"#).unwrap();
+        let Command::Write { width: 1, height: 1, particle, .. } = by_name else {
+            panic!("expected a single cell write, got {by_name:?}");
+        };
+        assert_eq!(particle.resolve(), Ok(sim::particle::defualts::WATER));
+
+        let in_full: Command = serde_json::from_str(r#"{"command": "write", "x": 1, "y": 2, "particle": {"Wall": "Absorptive"}}"#).unwrap();
+        let Command::Write { particle, .. } = in_full else {
+            panic!("expected a write, got {in_full:?}");
+        };
+        assert_eq!(particle.resolve(), Ok(sim::particle::defualts::WALL_ABSORPTIVE));
+    }
+
+    #[test]
+    fn particles_that_cant_be_simulated_are_not_written() {
+        let mut water = sim::particle::defualts::WATER;
+        for invalidate in [
+            |properties: &mut sim::PhysicalProperties| properties.mass = f32::NAN,
+            |properties: &mut sim::PhysicalProperties| properties.mass = 0.0,
+            |properties: &mut sim::PhysicalProperties| properties.heat = -1.0,
+            |properties: &mut sim::PhysicalProperties| properties.internal_position.y = 1.0,
+        ] {
+            let mut invalid = water;
+            invalidate(invalid.physical_properties_mut().unwrap());
+            assert!(ParticleSpec::Particle(invalid).resolve().is_err(), "wrote {invalid:?}");
+        }
+        water.physical_properties_mut().unwrap().set_mass(3.0);
+        assert_eq!(ParticleSpec::Particle(water).resolve(), Ok(water));
+        assert!(ParticleSpec::Name("lava".into()).resolve().is_err());
+
+        let mut scene = Scene {
+            particles: PropertyGrid::with_dims(Coords::new(2, 2), |_| water),
+            force_field: PropertyGrid::with_dims(Coords::new(2, 2), |_| Vector::ZERO),
+        };
+        assert!(scene.is_valid());
+        *scene.force_field.get_mut(Coords::new(1, 0)) = Vector::new(f32::INFINITY, 0.0);
+        assert!(!scene.is_valid());
+    }
+}
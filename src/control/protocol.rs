@@ -0,0 +1,107 @@
+//! The messages of the control protocol. Each message is a JSON object on a line of its own.
+//!
+//! Commands are tagged by `"command"` and responses by `"response"`, e.g.
+//!
+//! ```text
+//! {"command": "write", "x": 10, "y": 20, "width": 5, "particle": "water"}
+//! {"response": "ok"}
+//! {"command": "read", "x": 10, "y": 20}
+//! {"response": "cells", "cells": [[{"Water": {"physical_properties": {...}}}]]}
+//! ```
+
+use serde::{Deserialize, Serialize};
+
+pub use crate::export::ExportFormat;
+use crate::sim::types::Scalar;
+use crate::sim::Particle;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "command", rename_all = "snake_case")]
+pub enum Command {
+    Pause,
+    Play,
+    /// Runs a single tick and pauses
+    Step,
+    /// Reads the cells of the rectangle whose lower-left cell is at `x`, `y`
+    Read {
+        x: usize,
+        y: usize,
+        #[serde(default = "one")]
+        width: usize,
+        #[serde(default = "one")]
+        height: usize,
+    },
+    /// Fills the rectangle whose lower-left cell is at `x`, `y` with `particle`
+    Write {
+        x: usize,
+        y: usize,
+        #[serde(default = "one")]
+        width: usize,
+        #[serde(default = "one")]
+        height: usize,
+        particle: ParticleSpec,
+    },
+    /// Loads the particles and force field of a scene file saved by `SaveScene`
+    LoadScene { path: String },
+    SaveScene { path: String },
+    /// Exports the physical fields to the directory at `path`, as described in `export`
+    ExportFields {
+        path: String,
+        #[serde(default)]
+        format: ExportFormat,
+    },
+    /// Sends a `Response::Tick` after every tick, until unsubscribed
+    Subscribe,
+    Unsubscribe,
+}
+
+fn one() -> usize {
+    1
+}
+
+/// A particle written in full, or the name of a particle to write with its default properties
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ParticleSpec {
+    Particle(Particle),
+    Name(String),
+}
+
+impl ParticleSpec {
+    /// The particle to write, unless there's none by that name or it has values that can't be simulated,
+    /// e.g. NaNs, a mass that isn't positive, or an internal position outside of its cell
+    pub fn resolve(&self) -> Result<Particle, String> {
+        match self {
+            Self::Particle(particle) if particle.is_valid() => Ok(*particle),
+            Self::Particle(particle) => Err(format!("particle {particle:?} has values that can't be simulated")),
+            Self::Name(name) => Particle::from_name(name).ok_or_else(|| format!("unknown particle {name}")),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "response", rename_all = "snake_case")]
+pub enum Response {
+    Ok,
+    /// The cells that were read, where `cells[i][j]` is the cell `i` to the right and `j` above the lower-left one
+    Cells { cells: Vec<Vec<Particle>> },
+    Tick(TickStats),
+    Error { message: String },
+}
+
+/// Statistics sent to subscribers after each tick
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TickStats {
+    /// Number of ticks simulated so far, including this one
+    pub tick: u64,
+    /// Number of cells holding each kind of particle, in the same order as the variants of `Particle`
+    pub cell_counts: [usize; 4],
+    pub air_mass: Scalar,
+    pub water_mass: Scalar,
+}
+
+impl Response {
+    pub fn error(message: impl Into<String>) -> Self {
+        Self::Error { message: message.into() }
+    }
+}
@@ -1,71 +1,193 @@
+mod budget;
+mod inspect;
 mod palette;
+mod tool;
+mod undo;
 
 use bevy::prelude::*;
-use rand::Rng;
+use bevy::ecs::schedule::SystemConfigs;
+use rand::{Rng, SeedableRng};
 
-use crate::camera::{camera_to_grid, window_to_camera};
+use crate::sim::force_field::Fan;
+use crate::sim::topology::Topology;
 use crate::sim::types::Vector;
-use crate::sim::{path, Particle, PropertyGrid};
+use crate::sim::{Coords, Particle, PropertyGrid};
 use crate::schedule::SimSet;
-use palette::ParticleToDraw;
-use rand::rngs::ThreadRng;
-
-#[derive(Component)]
-struct LastCursorCoords(Option<Vector>);
+pub(crate) use budget::PaintBudget;
+pub(crate) use palette::{FanToDraw, ParticleToDraw};
+pub(crate) use tool::{use_tool, Brush, Paint, PaintCells};
+pub(crate) use undo::{StrokeEvent, StrokeHistory};
+use tool::flood_fill;
+use rand::rngs::StdRng;
 
 pub struct DrawPlugin;
 
 impl Plugin for DrawPlugin {
     fn build(&self, app: &mut App) {
         app
+            .add_plugins(inspect::InspectPlugin)
             .add_plugins(palette::PalettePlugin)
-            .add_systems(Startup, add_last_cursor_coords)
-            .add_systems(Update, draw_particle.in_set(SimSet::Recolor));
+            .add_plugins(tool::ToolPlugin)
+            .add_plugins(undo::UndoPlugin)
+            .insert_resource(DrawRng::from_entropy())
+            .add_systems(Update, paint_systems().in_set(SimSet::Draw));
+    }
+}
+
+/// Randomness used when painting, kept separate so that a recorded session can be replayed exactly
+#[derive(Resource)]
+pub struct DrawRng(pub StdRng);
+
+impl DrawRng {
+    pub fn from_entropy() -> Self {
+        Self(StdRng::from_entropy())
+    }
+
+    pub fn from_seed(seed: u64) -> Self {
+        Self(StdRng::seed_from_u64(seed))
     }
 }
 
-fn add_last_cursor_coords(mut commands: Commands) {
-    commands.spawn(LastCursorCoords(None));
+/// The systems that apply `Paint` and `StrokeEvent`s to the grids
+pub(crate) fn paint_systems() -> SystemConfigs {
+    ((draw_particle, draw_fan), undo::apply_stroke_events).chain()
 }
 
-fn draw_particle(
+/// Paints the selected particle, or `Particle::Vacuum` when erasing
+pub(crate) fn draw_particle(
     particle_to_draw: Query<&ParticleToDraw>,
+    brush: Query<&Brush>,
     mut particle_grid: Query<&mut PropertyGrid<Particle>>,
-    mut last_cursor_coords: Query<&mut LastCursorCoords>,
-    cursor_input: Res<ButtonInput<MouseButton>>,
-    window: Query<&Window>,
-    camera: Query<&Transform, With<Camera>>,
+    topology: Res<Topology>,
+    mut stroke_history: Query<&mut StrokeHistory>,
+    mut paints: EventReader<Paint>,
+    mut rng: ResMut<DrawRng>,
+    mut budget: Option<ResMut<PaintBudget>>,
 ) {
-    let ParticleToDraw(Some(particle_to_draw)) = particle_to_draw.single() else {
-        return;
-    };
+    let particle_to_draw = particle_to_draw.single();
+    let brush = brush.single();
     let mut particle_grid = particle_grid.single_mut();
-    let mut last_cursor_coords = last_cursor_coords.single_mut();
+    let mut stroke_history = stroke_history.single_mut();
 
-    let window = window.single();
-    let camera = camera.single();
+    for paint in paints.read() {
+        paint_particle(
+            paint,
+            particle_to_draw.0,
+            brush.throw,
+            &mut particle_grid,
+            *topology,
+            &mut rng.0,
+            budget.as_deref_mut(),
+            |coords, before, after| stroke_history.record_particle(coords, before, after),
+        );
+    }
+}
 
-    if cursor_input.pressed(MouseButton::Left) {
-        if let Some(cursor_position) = window.cursor_position() {
-            let end = camera_to_grid(window_to_camera(cursor_position, window, camera));
-            let start = last_cursor_coords.0.unwrap_or(end);
+pub(crate) fn draw_fan(
+    fan_to_draw: Query<&FanToDraw>,
+    mut force_field: Query<&mut PropertyGrid<Vector>>,
+    topology: Res<Topology>,
+    mut stroke_history: Query<&mut StrokeHistory>,
+    mut paints: EventReader<Paint>,
+    mut budget: Option<ResMut<PaintBudget>>,
+) {
+    let FanToDraw(Some(fan_to_draw)) = fan_to_draw.single() else {
+        paints.clear();
+        return;
+    };
+    let mut force_field = force_field.single_mut();
+    let mut stroke_history = stroke_history.single_mut();
+
+    for paint in paints.read() {
+        paint_fan(
+            paint,
+            fan_to_draw,
+            &mut force_field,
+            *topology,
+            budget.as_deref_mut(),
+            |coords, before, after| stroke_history.record_fan(coords, before, after),
+        );
+    }
+}
 
-            let mut rng = rand::thread_rng();
+/// Paints `particle_to_draw`, or `Particle::Vacuum` when erasing, and calls `record` with each cell overwritten
+/// along with what it held before and after. When throwing, the painted particles move with the cursor.
+/// With a `PaintBudget`, walls are left alone, particles are painted with their default properties and without being thrown,
+/// and only cells that change to a kind other than vacuum are paid for, so erasing is free.
+pub(crate) fn paint_particle(
+    paint: &Paint,
+    particle_to_draw: Option<Particle>,
+    throw: bool,
+    particle_grid: &mut PropertyGrid<Particle>,
+    topology: Topology,
+    rng: &mut impl Rng,
+    mut budget: Option<&mut PaintBudget>,
+    mut record: impl FnMut(Coords, Particle, Particle),
+) {
+    let mut particle_to_draw = match (paint.erase, particle_to_draw) {
+        (true, _) => Particle::Vacuum,
+        (false, Some(particle_to_draw)) => particle_to_draw,
+        (false, None) => return,
+    };
+    if budget.is_some() {
+        particle_to_draw = particle_to_draw.with_default_properties();
+    }
+    if let (true, None, Some(physical_properties)) = (throw, &budget, particle_to_draw.physical_properties_mut()) {
+        physical_properties.set_velocity(paint.cursor_velocity);
+    }
 
-            for coords in path::get_path(start, end) {
-                if let Some(particle) = particle_grid.try_get_mut(coords) {
-                    *particle = randomize_internal_position(&mut rng, particle_to_draw.clone());
+    for coords in get_cells(paint, particle_grid, topology, |a, b| a.name() == b.name()) {
+        if let Some(particle) = particle_grid.try_get_mut(coords) {
+            if let Some(budget) = &mut budget {
+                if matches!(particle, Particle::Wall(_)) {
+                    continue;
+                }
+                if budget::is_paid_for(particle, &particle_to_draw) && !budget.spend(particle_to_draw.name()) {
+                    break;
                 }
             }
+            let drawn = randomize_internal_position(rng, particle_to_draw);
+            record(coords, *particle, drawn);
+            *particle = drawn;
+        }
+    }
+}
 
-            last_cursor_coords.0 = Some(end);
+/// Paints `fan_to_draw` unless erasing, and calls `record` like `paint_particle` does
+pub(crate) fn paint_fan(
+    paint: &Paint,
+    fan_to_draw: &Fan,
+    force_field: &mut PropertyGrid<Vector>,
+    topology: Topology,
+    mut budget: Option<&mut PaintBudget>,
+    mut record: impl FnMut(Coords, Vector, Vector),
+) {
+    if paint.erase {
+        return;
+    }
+
+    for coords in get_cells(paint, force_field, topology, |a, b| a == b) {
+        if let Some(impulse) = force_field.try_get_mut(coords) {
+            if let Some(budget) = &mut budget {
+                if *impulse != fan_to_draw.impulse && !budget.spend(fan_to_draw.name) {
+                    break;
+                }
+            }
+            record(coords, *impulse, fan_to_draw.impulse);
+            *impulse = fan_to_draw.impulse;
         }
-    } else {
-        last_cursor_coords.0 = None;
     }
 }
 
-fn randomize_internal_position(rng: &mut ThreadRng, mut particle: Particle) -> Particle {
+/// Returns the cells to paint on the given layer
+fn get_cells<T>(paint: &Paint, layer: &PropertyGrid<T>, topology: Topology, similar: impl Fn(&T, &T) -> bool) -> Vec<Coords> {
+    match &paint.cells {
+        PaintCells::Cells(cells) => cells.clone(),
+        PaintCells::FloodFill(start) => flood_fill(layer, *start, topology, similar),
+    }
+}
+
+fn randomize_internal_position(rng: &mut impl Rng, mut particle: Particle) -> Particle {
     if let Some(internal_position) = particle.physical_properties_mut() {
         internal_position.internal_position.x = rng.gen();
         internal_position.internal_position.y = rng.gen();
@@ -74,3 +196,48 @@ fn randomize_internal_position(rng: &mut ThreadRng, mut particle: Particle) -> P
         particle
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::*;
+    use crate::sim::particle::defualts;
+
+    fn paint(erase: bool) -> Paint {
+        Paint {
+            cells: PaintCells::Cells(vec![Coords::new(0, 0), Coords::new(1, 0), Coords::new(2, 0)]),
+            erase,
+            cursor_velocity: Vector::new(3.0, 0.0),
+        }
+    }
+
+    #[test]
+    fn budgeted_painting_uses_default_properties() {
+        let mut particle_grid = PropertyGrid::<Particle>::default();
+        let mut budget = PaintBudget::new(&BTreeMap::from([("Water".to_owned(), 3)]));
+        let mut heavy_water = defualts::WATER;
+        heavy_water.physical_properties_mut().unwrap().set_mass(100.0);
+
+        paint_particle(&paint(false), Some(heavy_water), true, &mut particle_grid, Topology::VonNeumann, &mut DrawRng::from_seed(0).0, Some(&mut budget), |_, _, _| ());
+
+        let physical_properties = particle_grid.get(Coords::new(1, 0)).physical_properties().unwrap();
+        assert_eq!(physical_properties.mass, defualts::WATER.physical_properties().unwrap().mass);
+        assert_eq!(physical_properties.velocity(), Vector::ZERO);
+        assert_eq!(budget.iter().collect::<Vec<_>>(), [("water", 0)]);
+    }
+
+    #[test]
+    fn budgeted_erasing_is_free() {
+        let mut particle_grid = PropertyGrid::<Particle>::default();
+        *particle_grid.get_mut(Coords::new(0, 0)) = defualts::WATER;
+        *particle_grid.get_mut(Coords::new(2, 0)) = defualts::AIR;
+        let mut budget = PaintBudget::new(&BTreeMap::from([("Water".to_owned(), 0)]));
+
+        paint_particle(&paint(true), Some(defualts::WATER), false, &mut particle_grid, Topology::VonNeumann, &mut DrawRng::from_seed(0).0, Some(&mut budget), |_, _, _| ());
+
+        assert!(*particle_grid.get(Coords::new(0, 0)) == defualts::VACUUM);
+        assert!(*particle_grid.get(Coords::new(2, 0)) == defualts::VACUUM);
+        assert_eq!(budget.iter().collect::<Vec<_>>(), [("water", 0)]);
+    }
+}
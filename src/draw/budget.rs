@@ -0,0 +1,74 @@
+use std::collections::BTreeMap;
+
+use bevy::prelude::*;
+
+use crate::sim::Particle;
+
+/// Limits painting to a number of cells of each palette entry, and keeps walls from being painted over.
+/// Painting is unlimited while this resource doesn't exist.
+#[derive(Resource, Clone, Debug, Default, PartialEq)]
+pub struct PaintBudget {
+    /// Cells left to paint of each palette entry, by lowercase name. Entries that aren't listed can't be painted.
+    remaining: BTreeMap<String, usize>,
+}
+
+impl PaintBudget {
+    pub fn new<'a>(budget: impl IntoIterator<Item = (&'a String, &'a usize)>) -> Self {
+        Self {
+            remaining: budget.into_iter().map(|(name, cells)| (name.to_lowercase(), *cells)).collect(),
+        }
+    }
+
+    /// Spends a cell of the palette entry `name`, or returns false if there are none left
+    pub fn spend(&mut self, name: &str) -> bool {
+        match self.remaining.get_mut(&name.to_lowercase()) {
+            Some(cells) if *cells > 0 => {
+                *cells -= 1;
+                true
+            },
+            _ => false,
+        }
+    }
+
+    /// Gives back a cell of the palette entry `name`, if it's one that can be painted
+    pub fn refund(&mut self, name: &str) {
+        if let Some(cells) = self.remaining.get_mut(&name.to_lowercase()) {
+            *cells += 1;
+        }
+    }
+
+    /// The palette entries that can be painted and the cells left of each, in alphabetical order
+    pub fn iter(&self) -> impl Iterator<Item = (&str, usize)> {
+        self.remaining.iter().map(|(name, cells)| (name.as_str(), *cells))
+    }
+}
+
+/// Whether painting `after` over `before` costs a cell, which it does unless the kind stays the same or it's erased
+pub(crate) fn is_paid_for(before: &Particle, after: &Particle) -> bool {
+    before.name() != after.name() && !matches!(after, Particle::Vacuum)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spending_stops_at_zero() {
+        let budget = BTreeMap::from([("Water".to_owned(), 2)]);
+        let mut budget = PaintBudget::new(&budget);
+        assert!(budget.spend("water"));
+        assert!(budget.spend("WATER"));
+        assert!(!budget.spend("Water"));
+        assert!(!budget.spend("Air"));
+        assert_eq!(budget.iter().collect::<Vec<_>>(), [("water", 0)]);
+    }
+
+    #[test]
+    fn refunds_only_go_to_listed_entries() {
+        let budget = BTreeMap::from([("Water".to_owned(), 0)]);
+        let mut budget = PaintBudget::new(&budget);
+        budget.refund("Water");
+        budget.refund("Vacuum");
+        assert_eq!(budget.iter().collect::<Vec<_>>(), [("water", 1)]);
+    }
+}
@@ -0,0 +1,150 @@
+use bevy::prelude::*;
+
+use crate::camera::{camera_to_grid, cell_size, grid_to_camera, window_to_camera};
+use crate::sim::topology::Topology;
+use crate::sim::{Coords, Particle, PropertyGrid, RelCoords};
+use super::palette::{get_full_details, get_style, get_text_color};
+use super::tool::{Brush, Tool};
+
+/// Offset of the hover tooltip from the cursor, in pixels
+const TOOLTIP_OFFSET: Vec2 = Vec2::new(16.0, 16.0);
+
+const PINNED_COLOR: Color = Color::YELLOW;
+
+pub struct InspectPlugin;
+
+impl Plugin for InspectPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_systems(Startup, setup_inspector)
+            .add_systems(Update, (handle_inspect_inputs, update_tooltip, update_pinned_inspector).chain())
+        ;
+    }
+}
+
+/// The cell clicked with the inspect tool, whose details are shown until it is unpinned
+#[derive(Component)]
+struct PinnedCell(Option<Coords>);
+
+#[derive(Component)]
+struct Tooltip;
+
+#[derive(Component)]
+struct PinnedInspector;
+
+fn setup_inspector(mut commands: Commands) {
+    commands.spawn(PinnedCell(None));
+
+    let panel = |marker: Style| NodeBundle {
+        style: Style {
+            position_type: PositionType::Absolute,
+            padding: UiRect::all(Val::Px(4.0)),
+            ..marker
+        },
+        background_color: BackgroundColor(Color::BLACK.with_a(0.7)),
+        visibility: Visibility::Hidden,
+        z_index: ZIndex::Global(i32::MAX - 1),
+        ..default()
+    };
+
+    commands.spawn((Tooltip, panel(Style::default()))).with_children(|tooltip| {
+        tooltip.spawn(TextBundle::from_sections([
+            TextSection::new("", get_style()),
+            TextSection::new("", get_style()),
+        ]));
+    });
+
+    commands.spawn((
+        PinnedInspector,
+        panel(Style { left: Val::Percent(1.0), bottom: Val::Percent(10.0), ..default() }),
+    )).with_children(|inspector| {
+        inspector.spawn(TextBundle::from_sections([
+            TextSection::new("", get_style()),
+            TextSection::new("", get_style()),
+        ]));
+    });
+}
+
+fn get_hovered_cell(window: &Window, camera: &Transform, topology: Topology) -> Option<Coords> {
+    let cursor_position = window.cursor_position()?;
+    Coords::try_from(RelCoords::from(camera_to_grid(window_to_camera(cursor_position, window, camera), topology))).ok()
+}
+
+/// With the inspect tool, left-clicking pins a cell and right-clicking unpins it
+fn handle_inspect_inputs(
+    brush: Query<&Brush>,
+    mut pinned: Query<&mut PinnedCell>,
+    particle_grid: Query<&PropertyGrid<Particle>>,
+    cursor_input: Res<ButtonInput<MouseButton>>,
+    window: Query<&Window>,
+    camera: Query<&Transform, With<Camera>>,
+    topology: Res<Topology>,
+) {
+    if brush.single().tool != Tool::Inspect {
+        return;
+    }
+
+    if cursor_input.just_pressed(MouseButton::Left) {
+        let hovered = get_hovered_cell(window.single(), camera.single(), *topology);
+        if hovered.is_some_and(|coords| particle_grid.single().try_get(coords).is_some()) {
+            pinned.single_mut().0 = hovered;
+        }
+    } else if cursor_input.just_pressed(MouseButton::Right) {
+        pinned.single_mut().0 = None;
+    }
+}
+
+/// While the inspect tool is selected, shows the details of the cell under the cursor next to the cursor
+fn update_tooltip(
+    brush: Query<&Brush>,
+    particle_grid: Query<&PropertyGrid<Particle>>,
+    mut tooltip: Query<(&mut Style, &mut Visibility, &Children), With<Tooltip>>,
+    mut texts: Query<&mut Text>,
+    window: Query<&Window>,
+    camera: Query<&Transform, With<Camera>>,
+    topology: Res<Topology>,
+) {
+    let (mut style, mut visibility, children) = tooltip.single_mut();
+    let window = window.single();
+
+    let hovered = get_hovered_cell(window, camera.single(), *topology)
+        .and_then(|coords| Some((coords, particle_grid.single().try_get(coords)?)));
+    let (Tool::Inspect, Some((coords, particle)), Some(cursor_position)) = (brush.single().tool, hovered, window.cursor_position()) else {
+        *visibility = Visibility::Hidden;
+        return;
+    };
+
+    *visibility = Visibility::Visible;
+    style.left = Val::Px(cursor_position.x + TOOLTIP_OFFSET.x);
+    style.top = Val::Px(cursor_position.y + TOOLTIP_OFFSET.y);
+    write_details(&mut texts.get_mut(children[0]).unwrap(), coords, particle);
+}
+
+/// Shows the details of the pinned cell, updated as it is simulated
+fn update_pinned_inspector(
+    pinned: Query<&PinnedCell>,
+    particle_grid: Query<&PropertyGrid<Particle>>,
+    mut inspector: Query<(&mut Visibility, &Children), With<PinnedInspector>>,
+    mut texts: Query<&mut Text>,
+    mut gizmos: Gizmos,
+    topology: Res<Topology>,
+) {
+    let (mut visibility, children) = inspector.single_mut();
+
+    let Some(coords) = pinned.single().0 else {
+        *visibility = Visibility::Hidden;
+        return;
+    };
+
+    *visibility = Visibility::Visible;
+    write_details(&mut texts.get_mut(children[0]).unwrap(), coords, particle_grid.single().get(coords));
+    gizmos.rect_2d(grid_to_camera(coords, *topology).xy(), 0.0, cell_size(*topology), PINNED_COLOR);
+}
+
+fn write_details(text: &mut Text, coords: Coords, particle: &Particle) {
+    let x = coords.x;
+    let y = coords.y;
+    text.sections[0].value = format!("({x}, {y}) ");
+    text.sections[1].value = format!("{}\n{}", particle.name(), get_full_details(particle));
+    text.sections[1].style.color = get_text_color(particle);
+}
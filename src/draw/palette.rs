@@ -1,8 +1,11 @@
 use bevy::prelude::*;
 
 use crate::sim::{particle, Particle, PhysicalProperties};
+use crate::sim::types::{Scalar, Vector};
+use crate::sim::force_field::{fans, Fan};
 use crate::sim::particle::Wall;
 use crate::color;
+use super::tool::{Brush, BrushShape, Tool};
 
 pub struct PalettePlugin;
 
@@ -10,7 +13,12 @@ impl Plugin for PalettePlugin {
     fn build(&self, app: &mut App) {
         app
             .add_systems(Startup, (setup_palette, setup_particle_to_draw, select_initial_particle).chain())
-            .add_systems(Update, (handle_buttons, update_palette).chain())
+            .add_systems(Update, (
+                highlight_buttons,
+                (handle_buttons, update_palette).chain(),
+                (handle_tool_buttons, update_tool_buttons).chain(),
+                handle_property_buttons.run_if(not(crate::puzzle::is_solving)).before(update_palette),
+            ))
         ;
     }
 }
@@ -24,12 +32,41 @@ struct PaletteTitle;
 #[derive(Component)]
 struct PaletteDetails;
 
+#[derive(Component)]
+struct PaletteBrushDetails;
+
+#[derive(Component)]
+struct PaletteButton;
+
+/// Adjusts a physical property of the particle to draw
+#[derive(Component, Clone, Copy)]
+enum PropertyStep {
+    /// Multiplies the mass by the given factor
+    Mass(Scalar),
+    /// Multiplies the temperature by the given factor
+    Temperature(Scalar),
+    /// Adds to the velocity
+    Velocity(Vector),
+    /// Restores the default properties
+    Reset,
+}
+
+#[derive(Component)]
+struct ThrowToggle;
+
+const MASS_STEP: Scalar = 1.25;
+const TEMPERATURE_STEP: Scalar = 1.25;
+const VELOCITY_STEP: Scalar = 0.1;
+
 #[derive(Component)]
 pub struct ParticleToDraw(pub Option<Particle>);
 
-const INITIAL_PARTICLE_TO_DRAW: &'static str = particle::names::AIR;
+#[derive(Component)]
+pub struct FanToDraw(pub Option<Fan>);
+
+const INITIAL_PARTICLE_TO_DRAW: &str = particle::names::AIR;
 
-fn get_style() -> TextStyle {
+pub(super) fn get_style() -> TextStyle {
     TextStyle {
         font_size: 16.0,
         color: Color::WHITE,
@@ -84,7 +121,7 @@ fn setup_palette(mut commands: Commands) {
                 style: Style {
                     display: Display::Grid,
                     grid_template_columns: RepeatedGridTrack::flex(4, 1.0),
-                    grid_template_rows: RepeatedGridTrack::flex(4, 1.0),
+                    grid_template_rows: RepeatedGridTrack::flex(18, 1.0),
                     min_width: Val::Percent(100.0),
                     width: Val::Percent(100.0),
                     margin: UiRect::top(Val::Px(20.0)),
@@ -93,12 +130,8 @@ fn setup_palette(mut commands: Commands) {
                 ..default()
             }
         ).with_children(|grid| {
-            grid.spawn(TextBundle {
-                text: Text::from_section("ELEMENTS", get_style()),
-                style: Style { grid_column: GridPlacement::span(4), ..default() },
-                ..default()
-            });
-            
+            spawn_header(grid, "ELEMENTS");
+
             let elements = [
                 particle::defualts::VACUUM,
                 particle::defualts::AIR,
@@ -106,51 +139,194 @@ fn setup_palette(mut commands: Commands) {
             ];
 
             for element in elements {
-                grid.spawn((
-                    ButtonBundle {
-                        background_color: BackgroundColor(Color::DARK_GRAY),
-                        style: Style {
-                            border: UiRect::all(Val::Px(2.0)),
-                            ..default()
-                        },
-                        ..default()
-                    },
-                    element,
-                )).with_children(|button| {
-                    button.spawn(TextBundle {
-                        text: Text::from_section(element.name(), TextStyle { color: get_text_color(&element), ..get_style() }),
-                        ..default()
-                    });
+                spawn_button(grid, element, element.name(), get_text_color(&element));
+            }
+
+            spawn_header(grid, "FANS");
+
+            for fan in fans::ALL {
+                spawn_button(grid, fan, fan.name, Color::WHITE);
+            }
+
+            spawn_header(grid, "TOOLS");
+
+            let tools = [
+                Tool::Freehand,
+                Tool::Line,
+                Tool::Rectangle,
+                Tool::FilledRectangle,
+                Tool::FloodFill,
+                Tool::Inspect,
+            ];
+
+            for tool in tools {
+                spawn_button(grid, tool, tool.name(), Color::WHITE);
+            }
+
+            spawn_header(grid, "BRUSH");
+
+            for shape in [BrushShape::Circle, BrushShape::Square] {
+                spawn_button(grid, shape, shape.name(), Color::WHITE);
+            }
+
+            spawn_header(grid, "PROPERTIES");
+
+            let steps = [
+                ("mass", PropertyStep::Mass(1.0 / MASS_STEP), PropertyStep::Mass(MASS_STEP)),
+                ("temperature", PropertyStep::Temperature(1.0 / TEMPERATURE_STEP), PropertyStep::Temperature(TEMPERATURE_STEP)),
+                ("velocity x", PropertyStep::Velocity(Vector::new(-VELOCITY_STEP, 0.0)), PropertyStep::Velocity(Vector::new(VELOCITY_STEP, 0.0))),
+                ("velocity y", PropertyStep::Velocity(Vector::new(0.0, -VELOCITY_STEP)), PropertyStep::Velocity(Vector::new(0.0, VELOCITY_STEP))),
+            ];
+
+            for (label, decrease, increase) in steps {
+                grid.spawn(TextBundle {
+                    text: Text::from_section(label, get_style()),
+                    style: Style { grid_column: GridPlacement::span(2), ..default() },
+                    ..default()
                 });
+                spawn_button(grid, decrease, "-", Color::WHITE);
+                spawn_button(grid, increase, "+", Color::WHITE);
             }
+
+            spawn_button(grid, PropertyStep::Reset, "Reset", Color::WHITE);
+            spawn_button(grid, ThrowToggle, "Throw", Color::WHITE);
+
+            grid.spawn((
+                PaletteBrushDetails,
+                TextBundle {
+                    text: Text::from_section("brush details go here", get_style()),
+                    style: Style { grid_column: GridPlacement::span(4), ..default() },
+                    ..default()
+                },
+            ));
+        });
+    });
+}
+
+fn spawn_header(grid: &mut ChildBuilder, header: &str) {
+    grid.spawn(TextBundle {
+        text: Text::from_section(header, get_style()),
+        style: Style { grid_column: GridPlacement::span(4), margin: UiRect::top(Val::Px(10.0)), ..default() },
+        ..default()
+    });
+}
+
+fn spawn_button(grid: &mut ChildBuilder, marker: impl Component, label: &str, text_color: Color) {
+    grid.spawn((
+        ButtonBundle {
+            background_color: BackgroundColor(Color::DARK_GRAY),
+            style: Style {
+                border: UiRect::all(Val::Px(2.0)),
+                ..default()
+            },
+            ..default()
+        },
+        PaletteButton,
+        marker,
+    )).with_children(|button| {
+        button.spawn(TextBundle {
+            text: Text::from_section(label, TextStyle { color: text_color, ..get_style() }),
+            ..default()
         });
     });
 }
 
+fn highlight_buttons(
+    mut interaction_query: Query<(&Interaction, &mut BackgroundColor), (Changed<Interaction>, With<PaletteButton>)>,
+) {
+    for (interaction, mut background_color) in &mut interaction_query {
+        background_color.0 = match *interaction {
+            Interaction::None => Color::DARK_GRAY,
+            Interaction::Hovered | Interaction::Pressed => Color::GRAY,
+        };
+    }
+}
+
 fn handle_buttons(
-    mut interaction_query: Query<
-        (&Interaction, &mut BackgroundColor, &Particle),
-        (Changed<Interaction>, With<Button>),
-    >,
+    interaction_query: Query<(&Interaction, AnyOf<(&Particle, &Fan)>), Changed<Interaction>>,
     mut particle_to_draw: Query<&mut ParticleToDraw>,
+    mut fan_to_draw: Query<&mut FanToDraw>,
 ) {
-    for (interaction, mut background_color, particle) in &mut interaction_query {
-        match *interaction {
-            Interaction::None => {
-                background_color.0 = Color::DARK_GRAY;
-            },
-            Interaction::Hovered => {
-                background_color.0 = Color::GRAY;
-            },
-            Interaction::Pressed => {
-                particle_to_draw.single_mut().0 = Some(*particle);
+    for (interaction, (particle, fan)) in &interaction_query {
+        if *interaction == Interaction::Pressed {
+            particle_to_draw.single_mut().0 = particle.copied();
+            fan_to_draw.single_mut().0 = fan.copied();
+        }
+    }
+}
+
+fn handle_tool_buttons(
+    interaction_query: Query<(&Interaction, AnyOf<(&Tool, &BrushShape, &ThrowToggle)>), Changed<Interaction>>,
+    mut brush: Query<&mut Brush>,
+) {
+    for (interaction, (tool, shape, throw)) in &interaction_query {
+        if *interaction == Interaction::Pressed {
+            let mut brush = brush.single_mut();
+            if let Some(tool) = tool {
+                brush.tool = *tool;
+            }
+            if let Some(shape) = shape {
+                brush.shape = *shape;
             }
+            if throw.is_some() {
+                brush.throw = !brush.throw;
+            }
+        }
+    }
+}
+
+fn handle_property_buttons(
+    interaction_query: Query<(&Interaction, &PropertyStep), Changed<Interaction>>,
+    mut particle_to_draw: Query<&mut ParticleToDraw>,
+) {
+    for (interaction, step) in &interaction_query {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+        let mut particle_to_draw = particle_to_draw.single_mut();
+        let Some(particle) = &mut particle_to_draw.0 else {
+            continue;
+        };
+        if let PropertyStep::Reset = step {
+            *particle = particle.with_default_properties();
+            continue;
+        }
+        let Some(properties) = particle.physical_properties_mut() else {
+            continue;
+        };
+        match *step {
+            PropertyStep::Mass(factor) => properties.set_mass(properties.mass * factor),
+            PropertyStep::Temperature(factor) => properties.set_temperature(properties.temperature() * factor),
+            PropertyStep::Velocity(delta) => properties.set_velocity(properties.velocity() + delta),
+            PropertyStep::Reset => (),
         }
     }
 }
 
+fn update_tool_buttons(
+    brush: Query<&Brush, Changed<Brush>>,
+    mut buttons: Query<(&mut BorderColor, AnyOf<(&Tool, &BrushShape, &ThrowToggle)>)>,
+    mut brush_details: Query<&mut Text, With<PaletteBrushDetails>>,
+) {
+    let Ok(brush) = brush.get_single() else {
+        return;
+    };
+
+    for (mut border_color, (tool, shape, throw)) in &mut buttons {
+        border_color.0 = if tool == Some(&brush.tool) || shape == Some(&brush.shape) || (throw.is_some() && brush.throw) {
+            Color::GRAY
+        } else {
+            Color::DARK_GRAY
+        };
+    }
+
+    let radius = brush.radius;
+    brush_details.single_mut().sections[0].value = format!("  - radius: {radius} (use [ ] or Ctrl+scroll)");
+}
+
 fn setup_particle_to_draw(mut commands: Commands) {
     commands.spawn(ParticleToDraw(None));
+    commands.spawn(FanToDraw(None));
 }
 
 fn select_initial_particle(
@@ -166,32 +342,41 @@ fn select_initial_particle(
 }
 
 fn update_palette(
-    particle_to_draw: Query<&ParticleToDraw, Changed<ParticleToDraw>>,
-    mut buttons: Query<(&mut BorderColor, &Particle), With<Button>>,
+    particle_to_draw: Query<Ref<ParticleToDraw>>,
+    fan_to_draw: Query<Ref<FanToDraw>>,
+    mut buttons: Query<(&mut BorderColor, AnyOf<(&Particle, &Fan)>)>,
     mut palette_title: Query<&mut Text, (With<PaletteTitle>, Without<PaletteDetails>)>,
     mut palette_details: Query<&mut Text, With<PaletteDetails>>,
 ) {
-    let Ok(ParticleToDraw(Some(particle_to_draw))) = particle_to_draw.get_single() else {
+    let particle_to_draw = particle_to_draw.single();
+    let fan_to_draw = fan_to_draw.single();
+    if !particle_to_draw.is_changed() && !fan_to_draw.is_changed() {
         return;
-    };
+    }
 
-    for (mut border_color, particle) in &mut buttons {
-        border_color.0 = if particle.name() == particle_to_draw.name() {
-            Color::GRAY
-        } else {
-            Color::DARK_GRAY
+    for (mut border_color, (particle, fan)) in &mut buttons {
+        border_color.0 = match (particle_to_draw.0, particle, fan_to_draw.0, fan) {
+            (Some(particle_to_draw), Some(particle), _, _) if particle.name() == particle_to_draw.name() => Color::GRAY,
+            (_, _, Some(fan_to_draw), Some(fan)) if fan.name == fan_to_draw.name => Color::GRAY,
+            _ => Color::DARK_GRAY,
         };
     }
 
     let mut palette_title = palette_title.single_mut();
     let mut palette_details = palette_details.single_mut();
 
-    palette_title.sections[1].value = particle_to_draw.name().into();
-    palette_title.sections[1].style.color = get_text_color(particle_to_draw);
-    palette_details.sections[0].value = get_details(particle_to_draw);
+    if let Some(particle_to_draw) = &particle_to_draw.0 {
+        palette_title.sections[1].value = particle_to_draw.name().into();
+        palette_title.sections[1].style.color = get_text_color(particle_to_draw);
+        palette_details.sections[0].value = get_details(particle_to_draw);
+    } else if let Some(fan_to_draw) = &fan_to_draw.0 {
+        palette_title.sections[1].value = fan_to_draw.name.into();
+        palette_title.sections[1].style.color = Color::WHITE;
+        palette_details.sections[0].value = fan_details(fan_to_draw);
+    }
 }
 
-fn get_text_color(particle: &Particle) -> Color {
+pub(super) fn get_text_color(particle: &Particle) -> Color {
     color::get_color(particle).with_a(1.0)
 }
 
@@ -204,6 +389,18 @@ fn get_details(particle: &Particle) -> String {
     }
 }
 
+/// Like `get_details`, but also including the internal state that can't be chosen when drawing
+pub(super) fn get_full_details(particle: &Particle) -> String {
+    match particle {
+        Particle::Air { physical_properties } | Particle::Water { physical_properties } => format!(
+            "{}\n{}",
+            physical_property_details(physical_properties),
+            internal_physical_property_details(physical_properties),
+        ),
+        particle => get_details(particle),
+    }
+}
+
 fn physical_property_details(properties: &PhysicalProperties) -> String {
     let mass = properties.mass;
     let velocity = properties.velocity();
@@ -218,6 +415,18 @@ fn physical_property_details(properties: &PhysicalProperties) -> String {
 ")
 }
 
+fn internal_physical_property_details(properties: &PhysicalProperties) -> String {
+    let heat = properties.heat;
+    let kinetic_energy = properties.kinetic_energy();
+    let px = properties.internal_position.x;
+    let py = properties.internal_position.y;
+    format!("\
+  - heat:        {heat:5.3} J
+  - kinetic:     {kinetic_energy:5.3} J
+  - position:    ({px:4.2}, {py:4.2})\
+")
+}
+
 fn wall_details(wall: &Wall) -> String {
     format!("\
 * Wall Properties
@@ -229,3 +438,12 @@ fn wall_details(wall: &Wall) -> String {
         }
     )
 }
+
+fn fan_details(fan: &Fan) -> String {
+    let ix = fan.impulse.x;
+    let iy = fan.impulse.y;
+    format!("\
+* Fan Properties
+  - impulse:     ({ix:4.2}, {iy:4.2}) kg m/s\
+")
+}
@@ -0,0 +1,371 @@
+use std::collections::{HashSet, VecDeque};
+
+use bevy::input::mouse::MouseWheel;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::camera::{camera_to_grid, cell_size, grid_to_camera, window_to_camera};
+use crate::sim::types::Vector;
+use crate::sim::topology::Topology;
+use crate::sim::{Coords, PropertyGrid, RelCoords};
+use crate::schedule::{SimSet, TickRate};
+
+const MAX_BRUSH_RADIUS: usize = 32;
+
+/// Fastest a thrown particle can go, in cells per tick
+const MAX_THROW_SPEED: f32 = 2.0;
+
+const PREVIEW_COLOR: Color = Color::WHITE;
+
+pub struct ToolPlugin;
+
+impl Plugin for ToolPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_event::<Paint>()
+            .add_systems(Startup, add_brush)
+            .add_systems(Update, (
+                (handle_brush_size_inputs, use_tool)
+                    .chain()
+                    .in_set(SimSet::Draw)
+                    .before(super::draw_particle)
+                    .before(super::draw_fan),
+                draw_tool_preview,
+            ))
+        ;
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Component)]
+pub enum Tool {
+    Freehand,
+    Line,
+    Rectangle,
+    FilledRectangle,
+    FloodFill,
+    /// Pins a cell to the inspector instead of painting
+    Inspect,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Component)]
+pub enum BrushShape {
+    Circle,
+    Square,
+}
+
+impl Tool {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Freehand => "Freehand",
+            Self::Line => "Line",
+            Self::Rectangle => "Rect",
+            Self::FilledRectangle => "Fill Rect",
+            Self::FloodFill => "Flood",
+            Self::Inspect => "Inspect",
+        }
+    }
+}
+
+impl BrushShape {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Circle => "Circle",
+            Self::Square => "Square",
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct Brush {
+    pub tool: Tool,
+    pub shape: BrushShape,
+    /// Number of cells the brush extends past its center, so a radius of 0 paints single cells
+    pub radius: usize,
+    /// Whether painted particles get the velocity of the cursor instead of their own
+    pub throw: bool,
+}
+
+impl Default for Brush {
+    fn default() -> Self {
+        Self {
+            tool: Tool::Freehand,
+            shape: BrushShape::Circle,
+            radius: 0,
+            throw: false,
+        }
+    }
+}
+
+/// The cursor state of the stroke in progress, if any
+#[derive(Component, Default)]
+pub(crate) struct ToolState {
+    button: Option<MouseButton>,
+    last_cursor_coords: Option<Vector>,
+    drag_start: Option<Vector>,
+}
+
+/// Sent when a tool is used. Left-clicking paints the selected particle or fan, and right-clicking erases.
+#[derive(Event, Clone, Serialize, Deserialize)]
+pub struct Paint {
+    pub cells: PaintCells,
+    pub erase: bool,
+    /// Velocity of the cursor, in cells per tick
+    pub cursor_velocity: Vector,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub enum PaintCells {
+    Cells(Vec<Coords>),
+    /// Paint the region connected to the given cell, where "connected" is up to the layer being painted
+    FloodFill(Coords),
+}
+
+fn add_brush(mut commands: Commands) {
+    commands.spawn(Brush::default());
+    commands.spawn(ToolState::default());
+}
+
+/// `[`/`]` and the scroll wheel shrink/grow the brush
+fn handle_brush_size_inputs(
+    mut brush: Query<&mut Brush>,
+    keys: Res<ButtonInput<KeyCode>>,
+    mut scrolls: EventReader<MouseWheel>,
+) {
+    let mut brush = brush.single_mut();
+
+    // scrolling without `Ctrl` zooms the camera instead
+    let mut delta = scrolls.read().map(|scroll| scroll.y.signum() as isize).sum::<isize>();
+    if !keys.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]) {
+        delta = 0;
+    }
+    if keys.just_pressed(KeyCode::BracketRight) {
+        delta += 1;
+    }
+    if keys.just_pressed(KeyCode::BracketLeft) {
+        delta -= 1;
+    }
+
+    if delta != 0 {
+        brush.radius = brush.radius.saturating_add_signed(delta).min(MAX_BRUSH_RADIUS);
+    }
+}
+
+pub(crate) fn use_tool(
+    brush: Query<&Brush>,
+    mut tool_state: Query<&mut ToolState>,
+    mut paints: EventWriter<Paint>,
+    cursor_input: Res<ButtonInput<MouseButton>>,
+    window: Query<&Window>,
+    camera: Query<&Transform, With<Camera>>,
+    topology: Res<Topology>,
+    time: Res<Time<Real>>,
+    tick_rate: Res<TickRate>,
+) {
+    let brush = brush.single();
+    let mut tool_state = tool_state.single_mut();
+    let window = window.single();
+
+    if tool_state.button.is_none() {
+        tool_state.button = [MouseButton::Left, MouseButton::Right].into_iter().find(|button| cursor_input.just_pressed(*button));
+    }
+    let Some(button) = tool_state.button else {
+        return;
+    };
+    let erase = button == MouseButton::Right;
+    let released = !cursor_input.pressed(button);
+
+    let cursor_coords = window.cursor_position()
+        .map(|cursor_position| camera_to_grid(window_to_camera(cursor_position, window, camera.single()), *topology))
+        .or(tool_state.last_cursor_coords);
+
+    if let Some(end) = cursor_coords {
+        let cells = match brush.tool {
+            Tool::Freehand => {
+                let start = tool_state.last_cursor_coords.unwrap_or(end);
+                Some(PaintCells::Cells(stamp(brush, topology.get_path(start, end))))
+            },
+            Tool::Line | Tool::Rectangle | Tool::FilledRectangle => {
+                let start = *tool_state.drag_start.get_or_insert(end);
+                released.then(|| PaintCells::Cells(match brush.tool {
+                    Tool::Line => stamp(brush, topology.get_path(start, end)),
+                    Tool::Rectangle => stamp(brush, rectangle_outline(start.into(), end.into())),
+                    _ => to_coords(filled_rectangle(start.into(), end.into())),
+                }))
+            },
+            Tool::FloodFill => cursor_input.just_pressed(button)
+                .then(|| Coords::try_from(RelCoords::from(end)).ok())
+                .flatten()
+                .map(PaintCells::FloodFill),
+            Tool::Inspect => None,
+        };
+
+        let ticks_this_frame = time.delta_seconds() * tick_rate.target_ticks_per_second() as f32;
+        let cursor_velocity = match tool_state.last_cursor_coords {
+            Some(start) if ticks_this_frame > 0.0 => ((end - start) / ticks_this_frame).clamp_length_max(MAX_THROW_SPEED),
+            _ => Vector::ZERO,
+        };
+
+        if let Some(cells) = cells {
+            paints.send(Paint { cells, erase, cursor_velocity });
+        }
+        tool_state.last_cursor_coords = Some(end);
+    }
+
+    if released {
+        *tool_state = ToolState::default();
+    }
+}
+
+/// Shows where the line or rectangle being dragged out will be painted
+fn draw_tool_preview(
+    brush: Query<&Brush>,
+    tool_state: Query<&ToolState>,
+    topology: Res<Topology>,
+    mut gizmos: Gizmos,
+) {
+    let brush = brush.single();
+    let tool_state = tool_state.single();
+    let (Some(start), Some(end)) = (tool_state.drag_start, tool_state.last_cursor_coords) else {
+        return;
+    };
+
+    // centers of the cells at either end
+    let start = grid_to_camera(RelCoords::from(start), *topology).xy();
+    let end = grid_to_camera(RelCoords::from(end), *topology).xy();
+    match brush.tool {
+        Tool::Line => gizmos.line_2d(start, end, PREVIEW_COLOR),
+        Tool::Rectangle | Tool::FilledRectangle => {
+            let size = (end - start).abs() + cell_size(*topology);
+            gizmos.rect_2d((start + end) / 2.0, 0.0, size, PREVIEW_COLOR);
+        },
+        Tool::Freehand | Tool::FloodFill | Tool::Inspect => (),
+    }
+}
+
+/// Replaces each cell with the brush's footprint centered on that cell, without duplicates or negative coordinates
+fn stamp(brush: &Brush, centers: impl IntoIterator<Item = RelCoords>) -> Vec<Coords> {
+    let r = brush.radius as isize;
+    let footprint = (-r..=r)
+        .flat_map(|dx| (-r..=r).map(move |dy| RelCoords::new(dx, dy)))
+        .filter(|delta| match brush.shape {
+            BrushShape::Square => true,
+            BrushShape::Circle => delta.x * delta.x + delta.y * delta.y <= r * (r + 1),
+        })
+        .collect::<Vec<_>>();
+
+    to_coords(centers.into_iter().flat_map(|center| footprint.iter().map(move |delta| center + *delta)))
+}
+
+fn rectangle_outline(a: RelCoords, b: RelCoords) -> Vec<RelCoords> {
+    let (lower, upper) = (RelCoords::new(a.x.min(b.x), a.y.min(b.y)), RelCoords::new(a.x.max(b.x), a.y.max(b.y)));
+    filled_rectangle(lower, upper)
+        .into_iter()
+        .filter(|coords| coords.x == lower.x || coords.x == upper.x || coords.y == lower.y || coords.y == upper.y)
+        .collect()
+}
+
+fn filled_rectangle(a: RelCoords, b: RelCoords) -> Vec<RelCoords> {
+    (a.x.min(b.x)..=a.x.max(b.x))
+        .flat_map(|x| (a.y.min(b.y)..=a.y.max(b.y)).map(move |y| RelCoords::new(x, y)))
+        .collect()
+}
+
+fn to_coords(cells: impl IntoIterator<Item = RelCoords>) -> Vec<Coords> {
+    let mut seen = HashSet::new();
+    cells.into_iter()
+        .filter_map(|coords| Coords::try_from(coords).ok())
+        .filter(|coords| seen.insert(*coords))
+        .collect()
+}
+
+/// Returns the cells connected to `start` through the neighbors of each cell in `topology` whose values are `similar`
+/// to the value at `start`, including `start` itself
+pub fn flood_fill<T>(grid: &PropertyGrid<T>, start: Coords, topology: Topology, similar: impl Fn(&T, &T) -> bool) -> Vec<Coords> {
+    let Some(target) = grid.try_get(start) else {
+        return vec![];
+    };
+
+    let mut visited = HashSet::from([start]);
+    let mut frontier = VecDeque::from([start]);
+    let mut region = vec![];
+
+    while let Some(coords) = frontier.pop_front() {
+        region.push(coords);
+        for dir in topology.neighbor_dirs(coords) {
+            let Ok(neighbor) = Coords::try_from(coords + *dir) else {
+                continue;
+            };
+            match grid.try_get(neighbor) {
+                Some(value) if similar(target, value) && visited.insert(neighbor) => frontier.push_back(neighbor),
+                _ => (),
+            }
+        }
+    }
+
+    region
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn brush(shape: BrushShape, radius: usize) -> Brush {
+        Brush { tool: Tool::Freehand, shape, radius, throw: false }
+    }
+
+    #[test]
+    fn stamp_sizes() {
+        let center = [RelCoords::new(10, 10)];
+        assert_eq!(stamp(&brush(BrushShape::Circle, 0), center).len(), 1);
+        assert_eq!(stamp(&brush(BrushShape::Square, 0), center).len(), 1);
+        assert_eq!(stamp(&brush(BrushShape::Square, 2), center).len(), 25);
+        assert_eq!(stamp(&brush(BrushShape::Circle, 1), center).len(), 9);
+        assert_eq!(stamp(&brush(BrushShape::Circle, 2), center).len(), 21);
+    }
+
+    #[test]
+    fn stamp_skips_duplicates_and_negatives() {
+        let cells = stamp(&brush(BrushShape::Square, 1), [RelCoords::new(0, 0), RelCoords::new(1, 0)]);
+        assert_eq!(cells.len(), 6);
+    }
+
+    #[test]
+    fn rectangle_outline_is_hollow() {
+        let outline = rectangle_outline(RelCoords::new(4, 4), RelCoords::new(0, 0));
+        assert_eq!(outline.len(), 16);
+        assert!(!outline.contains(&RelCoords::new(2, 2)));
+        assert_eq!(filled_rectangle(RelCoords::new(4, 4), RelCoords::new(0, 0)).len(), 25);
+    }
+
+    #[test]
+    fn flood_fill_stops_at_boundary() {
+        // a vertical wall at x = 5 splits the grid in two
+        let grid = PropertyGrid::new(|coords| coords.x == 5);
+        let region = flood_fill(&grid, Coords::new(0, 0), Topology::VonNeumann, |a, b| a == b);
+
+        assert_eq!(region.len(), 5 * grid.dims().y);
+        assert!(region.iter().all(|coords| coords.x < 5));
+    }
+
+    #[test]
+    fn flood_fill_follows_the_topology() {
+        // a diagonal line of walls from the bottom left corner, which only von Neumann neighbors can't get through
+        let grid = PropertyGrid::with_dims(Coords::new(6, 6), |coords| coords.x == coords.y);
+        let below = flood_fill(&grid, Coords::new(1, 0), Topology::VonNeumann, |a, b| a == b);
+        assert_eq!(below.len(), 15);
+        assert!(below.iter().all(|coords| coords.x > coords.y));
+        assert_eq!(flood_fill(&grid, Coords::new(1, 0), Topology::Moore, |a, b| a == b).len(), 30);
+    }
+
+    #[test]
+    fn flood_fill_on_hexagonal_grids() {
+        // odd rows are shifted right, so walls at x = 2 in even rows and x = 1 in odd rows form a zigzag
+        // that hexagonal neighbors can't get through, but diagonal Moore neighbors can
+        let grid = PropertyGrid::with_dims(Coords::new(5, 4), |coords| coords.x == 2 - coords.y % 2);
+        let left = flood_fill(&grid, Coords::new(0, 0), Topology::Hexagonal, |a, b| a == b);
+        assert_eq!(left.len(), 6);
+        assert!(left.iter().all(|coords| coords.x < 2 - coords.y % 2));
+
+        assert_eq!(flood_fill(&grid, Coords::new(0, 0), Topology::Moore, |a, b| a == b).len(), 16);
+    }
+}
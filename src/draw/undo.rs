@@ -0,0 +1,264 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::sim::force_field::fans;
+use crate::sim::types::Vector;
+use crate::sim::{Coords, Particle, PropertyGrid};
+use crate::schedule::SimSet;
+use super::budget::{is_paid_for, PaintBudget};
+
+/// Maximum number of strokes that can be undone
+const MAX_UNDO: usize = 100;
+
+pub struct UndoPlugin;
+
+impl Plugin for UndoPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_event::<StrokeEvent>()
+            .add_systems(Startup, add_stroke_history)
+            .add_systems(Update, (
+                handle_undo_inputs,
+                finish_stroke,
+            ).in_set(SimSet::Draw).before(apply_stroke_events))
+        ;
+    }
+}
+
+/// Sent to end the stroke in progress or to move through the stroke history.
+/// These are applied after the frame's painting, so a stroke finishes after its last cells are painted.
+#[derive(Event, Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum StrokeEvent {
+    Finish,
+    Undo,
+    Redo,
+}
+
+/// Every cell overwritten by a single mouse-down-to-mouse-up stroke, in the order they were overwritten
+#[derive(Default)]
+struct Stroke {
+    particles: Vec<(Coords, Particle, Particle)>,
+    fans: Vec<(Coords, Vector, Vector)>,
+}
+
+impl Stroke {
+    fn is_empty(&self) -> bool {
+        self.particles.is_empty() && self.fans.is_empty()
+    }
+
+    /// The palette entries that were paid for to paint the stroke, one for each cell
+    fn cost(&self) -> impl Iterator<Item = &'static str> + '_ {
+        let particles = self.particles.iter()
+            .filter(|(_, before, after)| is_paid_for(before, after))
+            .map(|(_, _, after)| after.name());
+        let fans = self.fans.iter()
+            .filter(|(_, before, after)| before != after)
+            .filter_map(|(_, _, after)| fans::ALL.iter().find(|fan| fan.impulse == *after).map(|fan| fan.name));
+        particles.chain(fans)
+    }
+
+    /// Restores the cells the stroke overwrote, leaving the rest of the grid as it is.
+    /// Cells overwritten more than once get the value they had before the stroke.
+    /// With a budget, each painted cell that still holds what was painted is refunded.
+    fn undo(&self, particle_grid: &mut PropertyGrid<Particle>, force_field: &mut PropertyGrid<Vector>, mut budget: Option<&mut PaintBudget>) {
+        for (coords, before, after) in self.particles.iter().rev() {
+            let particle = particle_grid.get_mut(*coords);
+            if let Some(budget) = &mut budget {
+                if is_paid_for(before, after) && particle.name() == after.name() {
+                    budget.refund(after.name());
+                }
+            }
+            *particle = *before;
+        }
+        for (coords, before, after) in self.fans.iter().rev() {
+            if let (Some(budget), Some(fan)) = (&mut budget, fans::ALL.iter().find(|fan| fan.impulse == *after)) {
+                if before != after && force_field.get(*coords) == after {
+                    budget.refund(fan.name);
+                }
+            }
+            *force_field.get_mut(*coords) = *before;
+        }
+    }
+
+    /// Paints the stroke again, or returns false without painting if the budget can't pay for all of it
+    fn redo(&self, particle_grid: &mut PropertyGrid<Particle>, force_field: &mut PropertyGrid<Vector>, budget: Option<&mut PaintBudget>) -> bool {
+        if let Some(budget) = budget {
+            let mut remaining = budget.clone();
+            if !self.cost().all(|name| remaining.spend(name)) {
+                return false;
+            }
+            *budget = remaining;
+        }
+        for (coords, _, after) in self.particles.iter() {
+            *particle_grid.get_mut(*coords) = *after;
+        }
+        for (coords, _, after) in self.fans.iter() {
+            *force_field.get_mut(*coords) = *after;
+        }
+        true
+    }
+}
+
+#[derive(Component, Default)]
+pub struct StrokeHistory {
+    current: Stroke,
+    done: Vec<Stroke>,
+    undone: Vec<Stroke>,
+}
+
+impl StrokeHistory {
+    pub fn record_particle(&mut self, coords: Coords, before: Particle, after: Particle) {
+        self.current.particles.push((coords, before, after));
+    }
+
+    pub fn record_fan(&mut self, coords: Coords, before: Vector, after: Vector) {
+        self.current.fans.push((coords, before, after));
+    }
+
+    /// Forgets every stroke, including the one in progress
+    pub fn clear(&mut self) {
+        *self = Self::default();
+    }
+
+    fn finish(&mut self) {
+        if self.current.is_empty() {
+            return;
+        }
+        self.done.push(std::mem::take(&mut self.current));
+        if self.done.len() > MAX_UNDO {
+            self.done.remove(0);
+        }
+        self.undone.clear();
+    }
+}
+
+fn add_stroke_history(mut commands: Commands) {
+    commands.spawn(StrokeHistory::default());
+}
+
+fn finish_stroke(
+    mut stroke_events: EventWriter<StrokeEvent>,
+    cursor_input: Res<ButtonInput<MouseButton>>,
+) {
+    if cursor_input.any_just_released([MouseButton::Left, MouseButton::Right]) {
+        stroke_events.send(StrokeEvent::Finish);
+    }
+}
+
+/// `Ctrl+Z` undoes the last stroke and `Ctrl+Shift+Z` redoes it
+fn handle_undo_inputs(
+    mut stroke_events: EventWriter<StrokeEvent>,
+    keys: Res<ButtonInput<KeyCode>>,
+    cursor_input: Res<ButtonInput<MouseButton>>,
+) {
+    if !keys.just_pressed(KeyCode::KeyZ)
+    || !keys.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight])
+    || cursor_input.any_pressed([MouseButton::Left, MouseButton::Right]) {
+        return;
+    }
+
+    if keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]) {
+        stroke_events.send(StrokeEvent::Redo);
+    } else {
+        stroke_events.send(StrokeEvent::Undo);
+    }
+}
+
+pub(super) fn apply_stroke_events(
+    mut stroke_history: Query<&mut StrokeHistory>,
+    mut particle_grid: Query<&mut PropertyGrid<Particle>>,
+    mut force_field: Query<&mut PropertyGrid<Vector>>,
+    mut stroke_events: EventReader<StrokeEvent>,
+    mut budget: Option<ResMut<PaintBudget>>,
+) {
+    let mut stroke_history = stroke_history.single_mut();
+    let mut particle_grid = particle_grid.single_mut();
+    let mut force_field = force_field.single_mut();
+
+    for stroke_event in stroke_events.read() {
+        match stroke_event {
+            StrokeEvent::Finish => stroke_history.finish(),
+            StrokeEvent::Undo => if let Some(stroke) = stroke_history.done.pop() {
+                stroke.undo(&mut particle_grid, &mut force_field, budget.as_deref_mut());
+                stroke_history.undone.push(stroke);
+            },
+            StrokeEvent::Redo => if let Some(stroke) = stroke_history.undone.pop() {
+                if stroke.redo(&mut particle_grid, &mut force_field, budget.as_deref_mut()) {
+                    stroke_history.done.push(stroke);
+                } else {
+                    stroke_history.undone.push(stroke);
+                }
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::*;
+    use crate::sim::particle::defualts;
+    use crate::zero::Zero;
+
+    #[test]
+    fn undo_restores_first_overwritten_value() {
+        let mut particle_grid = PropertyGrid::<Particle>::default();
+        let mut force_field = PropertyGrid::<Vector>::zero();
+        let coords = Coords::new(3, 4);
+
+        let mut stroke = Stroke::default();
+        for drawn in [defualts::AIR, defualts::WATER] {
+            stroke.particles.push((coords, *particle_grid.get(coords), drawn));
+            *particle_grid.get_mut(coords) = drawn;
+        }
+
+        stroke.undo(&mut particle_grid, &mut force_field, None);
+        assert!(*particle_grid.get(coords) == defualts::VACUUM);
+
+        stroke.redo(&mut particle_grid, &mut force_field, None);
+        assert!(*particle_grid.get(coords) == defualts::WATER);
+    }
+
+    #[test]
+    fn undo_leaves_other_cells_alone() {
+        let mut particle_grid = PropertyGrid::<Particle>::default();
+        let mut force_field = PropertyGrid::<Vector>::zero();
+        let drawn_coords = Coords::new(1, 1);
+        let simulated_coords = Coords::new(1, 2);
+
+        let mut stroke = Stroke::default();
+        stroke.particles.push((drawn_coords, defualts::VACUUM, defualts::WATER));
+        *particle_grid.get_mut(drawn_coords) = defualts::WATER;
+        *particle_grid.get_mut(simulated_coords) = defualts::AIR;
+
+        stroke.undo(&mut particle_grid, &mut force_field, None);
+        assert!(*particle_grid.get(drawn_coords) == defualts::VACUUM);
+        assert!(*particle_grid.get(simulated_coords) == defualts::AIR);
+    }
+
+    #[test]
+    fn undo_refunds_cells_still_painted() {
+        let mut particle_grid = PropertyGrid::<Particle>::default();
+        let mut force_field = PropertyGrid::<Vector>::zero();
+        let mut budget = PaintBudget::new(&BTreeMap::from([("Water".to_owned(), 0)]));
+
+        let mut stroke = Stroke::default();
+        for x in 0..3 {
+            stroke.particles.push((Coords::new(x, 0), defualts::VACUUM, defualts::WATER));
+            *particle_grid.get_mut(Coords::new(x, 0)) = defualts::WATER;
+        }
+        // one drop has flowed away since it was painted
+        *particle_grid.get_mut(Coords::new(2, 0)) = defualts::VACUUM;
+
+        stroke.undo(&mut particle_grid, &mut force_field, Some(&mut budget));
+        assert_eq!(budget.iter().collect::<Vec<_>>(), [("water", 2)]);
+
+        // redoing costs the whole stroke again
+        assert!(!stroke.redo(&mut particle_grid, &mut force_field, Some(&mut budget)));
+        assert!(*particle_grid.get(Coords::new(0, 0)) == defualts::VACUUM);
+        budget.refund("Water");
+        assert!(stroke.redo(&mut particle_grid, &mut force_field, Some(&mut budget)));
+        assert_eq!(budget.iter().collect::<Vec<_>>(), [("water", 0)]);
+    }
+}
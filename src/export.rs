@@ -0,0 +1,426 @@
+//! Exporting the physical fields of the grid for analysis elsewhere, e.g. in NumPy or ParaView, on demand or every few ticks.
+//!
+//! Each export is a directory holding an array for each field, either as a `.npy` file or as a CSV table,
+//! or holding all of them in a VTK `fields.vti` file, where the momentum is replaced by the velocity.
+//! Along with them is a `metadata.json` sidecar that lists the tick, the dimensions of the grid, and the unit of each field.
+//! Arrays have a row for each row of cells from the bottom up, so that `field[y, x]` is the cell at `x`, `y`.
+//!
+//! Exports to VTK every few ticks or on demand are also listed in a `fields.pvd` collection, which ParaView opens as a time series.
+
+mod npy;
+mod vtk;
+
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::schedule::{self, SimState, TickCount};
+use crate::sim::particle::names;
+use crate::sim::physical_properties::PhysicalProperties;
+use crate::sim::topology::Topology;
+use crate::sim::types::{Scalar, Vector};
+use crate::sim::{self, Particle, PropertyGrid};
+use vtk::ImageDataWriter;
+
+/// Names of the kinds of particles, indexed by their material IDs
+const MATERIALS: [&str; 4] = [names::VACUUM, names::AIR, names::WATER, names::WALL];
+
+/// A field of floats, along with what it holds for cells without physical properties
+struct Field {
+    name: &'static str,
+    unit: &'static str,
+    value: fn(&PhysicalProperties) -> Scalar,
+    without_properties: Scalar,
+}
+
+const SCALAR_FIELDS: [Field; 3] = [
+    Field { name: "mass", unit: "kg", value: |properties| properties.mass, without_properties: 0.0 },
+    Field { name: "heat", unit: "J", value: |properties| properties.heat, without_properties: 0.0 },
+    Field { name: "temperature", unit: "K", value: PhysicalProperties::temperature, without_properties: Scalar::NAN },
+];
+const MOMENTUM_FIELDS: [Field; 2] = [
+    Field { name: "momentum_x", unit: "kg m/s", value: |properties| properties.momentum.x, without_properties: 0.0 },
+    Field { name: "momentum_y", unit: "kg m/s", value: |properties| properties.momentum.y, without_properties: 0.0 },
+];
+/// The file in each export to VTK
+const VTK_FILE: &str = "fields.vti";
+/// The collection of the exports to VTK in the subdirectories of a directory
+const VTK_COLLECTION: &str = "fields.pvd";
+
+/// A type of the values of an array, as the formats describe it
+trait Element: Copy + ToString {
+    /// NumPy's description of the type, with its byte order
+    const NPY_DESCR: &'static str;
+    const VTK_TYPE: &'static str;
+
+    fn append_le_bytes(self, bytes: &mut Vec<u8>);
+}
+
+impl Element for f32 {
+    const NPY_DESCR: &'static str = "<f4";
+    const VTK_TYPE: &'static str = "Float32";
+
+    fn append_le_bytes(self, bytes: &mut Vec<u8>) {
+        bytes.extend(self.to_le_bytes());
+    }
+}
+
+impl Element for u8 {
+    const NPY_DESCR: &'static str = "|u1";
+    const VTK_TYPE: &'static str = "UInt8";
+
+    fn append_le_bytes(self, bytes: &mut Vec<u8>) {
+        bytes.push(self);
+    }
+}
+
+/// Exports the fields every `every` ticks if set, in addition to letting them be exported on demand
+pub struct ExportPlugin {
+    pub settings: ExportSettings,
+}
+
+impl Plugin for ExportPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .insert_resource(self.settings.clone())
+            .add_systems(Update, handle_export_inputs)
+            .add_systems(
+                FixedUpdate,
+                export_every_few_ticks
+                    .after(schedule::count_tick)
+                    .run_if(|settings: Res<ExportSettings>| settings.every.is_some())
+                    .run_if(in_state(SimState::Playing).or_else(in_state(SimState::Stepping))),
+            )
+        ;
+    }
+}
+
+#[derive(Resource, Clone, Debug)]
+pub struct ExportSettings {
+    /// The directory to write each export to a subdirectory of, named after its tick
+    pub dir: PathBuf,
+    pub format: ExportFormat,
+    /// Ticks from one export to the next, or `None` to only export on demand
+    pub every: Option<u64>,
+}
+
+impl Default for ExportSettings {
+    fn default() -> Self {
+        Self {
+            dir: PathBuf::from("exports"),
+            format: ExportFormat::Npy,
+            every: None,
+        }
+    }
+}
+
+impl ExportSettings {
+    /// Exports the fields as of `tick` to a subdirectory of `dir`, and returns its path.
+    /// Exports to VTK are added to the collection in `dir`.
+    pub fn export(&self, particles: &PropertyGrid<Particle>, topology: Topology, tick: u64) -> Result<PathBuf, Box<dyn Error>> {
+        let dir = self.dir.join(tick_dir_name(tick));
+        export_fields(particles, topology, tick, &dir, self.format)?;
+        if self.format == ExportFormat::Vtk {
+            write_collection(&self.dir)?;
+        }
+        Ok(dir)
+    }
+
+    /// Whether the fields are exported as of `tick` when exporting every few ticks
+    pub fn is_due(&self, tick: u64) -> bool {
+        self.every.is_some_and(|every| tick.is_multiple_of(every.max(1)))
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExportFormat {
+    #[default]
+    Npy,
+    Csv,
+    Vtk,
+}
+
+impl ExportFormat {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "npy" => Some(Self::Npy),
+            "csv" => Some(Self::Csv),
+            "vtk" => Some(Self::Vtk),
+            _ => None,
+        }
+    }
+
+    fn extension(&self) -> &'static str {
+        match self {
+            Self::Npy => "npy",
+            Self::Csv => "csv",
+            Self::Vtk => "vti",
+        }
+    }
+}
+
+/// The contents of `metadata.json`
+#[derive(Serialize)]
+struct Metadata {
+    tick: u64,
+    width: usize,
+    height: usize,
+    /// Shape of each array, i.e., its rows and columns
+    shape: [usize; 2],
+    topology: Topology,
+    format: ExportFormat,
+    fields: Vec<FieldMetadata>,
+    /// Names of the kinds of particles, indexed by the values of the `material` field
+    materials: [&'static str; 4],
+}
+
+#[derive(Serialize)]
+struct FieldMetadata {
+    name: &'static str,
+    file: String,
+    unit: &'static str,
+    dtype: &'static str,
+}
+
+impl FieldMetadata {
+    fn new(field: &Field, file: String) -> Self {
+        Self { name: field.name, file, unit: field.unit, dtype: "float32" }
+    }
+}
+
+fn tick_dir_name(tick: u64) -> String {
+    format!("tick-{tick:06}")
+}
+
+/// Writes the fields of the grid as of `tick` to `dir`, creating it if needed
+pub fn export_fields(
+    particles: &PropertyGrid<Particle>,
+    topology: Topology,
+    tick: u64,
+    dir: &Path,
+    format: ExportFormat,
+) -> Result<(), Box<dyn Error>> {
+    std::fs::create_dir_all(dir)?;
+    let dims = particles.dims();
+    // the cells row by row from the bottom, as the arrays hold them
+    let cells = (0..dims.y)
+        .flat_map(|y| (0..dims.x).map(move |x| sim::Coords::new(x, y)))
+        .map(|coords| particles.get(coords))
+        .collect::<Vec<_>>();
+
+    let fields = match format {
+        ExportFormat::Npy | ExportFormat::Csv => write_arrays(dir, format, dims.y, dims.x, &cells)?,
+        ExportFormat::Vtk => write_image_data(dir, dims.y, dims.x, &cells)?,
+    };
+
+    let metadata = Metadata {
+        tick,
+        width: dims.x,
+        height: dims.y,
+        shape: [dims.y, dims.x],
+        topology,
+        format,
+        fields,
+        materials: MATERIALS,
+    };
+    let mut writer = BufWriter::new(File::create(dir.join("metadata.json"))?);
+    serde_json::to_writer_pretty(&mut writer, &metadata)?;
+    writer.flush()?;
+    Ok(())
+}
+
+/// Writes each field to a file of its own
+fn write_arrays(
+    dir: &Path,
+    format: ExportFormat,
+    rows: usize,
+    columns: usize,
+    cells: &[&Particle],
+) -> Result<Vec<FieldMetadata>, Box<dyn Error>> {
+    let mut fields = vec![];
+    for field in SCALAR_FIELDS.iter().chain(&MOMENTUM_FIELDS) {
+        let file = format!("{}.{}", field.name, format.extension());
+        write_array(&dir.join(&file), format, rows, columns, &field_values(field, cells))?;
+        fields.push(FieldMetadata::new(field, file));
+    }
+    let file = format!("material.{}", format.extension());
+    write_array(&dir.join(&file), format, rows, columns, &material_ids(cells))?;
+    fields.push(FieldMetadata { name: "material", file, unit: "", dtype: "uint8" });
+    Ok(fields)
+}
+
+/// Writes the scalar fields and the velocity to a single VTK file
+fn write_image_data(dir: &Path, rows: usize, columns: usize, cells: &[&Particle]) -> Result<Vec<FieldMetadata>, Box<dyn Error>> {
+    let file = || VTK_FILE.to_string();
+    let mut image_data = ImageDataWriter::new(BufWriter::new(File::create(dir.join(VTK_FILE))?), columns, rows, "material", "velocity")?;
+    let mut fields = vec![];
+    for field in &SCALAR_FIELDS {
+        image_data.write_array(field.name, 1, &field_values(field, cells))?;
+        fields.push(FieldMetadata::new(field, file()));
+    }
+    image_data.write_array("material", 1, &material_ids(cells))?;
+    fields.push(FieldMetadata { name: "material", file: file(), unit: "", dtype: "uint8" });
+
+    // with a third component, since ParaView only shows vectors in 3D
+    let velocities = cells.iter()
+        .map(|particle| particle.physical_properties()
+            .filter(|properties| properties.mass != 0.0)
+            .map_or(Vector::ZERO, PhysicalProperties::velocity))
+        .flat_map(|velocity| [velocity.x, velocity.y, 0.0])
+        .collect::<Vec<_>>();
+    image_data.write_array("velocity", 3, &velocities)?;
+    fields.push(FieldMetadata { name: "velocity", file: file(), unit: "m/s", dtype: "float32" });
+    image_data.finish()?;
+    Ok(fields)
+}
+
+/// Lists the exports to VTK in the subdirectories of `dir` in its collection, in the order of their ticks
+fn write_collection(dir: &Path) -> Result<(), Box<dyn Error>> {
+    let mut datasets = vec![];
+    for entry in std::fs::read_dir(dir)? {
+        let name = entry?.file_name();
+        let tick = name.to_str()
+            .and_then(|name| name.strip_prefix("tick-"))
+            .and_then(|tick| tick.parse::<u64>().ok());
+        if let Some(tick) = tick.filter(|tick| dir.join(tick_dir_name(*tick)).join(VTK_FILE).is_file()) {
+            datasets.push((tick, format!("{}/{VTK_FILE}", tick_dir_name(tick))));
+        }
+    }
+    datasets.sort();
+    vtk::write_collection(BufWriter::new(File::create(dir.join(VTK_COLLECTION))?), &datasets)?;
+    Ok(())
+}
+
+fn field_values(field: &Field, cells: &[&Particle]) -> Vec<Scalar> {
+    cells.iter()
+        .map(|particle| particle.physical_properties().map_or(field.without_properties, field.value))
+        .collect()
+}
+
+fn material_ids(cells: &[&Particle]) -> Vec<u8> {
+    cells.iter().map(|particle| sim::material_id(particle) as u8).collect()
+}
+
+fn write_array<T: Element>(
+    path: &Path,
+    format: ExportFormat,
+    rows: usize,
+    columns: usize,
+    values: &[T],
+) -> Result<(), Box<dyn Error>> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    match format {
+        ExportFormat::Npy => npy::write(writer, rows, columns, values)?,
+        ExportFormat::Vtk => unreachable!("VTK files hold all the fields"),
+        ExportFormat::Csv => {
+            for row in values.chunks(columns.max(1)) {
+                let row = row.iter().map(ToString::to_string).collect::<Vec<_>>();
+                writeln!(writer, "{}", row.join(","))?;
+            }
+            writer.flush()?;
+        },
+    }
+    Ok(())
+}
+
+/// `F8` exports the fields as they are now
+fn handle_export_inputs(
+    settings: Res<ExportSettings>,
+    particles: Query<&PropertyGrid<Particle>>,
+    topology: Res<Topology>,
+    tick_count: Res<TickCount>,
+    keys: Res<ButtonInput<KeyCode>>,
+) {
+    if keys.just_pressed(KeyCode::F8) {
+        export(&settings, particles.single(), *topology, tick_count.0);
+    }
+}
+
+fn export_every_few_ticks(
+    settings: Res<ExportSettings>,
+    particles: Query<&PropertyGrid<Particle>>,
+    topology: Res<Topology>,
+    tick_count: Res<TickCount>,
+) {
+    if settings.is_due(tick_count.0) {
+        export(&settings, particles.single(), *topology, tick_count.0);
+    }
+}
+
+fn export(settings: &ExportSettings, particles: &PropertyGrid<Particle>, topology: Topology, tick: u64) {
+    match settings.export(particles, topology, tick) {
+        Ok(dir) => info!("exported the fields to {}", dir.display()),
+        Err(err) => error!("couldn't export the fields to {}: {err}", settings.dir.display()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sim::particle::defualts;
+    use crate::sim::Coords;
+
+    #[test]
+    fn arrays_hold_the_rows_from_the_bottom_up() {
+        let dir = std::env::temp_dir().join(format!("dust-export-{}", std::process::id()));
+        let mut particles = PropertyGrid::with_dims(Coords::new(3, 2), |_| defualts::VACUUM);
+        *particles.get_mut(Coords::new(2, 0)) = defualts::WATER;
+        *particles.get_mut(Coords::new(0, 1)) = defualts::WALL_REFLECTIVE;
+        let settings = ExportSettings { dir: dir.clone(), format: ExportFormat::Csv, every: Some(5) };
+        assert!(settings.is_due(10) && !settings.is_due(11));
+
+        let exported = settings.export(&particles, Topology::VonNeumann, 10).unwrap();
+        assert_eq!(exported, dir.join("tick-000010"));
+        let material = std::fs::read_to_string(exported.join("material.csv")).unwrap();
+        assert_eq!(material, "0,0,2\n3,0,0\n");
+        let mass = std::fs::read_to_string(exported.join("mass.csv")).unwrap();
+        let water_mass = defualts::WATER.physical_properties().unwrap().mass;
+        assert_eq!(mass.lines().next().unwrap(), format!("0,0,{water_mass}"));
+        let temperature = std::fs::read_to_string(exported.join("temperature.csv")).unwrap();
+        assert!(temperature.starts_with("NaN,NaN,"));
+
+        let metadata: serde_json::Value = serde_json::from_reader(File::open(exported.join("metadata.json")).unwrap()).unwrap();
+        assert_eq!(metadata["tick"], 10);
+        assert_eq!(metadata["shape"], serde_json::json!([2, 3]));
+        assert_eq!(metadata["fields"][0]["unit"], "kg");
+
+        let exported = ExportSettings { format: ExportFormat::Npy, ..settings }.export(&particles, Topology::VonNeumann, 11).unwrap();
+        let material = std::fs::read(exported.join("material.npy")).unwrap();
+        assert_eq!(&material[material.len() - 6..], &[0, 0, 2, 3, 0, 0]);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn exports_to_vtk_are_collected_in_order() {
+        let dir = std::env::temp_dir().join(format!("dust-export-vtk-{}", std::process::id()));
+        let particles = PropertyGrid::with_dims(Coords::new(4, 3), |_| defualts::AIR);
+        let settings = ExportSettings { dir: dir.clone(), format: ExportFormat::Vtk, every: None };
+        for tick in [12, 3] {
+            settings.export(&particles, Topology::VonNeumann, tick).unwrap();
+        }
+
+        let image_data = std::fs::read_to_string(dir.join("tick-000012").join(VTK_FILE)).unwrap();
+        let document = roxmltree::Document::parse(&image_data).unwrap();
+        let names = document.descendants()
+            .filter(|node| node.has_tag_name("DataArray"))
+            .map(|node| node.attribute("Name").unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["mass", "heat", "temperature", "material", "velocity"]);
+
+        let collection = std::fs::read_to_string(dir.join(VTK_COLLECTION)).unwrap();
+        let document = roxmltree::Document::parse(&collection).unwrap();
+        let files = document.descendants()
+            .filter(|node| node.has_tag_name("DataSet"))
+            .map(|node| node.attribute("file").unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(files, ["tick-000003/fields.vti", "tick-000012/fields.vti"]);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
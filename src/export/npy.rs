@@ -0,0 +1,54 @@
+//! Writing 2D arrays in NumPy's `.npy` format, version 1.0, which `numpy.load` reads as is.
+
+use std::io::{self, Write};
+
+use super::Element;
+
+const MAGIC: &[u8] = b"\x93NUMPY";
+/// The header is padded so that the data after it is aligned to this many bytes
+const ALIGNMENT: usize = 64;
+
+/// Writes an array of `rows` rows of `columns` values each, given row by row
+pub fn write<T: Element>(mut writer: impl Write, rows: usize, columns: usize, values: &[T]) -> io::Result<()> {
+    assert_eq!(values.len(), rows * columns, "the values have to fill the array");
+
+    let mut header = format!("{{'descr': '{}', 'fortran_order': False, 'shape': ({rows}, {columns}), }}", T::NPY_DESCR);
+    // the magic string, the version, and the header's length come before it, and it ends with a newline
+    let preamble = MAGIC.len() + 2 + 2;
+    let padding = (ALIGNMENT - (preamble + header.len() + 1) % ALIGNMENT) % ALIGNMENT;
+    header.extend(std::iter::repeat_n(' ', padding));
+    header.push('\n');
+
+    writer.write_all(MAGIC)?;
+    writer.write_all(&[1, 0])?;
+    writer.write_all(&(header.len() as u16).to_le_bytes())?;
+    writer.write_all(header.as_bytes())?;
+
+    let mut bytes = Vec::with_capacity(std::mem::size_of_val(values));
+    for &value in values {
+        value.append_le_bytes(&mut bytes);
+    }
+    writer.write_all(&bytes)?;
+    writer.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn data_starts_aligned_after_the_header() {
+        let mut bytes = vec![];
+        write(&mut bytes, 2, 3, &[0.0f32, 1.0, 2.0, 3.0, 4.0, f32::NAN]).unwrap();
+
+        assert!(bytes.starts_with(MAGIC));
+        let header_len = u16::from_le_bytes([bytes[8], bytes[9]]) as usize;
+        let data_start = 10 + header_len;
+        assert_eq!(data_start % ALIGNMENT, 0);
+        let header = std::str::from_utf8(&bytes[10..data_start]).unwrap();
+        assert!(header.starts_with("{'descr': '<f4', 'fortran_order': False, 'shape': (2, 3), }"));
+        assert!(header.ends_with('\n'));
+        assert_eq!(bytes.len(), data_start + 6 * 4);
+        assert_eq!(&bytes[data_start + 4..data_start + 8], &1.0f32.to_le_bytes());
+    }
+}
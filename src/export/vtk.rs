@@ -0,0 +1,113 @@
+//! Writing the fields in VTK's XML formats, which ParaView opens: image data (`.vti`) holding the grid as of a tick,
+//! and collections (`.pvd`) listing such files as a time series.
+//!
+//! Arrays are stored as base64 in the XML itself, since VTK doesn't read NaNs back from text.
+//! Hexagonal grids are stored as square ones, without their rows shifted.
+
+use std::io::{self, Write};
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+
+use super::Element;
+
+/// Writes image data with a value, or a vector of values, for each cell of a grid
+pub struct ImageDataWriter<W: Write> {
+    writer: W,
+    cells: usize,
+}
+
+impl<W: Write> ImageDataWriter<W> {
+    /// Starts image data of `width` by `height` cells, where `scalars` and `vectors` name the arrays that ParaView shows by default
+    pub fn new(mut writer: W, width: usize, height: usize, scalars: &str, vectors: &str) -> io::Result<Self> {
+        writeln!(writer, r#"<?xml version="1.0"?>"#)?;
+        writeln!(writer, r#"<VTKFile type="ImageData" version="1.0" byte_order="LittleEndian" header_type="UInt32">"#)?;
+        writeln!(writer, r#"  <ImageData WholeExtent="0 {width} 0 {height} 0 0" Origin="0 0 0" Spacing="1 1 1">"#)?;
+        writeln!(writer, r#"    <Piece Extent="0 {width} 0 {height} 0 0">"#)?;
+        writeln!(writer, r#"      <CellData Scalars="{scalars}" Vectors="{vectors}">"#)?;
+        Ok(Self { writer, cells: width * height })
+    }
+
+    /// Writes an array with `components` values for each cell, given cell by cell and row by row from the bottom
+    pub fn write_array<T: Element>(&mut self, name: &str, components: usize, values: &[T]) -> io::Result<()> {
+        assert_eq!(values.len(), components * self.cells, "the values have to fill the grid");
+        let mut bytes = Vec::with_capacity(4 + std::mem::size_of_val(values));
+        // preceded by its length in bytes
+        bytes.extend((std::mem::size_of_val(values) as u32).to_le_bytes());
+        for &value in values {
+            value.append_le_bytes(&mut bytes);
+        }
+        writeln!(
+            self.writer,
+            r#"        <DataArray type="{}" Name="{name}" NumberOfComponents="{components}" format="binary">{}</DataArray>"#,
+            T::VTK_TYPE,
+            STANDARD.encode(&bytes),
+        )
+    }
+
+    pub fn finish(mut self) -> io::Result<()> {
+        writeln!(self.writer, "      </CellData>")?;
+        writeln!(self.writer, "    </Piece>")?;
+        writeln!(self.writer, "  </ImageData>")?;
+        writeln!(self.writer, "</VTKFile>")?;
+        self.writer.flush()
+    }
+}
+
+/// Writes a collection of the files in `datasets`, each given by its time step and its path relative to the collection
+pub(crate) fn write_collection(mut writer: impl Write, datasets: &[(u64, String)]) -> io::Result<()> {
+    writeln!(writer, r#"<?xml version="1.0"?>"#)?;
+    writeln!(writer, r#"<VTKFile type="Collection" version="1.0" byte_order="LittleEndian">"#)?;
+    writeln!(writer, "  <Collection>")?;
+    for (timestep, file) in datasets {
+        writeln!(writer, r#"    <DataSet timestep="{timestep}" part="0" file="{file}"/>"#)?;
+    }
+    writeln!(writer, "  </Collection>")?;
+    writeln!(writer, "</VTKFile>")?;
+    writer.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn image_data_is_well_formed() {
+        let mut bytes = vec![];
+        let mut image_data = ImageDataWriter::new(&mut bytes, 3, 2, "mass", "velocity").unwrap();
+        image_data.write_array("mass", 1, &[0.0f32, 1.0, 2.0, 3.0, 4.0, f32::NAN]).unwrap();
+        image_data.write_array("velocity", 3, &[1.5f32; 18]).unwrap();
+        image_data.finish().unwrap();
+
+        let xml = String::from_utf8(bytes).unwrap();
+        let document = roxmltree::Document::parse(&xml).unwrap();
+        let root = document.root_element();
+        assert_eq!(root.attribute("type"), Some("ImageData"));
+        let image_data = root.first_element_child().unwrap();
+        assert_eq!(image_data.attribute("WholeExtent"), Some("0 3 0 2 0 0"));
+        let cell_data = image_data.first_element_child().unwrap().first_element_child().unwrap();
+        assert_eq!(cell_data.tag_name().name(), "CellData");
+
+        let arrays = cell_data.children().filter(|node| node.is_element()).collect::<Vec<_>>();
+        assert_eq!(arrays.len(), 2);
+        let decoded = STANDARD.decode(arrays[0].text().unwrap()).unwrap();
+        assert_eq!(&decoded[..4], &24u32.to_le_bytes());
+        assert_eq!(&decoded[8..12], &1.0f32.to_le_bytes());
+        assert_eq!(arrays[1].attribute("NumberOfComponents"), Some("3"));
+        assert_eq!(STANDARD.decode(arrays[1].text().unwrap()).unwrap().len(), 4 + 18 * 4);
+    }
+
+    #[test]
+    fn collections_list_each_file() {
+        let mut bytes = vec![];
+        write_collection(&mut bytes, &[(0, "tick-000000/fields.vti".into()), (5, "tick-000005/fields.vti".into())]).unwrap();
+
+        let xml = String::from_utf8(bytes).unwrap();
+        let document = roxmltree::Document::parse(&xml).unwrap();
+        assert_eq!(document.root_element().attribute("type"), Some("Collection"));
+        let datasets = document.descendants().filter(|node| node.has_tag_name("DataSet")).collect::<Vec<_>>();
+        assert_eq!(datasets.len(), 2);
+        assert_eq!(datasets[1].attribute("timestep"), Some("5"));
+        assert_eq!(datasets[1].attribute("file"), Some("tick-000005/fields.vti"));
+    }
+}
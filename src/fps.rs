@@ -1,5 +1,8 @@
 use bevy::prelude::*;
-use bevy::diagnostic::{DiagnosticsStore, FrameTimeDiagnosticsPlugin, SystemInformationDiagnosticsPlugin};
+use bevy::diagnostic::{DiagnosticPath, DiagnosticsStore, FrameTimeDiagnosticsPlugin, SystemInformationDiagnosticsPlugin};
+
+use crate::schedule::{SchedulePlugin, SimSet, TickRate, TimingPlugin};
+use crate::sim::{particle, SimCorePlugin};
 
 pub struct FpsPlugin;
 
@@ -10,6 +13,7 @@ impl Plugin for FpsPlugin {
         app.add_systems(Startup, setup_fps_display);
         app.add_systems(Update, (
             update_fps_display,
+            update_sim_stats_display,
             toggle_fps_display_visibility,
         ));
     }
@@ -21,13 +25,17 @@ struct FpsRoot;
 #[derive(Component)]
 struct FpsText;
 
+#[derive(Component)]
+struct SimStatsText;
+
 #[derive(Component)]
 struct LastCpuUsage(Option<f64>);
 
-const MISSING_VALUE: &'static str = "N/a";
+const MISSING_VALUE: &str = "N/a";
 const FPS_INDEX: usize = 1;
-const CPU_INDEX: usize = 3;
-const MEM_INDEX: usize = 5;
+const TPS_INDEX: usize = 3;
+const CPU_INDEX: usize = 5;
+const MEM_INDEX: usize = 7;
 
 const DEFAULT_COLOR: Color = Color::WHITE;
 
@@ -46,6 +54,7 @@ fn setup_fps_display(
                 right: Val::Percent(1.0),
                 top: Val::Percent(1.0),
                 padding: UiRect::all(Val::Px(4.0)),
+                flex_direction: FlexDirection::Column,
                 ..default()
             },
             ..default()
@@ -70,6 +79,14 @@ fn setup_fps_display(
                     value: MISSING_VALUE.into(),
                     style: style.clone(),
                 },
+                TextSection {
+                    value: "\nTPS: ".into(),
+                    style: style.clone(),
+                },
+                TextSection {
+                    value: MISSING_VALUE.into(),
+                    style: style.clone(),
+                },
                 TextSection {
                     value: "\nCPU: ".into(),
                     style: style.clone(),
@@ -91,11 +108,21 @@ fn setup_fps_display(
         },
     )).id();
 
-    commands.entity(fps_root).push_children(&[fps_text]);
+    let sim_stats_text = commands.spawn((
+        SimStatsText,
+        TextBundle {
+            text: Text::from_section("", style.clone()),
+            style: Style { margin: UiRect::top(Val::Px(8.0)), ..default() },
+            ..default()
+        },
+    )).id();
+
+    commands.entity(fps_root).push_children(&[fps_text, sim_stats_text]);
 }
 
 fn update_fps_display(
     diagnostics: Res<DiagnosticsStore>,
+    tick_rate: Res<TickRate>,
     mut last_cpu_usage: Query<&mut LastCpuUsage>,
     mut text: Query<&mut Text, With<FpsText>>,
 ) {
@@ -113,6 +140,19 @@ fn update_fps_display(
         text.sections[FPS_INDEX].style.color = DEFAULT_COLOR;
     }
 
+    if let Some(tps) = diagnostics
+        .get(&SchedulePlugin::TPS)
+        .and_then(|tps| tps.smoothed())
+    {
+        let target_tps = tick_rate.target_ticks_per_second();
+        text.sections[TPS_INDEX].value = format!("{tps:>4.0}/{target_tps:.0}");
+        // scaled so that falling behind the target is colored like falling below 120 FPS
+        text.sections[TPS_INDEX].style.color = interpolate_color((120.0 * tps / target_tps) as f32, 120.0, 60.0, 30.0);
+    } else {
+        text.sections[TPS_INDEX].value = MISSING_VALUE.into();
+        text.sections[TPS_INDEX].style.color = DEFAULT_COLOR;
+    }
+
     if let Some(cpu) = diagnostics
         .get(&SystemInformationDiagnosticsPlugin::CPU_USAGE)
         .and_then(|cpu| cpu.value())
@@ -139,6 +179,45 @@ fn update_fps_display(
     }
 }
 
+fn update_sim_stats_display(
+    diagnostics: Res<DiagnosticsStore>,
+    mut text: Query<&mut Text, With<SimStatsText>>,
+) {
+    let value = |path: &DiagnosticPath| diagnostics.get(path).and_then(|diagnostic| diagnostic.value());
+    let smoothed = |path: &DiagnosticPath| diagnostics.get(path).and_then(|diagnostic| diagnostic.smoothed());
+    let format_value = |value: Option<f64>, precision: usize| match value {
+        Some(value) => format!("{value:>8.precision$}"),
+        None => format!("{MISSING_VALUE:>8}"),
+    };
+
+    let mut stats = format!(
+        "TICK: {}\nTIME: {} s",
+        format_value(value(&TimingPlugin::TICKS), 0),
+        format_value(value(&TimingPlugin::SIMULATED_TIME), 1),
+    );
+
+    let sets = [
+        ("GRAVITY", SimSet::Gravity),
+        ("FORCE", SimSet::Force),
+        ("LIQUID", SimSet::Liquid),
+        ("GAS", SimSet::Gas),
+        ("DRAW", SimSet::Draw),
+        ("RECOLOR", SimSet::Recolor),
+    ];
+    for (name, set) in sets {
+        let time = format_value(smoothed(&TimingPlugin::set_time(&set)), 3);
+        stats.push_str(&format!("\n{name}: {time} ms"));
+    }
+
+    let materials = [particle::names::VACUUM, particle::names::AIR, particle::names::WATER, particle::names::WALL];
+    for (name, path) in materials.iter().zip(&SimCorePlugin::CELL_COUNTS) {
+        let count = format_value(value(path), 0);
+        stats.push_str(&format!("\n{}: {count}", name.to_uppercase()));
+    }
+
+    text.single_mut().sections[0].value = stats;
+}
+
 fn interpolate_color(
     value: f32,
     g_threshold: f32,
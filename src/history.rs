@@ -0,0 +1,326 @@
+use std::collections::VecDeque;
+
+use bevy::prelude::*;
+use bevy::ui::RelativeCursorPosition;
+
+use crate::recording::UnrecordedChange;
+use crate::schedule::{SimSet, SimState};
+use crate::sim::{Coords, Particle, PropertyGrid};
+
+/// Number of ticks that can be rewound
+const DEFAULT_CAPACITY: usize = 240;
+
+const SCRUBBER_HEIGHT: f32 = 16.0;
+
+pub struct HistoryPlugin;
+
+impl Plugin for HistoryPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .insert_resource(History::new(DEFAULT_CAPACITY))
+            .add_systems(Startup, setup_scrubber)
+            .add_systems(
+                FixedUpdate,
+                record_history
+                    .after(SimSet::Gas)
+                    .run_if(in_state(SimState::Playing).or_else(in_state(SimState::Stepping))),
+            )
+            .add_systems(Update, (
+                (handle_history_inputs, handle_scrubber).run_if(not(crate::puzzle::is_solving)).before(SimSet::Recolor),
+                update_scrubber,
+            ))
+        ;
+    }
+}
+
+/// The cells that differ between two consecutive recorded states.
+///
+/// A delta holds the values of whichever of the two states is *not* current, so applying it
+/// switches between them in either direction.
+struct Delta(Vec<(Coords, Particle)>);
+
+impl Delta {
+    fn between(current: &PropertyGrid<Particle>, other: &PropertyGrid<Particle>) -> Self {
+        Self(
+            current.coords()
+                .filter(|coords| current.get(*coords) != other.get(*coords))
+                .map(|coords| (coords, *other.get(coords)))
+                .collect()
+        )
+    }
+
+    fn apply(&mut self, grid: &mut PropertyGrid<Particle>) {
+        for (coords, particle) in self.0.iter_mut() {
+            std::mem::swap(grid.get_mut(*coords), particle);
+        }
+    }
+}
+
+/// Ring buffer of the most recent ticks, stored as deltas between consecutive states
+#[derive(Resource)]
+pub struct History {
+    capacity: usize,
+    /// `deltas[i]` switches between states `i` and `i + 1`, where state 0 is the oldest
+    deltas: VecDeque<Delta>,
+    /// Index of the state held in `state`
+    position: usize,
+    /// The recorded state at `position`, which the grid is reset to whenever the position changes
+    state: Option<PropertyGrid<Particle>>,
+}
+
+impl History {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            deltas: VecDeque::with_capacity(capacity),
+            position: 0,
+            state: None,
+        }
+    }
+
+    /// Number of states that can be sought, including the current one
+    pub fn len(&self) -> usize {
+        if self.state.is_some() {
+            self.deltas.len() + 1
+        } else {
+            0
+        }
+    }
+
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    /// Forgets every recorded state, e.g. when the grid is replaced by something unrelated to it
+    pub fn clear(&mut self) {
+        *self = Self::new(self.capacity);
+    }
+
+    /// Records a new state after the current position, discarding any states after it.
+    pub fn record(&mut self, grid: &PropertyGrid<Particle>) {
+        let Some(state) = &mut self.state else {
+            self.state = Some(grid.clone());
+            return;
+        };
+
+        self.deltas.truncate(self.position);
+        self.deltas.push_back(Delta::between(grid, state));
+        if self.deltas.len() > self.capacity {
+            self.deltas.pop_front();
+        }
+        state.clone_from(grid);
+        self.position = self.deltas.len();
+    }
+
+    /// Moves to the state at `position` (clamped to the recorded range) and returns it,
+    /// or returns `None` if nothing has been recorded.
+    pub fn seek(&mut self, position: usize) -> Option<&PropertyGrid<Particle>> {
+        let state = self.state.as_mut()?;
+        let position = position.min(self.deltas.len());
+
+        while self.position > position {
+            self.position -= 1;
+            self.deltas[self.position].apply(state);
+        }
+        while self.position < position {
+            self.deltas[self.position].apply(state);
+            self.position += 1;
+        }
+
+        Some(state)
+    }
+}
+
+fn record_history(
+    particles: Query<&PropertyGrid<Particle>>,
+    mut history: ResMut<History>,
+) {
+    history.record(particles.single());
+}
+
+/// `,` steps backward one tick while paused
+fn handle_history_inputs(
+    mut particles: Query<&mut PropertyGrid<Particle>>,
+    mut history: ResMut<History>,
+    state: Res<State<SimState>>,
+    inputs: Res<ButtonInput<KeyCode>>,
+    mut unrecorded_changes: EventWriter<UnrecordedChange>,
+) {
+    if *state.get() != SimState::Paused || !inputs.just_pressed(KeyCode::Comma) {
+        return;
+    }
+
+    let Some(position) = history.position().checked_sub(1) else {
+        return;
+    };
+    if let Some(recorded) = history.seek(position) {
+        particles.single_mut().clone_from(recorded);
+        unrecorded_changes.send(UnrecordedChange("rewinding the history"));
+    }
+}
+
+#[derive(Component)]
+struct Scrubber;
+
+#[derive(Component)]
+struct ScrubberFill;
+
+#[derive(Component)]
+struct ScrubberText;
+
+fn setup_scrubber(mut commands: Commands) {
+    commands.spawn((
+        Scrubber,
+        RelativeCursorPosition::default(),
+        ButtonBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                left: Val::Percent(5.0),
+                right: Val::Percent(5.0),
+                bottom: Val::Percent(1.0),
+                height: Val::Px(SCRUBBER_HEIGHT),
+                ..default()
+            },
+            background_color: BackgroundColor(Color::DARK_GRAY),
+            ..default()
+        },
+    )).with_children(|scrubber| {
+        scrubber.spawn((
+            ScrubberFill,
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    height: Val::Percent(100.0),
+                    width: Val::Percent(0.0),
+                    ..default()
+                },
+                background_color: BackgroundColor(Color::GRAY),
+                ..default()
+            },
+        ));
+        scrubber.spawn((
+            ScrubberText,
+            TextBundle {
+                text: Text::from_section("", TextStyle {
+                    font_size: SCRUBBER_HEIGHT - 2.0,
+                    color: Color::WHITE,
+                    ..default()
+                }),
+                style: Style { margin: UiRect::horizontal(Val::Px(4.0)), ..default() },
+                ..default()
+            },
+        ));
+    });
+}
+
+/// Pressing or dragging along the scrubber pauses the simulation and seeks to the corresponding tick
+fn handle_scrubber(
+    scrubber: Query<(&Interaction, &RelativeCursorPosition), With<Scrubber>>,
+    mut particles: Query<&mut PropertyGrid<Particle>>,
+    mut history: ResMut<History>,
+    state: Res<State<SimState>>,
+    mut next_state: ResMut<NextState<SimState>>,
+    mut unrecorded_changes: EventWriter<UnrecordedChange>,
+) {
+    let (interaction, cursor) = scrubber.single();
+    let (Interaction::Pressed, Some(cursor)) = (interaction, cursor.normalized) else {
+        return;
+    };
+    let Some(last) = history.len().checked_sub(1) else {
+        return;
+    };
+
+    if *state.get() == SimState::Playing {
+        next_state.set(SimState::Paused);
+    }
+
+    let position = (cursor.x.clamp(0.0, 1.0) * last as f32).round() as usize;
+    if position == history.position() {
+        return;
+    }
+    if let Some(recorded) = history.seek(position) {
+        particles.single_mut().clone_from(recorded);
+        unrecorded_changes.send(UnrecordedChange("scrubbing through the history"));
+    }
+}
+
+fn update_scrubber(
+    history: Res<History>,
+    mut fill: Query<&mut Style, With<ScrubberFill>>,
+    mut text: Query<&mut Text, With<ScrubberText>>,
+) {
+    if !history.is_changed() {
+        return;
+    }
+
+    let last = history.len().saturating_sub(1);
+    let position = history.position();
+    let fraction = if last == 0 { 1.0 } else { position as f32 / last as f32 };
+
+    fill.single_mut().width = Val::Percent(100.0 * fraction);
+    text.single_mut().sections[0].value = format!("HISTORY {position}/{last}");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sim::particle::defualts;
+
+    fn grid_with(coords: &[Coords], particle: Particle) -> PropertyGrid<Particle> {
+        PropertyGrid::new(|c| if coords.contains(&c) { particle } else { Particle::Vacuum })
+    }
+
+    fn grids_equal(a: &PropertyGrid<Particle>, b: &PropertyGrid<Particle>) -> bool {
+        a.coords().all(|coords| a.get(coords) == b.get(coords))
+    }
+
+    #[test]
+    fn seek_back_and_forth() {
+        let states = [
+            grid_with(&[], defualts::AIR),
+            grid_with(&[Coords::new(1, 1)], defualts::AIR),
+            grid_with(&[Coords::new(1, 1), Coords::new(2, 3)], defualts::WATER),
+        ];
+
+        let mut history = History::new(10);
+        for state in &states {
+            history.record(state);
+        }
+        assert_eq!(history.len(), 3);
+        assert_eq!(history.position(), 2);
+
+        for position in [0, 1, 2, 1, 0, 2] {
+            let recorded = history.seek(position).unwrap();
+            assert!(grids_equal(recorded, &states[position]));
+        }
+    }
+
+    #[test]
+    fn capacity_drops_oldest() {
+        let mut history = History::new(2);
+        for i in 0..5 {
+            history.record(&grid_with(&[Coords::new(i, 0)], defualts::AIR));
+        }
+
+        assert_eq!(history.len(), 3);
+        let oldest = history.seek(0).unwrap();
+        assert!(grids_equal(oldest, &grid_with(&[Coords::new(2, 0)], defualts::AIR)));
+    }
+
+    #[test]
+    fn record_after_seek_discards_future() {
+        let mut history = History::new(10);
+        for i in 0..4 {
+            history.record(&grid_with(&[Coords::new(i, 0)], defualts::AIR));
+        }
+
+        history.seek(1);
+        let diverged = grid_with(&[Coords::new(9, 9)], defualts::WATER);
+        history.record(&diverged);
+
+        assert_eq!(history.len(), 3);
+        assert_eq!(history.position(), 2);
+        assert!(grids_equal(history.seek(2).unwrap(), &diverged));
+        assert!(grids_equal(history.seek(1).unwrap(), &grid_with(&[Coords::new(1, 0)], defualts::AIR)));
+    }
+}
@@ -0,0 +1,259 @@
+#![allow(clippy::type_complexity, clippy::too_many_arguments)] // bevy systems routinely trip these
+
+#[cfg(feature = "gui")]
+mod camera;
+#[cfg(feature = "gui")]
+mod capture;
+#[cfg(feature = "gui")]
+mod color;
+pub mod control;
+#[cfg(feature = "gui")]
+mod draw;
+pub mod export;
+#[cfg(feature = "gui")]
+mod fps;
+#[cfg(feature = "gui")]
+mod history;
+#[cfg(feature = "gui")]
+mod net;
+#[cfg(feature = "gui")]
+mod puzzle;
+#[cfg(feature = "gui")]
+mod recording;
+#[cfg(feature = "gui")]
+mod scenario;
+mod schedule;
+pub mod sim;
+mod zero;
+
+#[cfg(feature = "gui")]
+use std::net::ToSocketAddrs;
+#[cfg(feature = "gui")]
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use bevy::app::ScheduleRunnerPlugin;
+use bevy::diagnostic::DiagnosticsPlugin;
+use bevy::input::InputPlugin;
+use bevy::prelude::*;
+
+/// How long each update of a headless app takes at least, which matches the default tick rate
+const HEADLESS_FRAME_TIME: Duration = Duration::from_nanos(16_666_667);
+
+/// Printed along with any error in the command-line arguments
+#[cfg(feature = "gui")]
+const USAGE: &str = "\
+usage: dust [options]
+  --replay <file>           replay a recorded session
+  --scenario <file>         start from a scenario, which can also be loaded later by dropping its file onto the window
+  --capture <path>          capture frames of the grid to a .gif file, or to numbered PNGs in a directory
+  --capture-every <ticks>   set the ticks from one captured frame to the next
+  --capture-scale <pixels>  set the width and height of each cell in captured frames
+  --capture-mode <mode>     set the render mode of captured frames, e.g. Temperature
+  --export <dir>            export the physical fields to a directory, at the end of a headless replay and otherwise on demand
+  --export-every <ticks>    export the fields every few ticks instead
+  --export-format <format>  export the fields as npy arrays, csv tables, or vtk files for ParaView
+  --control <port>          listen for control commands on a localhost port
+  --puzzle <dir>            play the levels in a directory
+  --host <port>             host a shared canvas that other instances can join
+  --join <address>          join the shared canvas hosted at an address, e.g. 192.168.1.20:7700
+  --headless                run without a window, either replaying, or listening for control commands or a shared canvas";
+
+/// Prints `message` and the usage, and exits
+#[cfg(feature = "gui")]
+fn usage_error(message: impl std::fmt::Display) -> ! {
+    eprintln!("error: {message}\n\n{USAGE}");
+    std::process::exit(2);
+}
+
+/// The value following `flag`, described as `what`
+#[cfg(feature = "gui")]
+fn flag_value(args: &mut impl Iterator<Item = String>, flag: &str, what: &str) -> String {
+    args.next().unwrap_or_else(|| usage_error(format!("{flag} needs {what}")))
+}
+
+/// A number of ticks, pixels, etc. that has to be more than zero
+#[cfg(feature = "gui")]
+fn positive<T: std::str::FromStr + Default + PartialOrd>(value: &str, what: &str) -> T {
+    value.parse().ok().filter(|value| *value > T::default()).unwrap_or_else(|| usage_error(format!("invalid {what} {value}")))
+}
+
+/// Runs the sandbox with the command-line arguments in `USAGE`
+#[cfg(feature = "gui")]
+pub fn run() {
+    let mut replay = None;
+    let mut scenario = None;
+    let mut capture_path = None;
+    let mut capture = capture::CaptureSettings::default();
+    let mut export_dir = None;
+    let mut export = export::ExportSettings::default();
+    let mut control = None;
+    let mut puzzle = None;
+    let mut host = None;
+    let mut join = None;
+    let mut headless = false;
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--replay" => {
+                let path = flag_value(&mut args, &arg, "a recording file");
+                let recording = recording::Recording::load(&path).unwrap_or_else(|err| usage_error(format!("couldn't load recording {path}: {err}")));
+                replay = Some(recording);
+            },
+            "--scenario" => {
+                let path = flag_value(&mut args, &arg, "a scenario file");
+                scenario = Some(scenario::description::Scenario::load(Path::new(&path)).unwrap_or_else(|err| usage_error(format!("couldn't load scenario {err}"))));
+            },
+            "--capture" => capture_path = Some(PathBuf::from(flag_value(&mut args, &arg, "a capture path"))),
+            "--capture-every" => {
+                let ticks = flag_value(&mut args, &arg, "the ticks between captured frames");
+                capture.every = positive(&ticks, "ticks between captured frames");
+            },
+            "--capture-scale" => {
+                let scale = flag_value(&mut args, &arg, "a capture scale");
+                capture.scale = positive(&scale, "capture scale");
+            },
+            "--capture-mode" => {
+                let mode = flag_value(&mut args, &arg, "a render mode");
+                capture.mode = color::RenderMode::from_name(&mode).unwrap_or_else(|| usage_error(format!("unknown render mode {mode}")));
+            },
+            "--export" => export_dir = Some(PathBuf::from(flag_value(&mut args, &arg, "an export directory"))),
+            "--export-every" => {
+                let ticks = flag_value(&mut args, &arg, "the ticks between exports");
+                export.every = Some(positive(&ticks, "ticks between exports"));
+            },
+            "--export-format" => {
+                let format = flag_value(&mut args, &arg, "an export format");
+                export.format = export::ExportFormat::from_name(&format).unwrap_or_else(|| usage_error(format!("unknown export format {format}")));
+            },
+            "--control" => {
+                let port = flag_value(&mut args, &arg, "a control port");
+                control = Some(port.parse().unwrap_or_else(|err| usage_error(format!("invalid control port {port}: {err}"))));
+            },
+            "--puzzle" => {
+                let dir = flag_value(&mut args, &arg, "a level directory");
+                let levels = puzzle::load_levels(&dir).unwrap_or_else(|err| usage_error(format!("couldn't load levels from {dir}: {err}")));
+                puzzle = Some(levels);
+            },
+            "--host" => {
+                let port = flag_value(&mut args, &arg, "a port to host on");
+                host = Some(port.parse().unwrap_or_else(|err| usage_error(format!("invalid port to host on {port}: {err}"))));
+            },
+            "--join" => {
+                let addr = flag_value(&mut args, &arg, "an address to join");
+                let resolved = addr.to_socket_addrs().ok().and_then(|mut addrs| addrs.next());
+                join = Some(resolved.unwrap_or_else(|| usage_error(format!("invalid address to join {addr}"))));
+            },
+            "--headless" => headless = true,
+            _ => usage_error(format!("unknown argument {arg}")),
+        }
+    }
+
+    let capture = capture_path.map(|path| capture::CaptureSettings { path, ..capture });
+    let export_requested = export_dir.is_some() || export.every.is_some();
+    let export = match export_dir {
+        Some(dir) => export::ExportSettings { dir, ..export },
+        None => export,
+    };
+
+    if scenario.is_some() && (replay.is_some() || puzzle.is_some() || join.is_some()) {
+        usage_error("--scenario can't be combined with --replay, --puzzle, or --join, which set up the grid themselves");
+    }
+
+    if host.is_some() && join.is_some() {
+        usage_error("an instance can't both --host and --join a shared canvas");
+    }
+    if headless && (puzzle.is_some() || join.is_some()) {
+        usage_error("--puzzle and --join need a window");
+    }
+    if let (false, Some(scenario)) = (headless, &scenario) {
+        scenario::check_fits_window(scenario).unwrap_or_else(|err| usage_error(format!("can't show the scenario: {err}")));
+    }
+
+    // bound and connected before building the app, so that a port that's in use or a host that can't be reached
+    // is reported like a bad argument
+    let control = control.map(|port| {
+        control::ControlPlugin::bind(port).unwrap_or_else(|err| usage_error(format!("couldn't listen for control commands on port {port}: {err}")))
+    });
+    let host = host.map(|port| {
+        net::HostPlugin::bind(port).unwrap_or_else(|err| usage_error(format!("couldn't host a session on port {port}: {err}")))
+    });
+    let join = join.map(|addr| {
+        net::JoinPlugin::connect(addr).unwrap_or_else(|err| usage_error(format!("couldn't join the session at {addr}: {err}")))
+    });
+
+    if headless {
+        match (replay, control, host) {
+            (Some(recording), None, None) => {
+                let capture = capture.map(|settings| {
+                    let path = settings.path.display().to_string();
+                    capture::FrameCapture::start(settings, schedule::TickRate::default().ticks_per_second)
+                        .unwrap_or_else(|err| usage_error(format!("couldn't capture frames to {path}: {err}")))
+                });
+                recording::print_headless_replay(recording, capture, export_requested.then_some(export));
+            },
+            (None, control, host) if control.is_some() || host.is_some() => {
+                let mut app = headless_app();
+                app
+                    .add_plugins(scenario::ScenarioPlugin { scenario })
+                    .add_plugins(capture::CapturePlugin { capture })
+                    .add_plugins(export::ExportPlugin { settings: export });
+                if let Some(control) = control {
+                    app.add_plugins(control);
+                }
+                if let Some(host) = host {
+                    app.add_plugins(host);
+                }
+                app.run();
+            },
+            _ => usage_error("--headless needs either a recording to --replay, or a --control or --host port"),
+        }
+        return;
+    }
+    // the modes that set up the grid themselves don't take scenarios
+    let takes_scenarios = replay.is_none() && puzzle.is_none() && join.is_none();
+
+    let mut app = App::new();
+    app
+        .insert_resource(Msaa::Off)
+        .add_plugins(DefaultPlugins)
+        .add_plugins(fps::FpsPlugin)
+        .add_plugins(draw::DrawPlugin)
+        .add_plugins(color::ColorPlugin)
+        .add_plugins(camera::CameraPlugin)
+        .add_plugins(sim::SimPlugin)
+        .add_plugins(schedule::SchedulePlugin)
+        .add_plugins(history::HistoryPlugin)
+        .add_plugins(recording::RecordingPlugin { replay })
+        .add_plugins(capture::CapturePlugin { capture })
+        .add_plugins(export::ExportPlugin { settings: export });
+    if takes_scenarios {
+        app.add_plugins(scenario::ScenarioPlugin { scenario });
+    }
+    if let Some(control) = control {
+        app.add_plugins(control);
+    }
+    if let Some(levels) = puzzle {
+        app.add_plugins(puzzle::PuzzlePlugin { levels });
+    }
+    if let Some(host) = host {
+        app.add_plugins(host);
+    }
+    if let Some(join) = join {
+        app.add_plugins(join);
+    }
+    app.run();
+}
+
+/// An app that runs the simulation without a window, e.g. to be driven through the `control` server
+pub fn headless_app() -> App {
+    let mut app = App::new();
+    app
+        .add_plugins(MinimalPlugins.set(ScheduleRunnerPlugin::run_loop(HEADLESS_FRAME_TIME)))
+        .add_plugins(InputPlugin)
+        .add_plugins(DiagnosticsPlugin)
+        .add_plugins(sim::SimCorePlugin)
+        .add_plugins(schedule::SchedulePlugin);
+    app
+}
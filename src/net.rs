@@ -0,0 +1,536 @@
+//! Sharing a canvas between instances of the app over the network, with the protocol described in `protocol`.
+//!
+//! One instance hosts the session and runs the simulation, and the others join it by address.
+//! Joined instances don't simulate anything themselves: they send their strokes to the host instead of painting them,
+//! and show the cells that the host sends back after each frame in which any changed.
+//! They also take the topology and gravity from the host, so their own controls for them are turned off,
+//! and they leave a session whose grid is a different size than theirs.
+//! The host paints each client's strokes with the selections the client made them with, unless they hold values that
+//! the simulation can't run with, and leaves them out of its own undo history.
+//! Neither the strokes painted by the host nor the cells sent to the clients can be recorded, so they stop any recording.
+
+pub mod protocol;
+
+use std::io::{self, BufReader, BufWriter, Write};
+use std::net::{Ipv4Addr, Shutdown, SocketAddr, TcpListener, TcpStream};
+use std::sync::mpsc::{self, Receiver, Sender, TryRecvError};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use bevy::prelude::*;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
+use crate::draw::{self, Brush, FanToDraw, Paint, PaintBudget, ParticleToDraw};
+use crate::recording::UnrecordedChange;
+use crate::schedule::{SimSet, SimState, TickCount};
+use crate::sim::force_field::fans;
+use crate::sim::gravity::Gravity;
+use crate::sim::topology::Topology;
+use crate::sim::types::Vector;
+use crate::sim::{Particle, PropertyGrid};
+use protocol::{ClientMessage, HostMessage};
+
+/// Hosts a session for the clients that connect to a listener bound by `bind`
+pub struct HostPlugin {
+    listener: TcpListener,
+}
+
+impl HostPlugin {
+    /// Listens on `port` of every network interface, or on any free port if `port` is 0
+    pub fn bind(port: u16) -> io::Result<Self> {
+        Ok(Self { listener: TcpListener::bind((Ipv4Addr::UNSPECIFIED, port))? })
+    }
+}
+
+impl Plugin for HostPlugin {
+    fn build(&self, app: &mut App) {
+        let listener = self.listener.try_clone().expect("couldn't take the listener to host the session on");
+        let (events, received) = mpsc::channel();
+        info!("hosting a session on {}", listener.local_addr().expect("a bound listener has an address"));
+        thread::spawn(move || accept_clients(listener, events));
+
+        app
+            // for headless hosts, which don't record anything
+            .add_event::<UnrecordedChange>()
+            .insert_resource(Host {
+                events: Mutex::new(received),
+                clients: vec![],
+                joining: vec![],
+                sent: None,
+                rng: StdRng::from_entropy(),
+            })
+            .add_systems(Update, paint_client_strokes.in_set(SimSet::Draw))
+            // after everything that changes the grids, i.e., the ticks, painting, and stepping
+            .add_systems(PostUpdate, send_changes)
+        ;
+    }
+}
+
+/// Joins the session at the other end of a connection made by `connect`
+pub struct JoinPlugin {
+    addr: SocketAddr,
+    stream: TcpStream,
+}
+
+impl JoinPlugin {
+    /// Connects to the host of the session at `addr`
+    pub fn connect(addr: SocketAddr) -> io::Result<Self> {
+        let stream = TcpStream::connect(addr)?;
+        stream.set_nodelay(true)?;
+        Ok(Self { addr, stream })
+    }
+}
+
+impl Plugin for JoinPlugin {
+    fn build(&self, app: &mut App) {
+        let stream = self.stream.try_clone().expect("couldn't configure the connection");
+        let reader = stream.try_clone().expect("couldn't configure the connection");
+        let connection = stream.try_clone().expect("couldn't configure the connection");
+
+        let (messages, received) = mpsc::channel();
+        thread::spawn(move || {
+            let mut reader = BufReader::new(reader);
+            while let Ok(message) = protocol::read_message(&mut reader) {
+                if messages.send(message).is_err() {
+                    break;
+                }
+            }
+        });
+        let (strokes, outgoing) = mpsc::channel::<ClientMessage>();
+        thread::spawn(move || {
+            let mut writer = BufWriter::new(stream);
+            for stroke in outgoing {
+                if protocol::write_message(&mut writer, &stroke).and_then(|()| writer.flush()).is_err() {
+                    break;
+                }
+            }
+        });
+        info!("joined the session at {}", self.addr);
+
+        app
+            .add_event::<Paint>()
+            .add_event::<UnrecordedChange>()
+            .insert_resource(Session {
+                connection,
+                messages: Mutex::new(received),
+                strokes,
+            })
+            .add_systems(Update, (
+                send_strokes
+                    .in_set(SimSet::Draw)
+                    .after(draw::use_tool)
+                    .before(draw::draw_particle)
+                    .before(draw::draw_fan),
+                apply_host_messages.before(SimSet::Recolor),
+                hold_paused.run_if(not(in_state(SimState::Paused))),
+            ))
+        ;
+    }
+}
+
+#[derive(Resource)]
+struct Host {
+    events: Mutex<Receiver<HostEvent>>,
+    /// Each client that has been sent a snapshot, as the sender of the messages to write to it
+    clients: Vec<Sender<Arc<Vec<u8>>>>,
+    /// Clients that connected since the last snapshot was sent
+    joining: Vec<Sender<Arc<Vec<u8>>>>,
+    /// What the clients have been sent so far, while there are any
+    sent: Option<Sent>,
+    /// Randomness for painting client strokes, kept apart from the `DrawRng` so that recordings aren't affected
+    rng: StdRng,
+}
+
+/// The grids and the settings as of the last message sent to the clients
+struct Sent {
+    particles: PropertyGrid<Particle>,
+    force_field: PropertyGrid<Vector>,
+    topology: Topology,
+    gravity: Gravity,
+}
+
+enum HostEvent {
+    Joined(Sender<Arc<Vec<u8>>>),
+    Message(ClientMessage),
+}
+
+/// The connection to the host of the session that was joined
+#[derive(Resource)]
+pub(crate) struct Session {
+    /// For leaving the session
+    connection: TcpStream,
+    messages: Mutex<Receiver<HostMessage>>,
+    strokes: Sender<ClientMessage>,
+}
+
+fn accept_clients(listener: TcpListener, events: Sender<HostEvent>) {
+    for stream in listener.incoming() {
+        let Ok(stream) = stream else {
+            continue;
+        };
+        let events = events.clone();
+        thread::spawn(move || serve_client(stream, events));
+    }
+}
+
+/// Reads the client's messages until it disconnects, while a second thread writes the host's messages to it
+fn serve_client(stream: TcpStream, events: Sender<HostEvent>) {
+    let peer = stream.peer_addr().map_or("an unknown address".into(), |addr| addr.to_string());
+    let (Ok(()), Ok(writer)) = (stream.set_nodelay(true), stream.try_clone()) else {
+        return;
+    };
+    let (frames, outgoing) = mpsc::channel::<Arc<Vec<u8>>>();
+    thread::spawn(move || {
+        let mut writer = writer;
+        for frame in outgoing {
+            if writer.write_all(&frame).is_err() {
+                break;
+            }
+        }
+    });
+    if events.send(HostEvent::Joined(frames)).is_err() {
+        return;
+    }
+    info!("{peer} joined the session");
+
+    let mut reader = BufReader::new(stream);
+    while let Ok(message) = protocol::read_message(&mut reader) {
+        if events.send(HostEvent::Message(message)).is_err() {
+            break;
+        }
+    }
+    info!("{peer} left the session");
+}
+
+fn paint_client_strokes(
+    mut host: ResMut<Host>,
+    mut particles: Query<&mut PropertyGrid<Particle>>,
+    mut force_field: Query<&mut PropertyGrid<Vector>>,
+    topology: Res<Topology>,
+    mut budget: Option<ResMut<PaintBudget>>,
+    mut unrecorded_changes: EventWriter<UnrecordedChange>,
+) {
+    let host = &mut *host;
+    let events = host.events.lock().unwrap().try_iter().collect::<Vec<_>>();
+    let mut particles = particles.single_mut();
+    let mut force_field = force_field.single_mut();
+
+    for event in events {
+        match event {
+            HostEvent::Joined(client) => host.joining.push(client),
+            // like `draw_particle` and `draw_fan`, so that erasing with a fan selected still erases particles
+            HostEvent::Message(ClientMessage::Paint { paint, particle, fan, throw }) => {
+                if !is_valid_stroke(&paint, particle) {
+                    warn!("ignored a stroke from a client with values that can't be simulated");
+                    continue;
+                }
+                unrecorded_changes.send(UnrecordedChange("painting a stroke from another instance"));
+                draw::paint_particle(&paint, particle, throw, &mut particles, *topology, &mut host.rng, budget.as_deref_mut(), |_, _, _| ());
+                if let Some(fan) = fan.and_then(|fan| fans::ALL.into_iter().find(|known| known.name == fan)) {
+                    draw::paint_fan(&paint, &fan, &mut force_field, *topology, budget.as_deref_mut(), |_, _, _| ());
+                }
+            },
+        }
+    }
+}
+
+/// Whether the simulation can run with the particle and the cursor velocity of a client's stroke,
+/// which are checked since any instance on the network can send them
+fn is_valid_stroke(paint: &Paint, particle: Option<Particle>) -> bool {
+    let valid_particle = particle
+        .and_then(|particle| particle.physical_properties().copied())
+        .is_none_or(|physical_properties| physical_properties.is_valid());
+    paint.cursor_velocity.is_finite() && valid_particle
+}
+
+/// Sends the cells that changed this frame to the clients, and a snapshot to the clients that just joined
+fn send_changes(
+    mut host: ResMut<Host>,
+    particles: Query<&PropertyGrid<Particle>>,
+    force_field: Query<&PropertyGrid<Vector>>,
+    topology: Res<Topology>,
+    gravity: Res<Gravity>,
+    tick_count: Res<TickCount>,
+) {
+    let host = &mut *host;
+    let particles = particles.single();
+    let force_field = force_field.single();
+
+    if host.clients.is_empty() {
+        host.sent = None;
+    }
+    if let Some(sent) = &mut host.sent {
+        let delta = HostMessage::Delta {
+            tick: tick_count.0,
+            particles: protocol::changes(&sent.particles, particles),
+            force_field: protocol::changes(&sent.force_field, force_field),
+            topology: (sent.topology != *topology).then_some(*topology),
+            gravity: (sent.gravity != *gravity).then(|| gravity.clone()),
+        };
+        if !delta.is_empty() {
+            let frame = Arc::new(protocol::encode(&delta));
+            host.clients.retain(|client| client.send(frame.clone()).is_ok());
+            sent.particles.clone_from(particles);
+            sent.force_field.clone_from(force_field);
+            sent.topology = *topology;
+            sent.gravity.clone_from(&gravity);
+        }
+    }
+
+    if !host.joining.is_empty() {
+        let snapshot = HostMessage::Snapshot {
+            tick: tick_count.0,
+            particles: particles.clone(),
+            force_field: force_field.clone(),
+            topology: *topology,
+            gravity: gravity.clone(),
+        };
+        let frame = Arc::new(protocol::encode(&snapshot));
+        for client in std::mem::take(&mut host.joining) {
+            if client.send(frame.clone()).is_ok() {
+                host.clients.push(client);
+            }
+        }
+        host.sent = Some(Sent {
+            particles: particles.clone(),
+            force_field: force_field.clone(),
+            topology: *topology,
+            gravity: gravity.clone(),
+        });
+    }
+}
+
+/// Takes the strokes made this frame before they're painted, and sends them to the host to paint instead
+fn send_strokes(
+    session: Res<Session>,
+    particle_to_draw: Query<&ParticleToDraw>,
+    fan_to_draw: Query<&FanToDraw>,
+    brush: Query<&Brush>,
+    mut paints: ResMut<Events<Paint>>,
+) {
+    let (Ok(particle_to_draw), Ok(fan_to_draw), Ok(brush)) = (particle_to_draw.get_single(), fan_to_draw.get_single(), brush.get_single()) else {
+        return;
+    };
+    for paint in paints.drain() {
+        // the host is gone if this fails, which `apply_host_messages` reports
+        let _ = session.strokes.send(ClientMessage::Paint {
+            paint,
+            particle: particle_to_draw.0,
+            fan: fan_to_draw.0.map(|fan| fan.name.to_owned()),
+            throw: brush.throw,
+        });
+    }
+}
+
+fn apply_host_messages(
+    session: Res<Session>,
+    mut particles: Query<&mut PropertyGrid<Particle>>,
+    mut force_field: Query<&mut PropertyGrid<Vector>>,
+    mut topology: ResMut<Topology>,
+    mut gravity: ResMut<Gravity>,
+    mut tick_count: ResMut<TickCount>,
+    mut unrecorded_changes: EventWriter<UnrecordedChange>,
+    mut disconnected: Local<bool>,
+) {
+    if *disconnected {
+        return;
+    }
+    let mut particles = particles.single_mut();
+    let mut force_field = force_field.single_mut();
+    let messages = session.messages.lock().unwrap();
+
+    loop {
+        match messages.try_recv() {
+            Ok(HostMessage::Snapshot { tick, particles: new_particles, force_field: new_force_field, topology: new_topology, gravity: new_gravity }) => {
+                // the window only shows grids of one size, and the deltas that follow are for the host's
+                let (dims, new_dims) = (particles.dims(), new_particles.dims());
+                if new_dims != dims || new_force_field.dims() != dims {
+                    error!(
+                        "left the session, since the host's grid is {}x{} and this one is {}x{}",
+                        new_dims.x, new_dims.y, dims.x, dims.y,
+                    );
+                    let _ = session.connection.shutdown(Shutdown::Both);
+                    *disconnected = true;
+                    break;
+                }
+                *particles = new_particles;
+                *force_field = new_force_field;
+                *topology = new_topology;
+                *gravity = new_gravity;
+                tick_count.0 = tick;
+                unrecorded_changes.send(UnrecordedChange("a change from the host of the session"));
+            },
+            Ok(HostMessage::Delta { tick, particles: particle_changes, force_field: force_field_changes, topology: new_topology, gravity: new_gravity }) => {
+                protocol::apply_changes(&mut particles, &particle_changes);
+                protocol::apply_changes(&mut force_field, &force_field_changes);
+                if let Some(new_topology) = new_topology {
+                    *topology = new_topology;
+                }
+                if let Some(new_gravity) = new_gravity {
+                    *gravity = new_gravity;
+                }
+                tick_count.0 = tick;
+                unrecorded_changes.send(UnrecordedChange("a change from the host of the session"));
+            },
+            Err(TryRecvError::Empty) => break,
+            Err(TryRecvError::Disconnected) => {
+                if !*disconnected {
+                    error!("lost the connection to the host of the session");
+                    *disconnected = true;
+                }
+                break;
+            },
+        }
+    }
+}
+
+/// Run condition for the controls of the settings that a joined instance takes from the host
+pub(crate) fn is_joined(session: Option<Res<Session>>) -> bool {
+    session.is_some()
+}
+
+/// The host runs the simulation, so a joined instance stays paused
+fn hold_paused(mut next_state: ResMut<NextState<SimState>>) {
+    next_state.set(SimState::Paused);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::*;
+    use crate::draw::PaintCells;
+    use crate::sim::particle::defualts;
+    use crate::sim::{Coords, N_PIXELS};
+
+    fn particle_at(app: &mut App, coords: Coords) -> Particle {
+        *app.world.query::<&PropertyGrid<Particle>>().single(&app.world).get(coords)
+    }
+
+    /// Updates both apps until `done`, failing if that takes too long
+    fn update_until(host: &mut App, client: &mut App, done: impl Fn(&mut App, &mut App) -> bool) {
+        let deadline = Instant::now() + Duration::from_secs(10);
+        loop {
+            host.update();
+            client.update();
+            if done(host, client) {
+                return;
+            }
+            assert!(Instant::now() < deadline, "timed out");
+            thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    #[test]
+    fn messages_roundtrip() {
+        let old = PropertyGrid::new(|_| defualts::VACUUM);
+        let mut new = old.clone();
+        *new.get_mut(Coords::new(3, 4)) = defualts::WATER;
+
+        let delta = HostMessage::Delta {
+            tick: 9,
+            particles: protocol::changes(&old, &new),
+            force_field: vec![],
+            topology: None,
+            gravity: None,
+        };
+        let HostMessage::Delta { tick: 9, particles, .. } = protocol::read_message(&mut &protocol::encode(&delta)[..]).unwrap() else {
+            panic!("expected the delta back");
+        };
+        let mut applied = old.clone();
+        protocol::apply_changes(&mut applied, &particles);
+        assert!(applied == new);
+    }
+
+    #[test]
+    fn strokes_that_cant_be_simulated_are_invalid() {
+        let paint = Paint { cells: PaintCells::Cells(vec![Coords::new(1, 1)]), erase: false, cursor_velocity: Vector::ZERO };
+        assert!(is_valid_stroke(&paint, Some(defualts::WATER)));
+        assert!(is_valid_stroke(&paint, None));
+
+        let mut nan_mass = defualts::WATER;
+        nan_mass.physical_properties_mut().unwrap().mass = f32::NAN;
+        assert!(!is_valid_stroke(&paint, Some(nan_mass)));
+        let mut outside = defualts::AIR;
+        outside.physical_properties_mut().unwrap().internal_position.x = 1.5;
+        assert!(!is_valid_stroke(&paint, Some(outside)));
+        let thrown = Paint { cursor_velocity: Vector::new(f32::INFINITY, 0.0), ..paint };
+        assert!(!is_valid_stroke(&thrown, Some(defualts::WATER)));
+    }
+
+    /// A paused host on a free port, and that port
+    fn start_host() -> (App, u16) {
+        let plugin = HostPlugin::bind(0).unwrap();
+        let port = plugin.listener.local_addr().unwrap().port();
+        let mut host = crate::headless_app();
+        host.add_plugins(plugin);
+        host.finish();
+        host.cleanup();
+        host.update();
+        host.world.resource_mut::<NextState<SimState>>().set(SimState::Paused);
+        (host, port)
+    }
+
+    /// A client of the host on `port`, drawing water
+    fn join(port: u16) -> App {
+        let mut client = crate::headless_app();
+        client.add_plugins(JoinPlugin::connect((Ipv4Addr::LOCALHOST, port).into()).unwrap());
+        client.finish();
+        client.cleanup();
+        client.world.spawn(ParticleToDraw(Some(defualts::WATER)));
+        client.world.spawn(FanToDraw(None));
+        client.world.spawn(Brush::default());
+        client
+    }
+
+    #[test]
+    fn strokes_from_clients_are_painted_by_the_host() {
+        let (mut host, port) = start_host();
+        // painted before the client joins, so it has to come with the snapshot
+        let wall = Coords::new(5, 0);
+        *host.world.query::<&mut PropertyGrid<Particle>>().single_mut(&mut host.world).get_mut(wall) = defualts::WALL_REFLECTIVE;
+
+        let mut client = join(port);
+        update_until(&mut host, &mut client, |_, client| particle_at(client, wall) == defualts::WALL_REFLECTIVE);
+
+        let water = Coords::new(10, 20);
+        client.world.send_event(Paint { cells: PaintCells::Cells(vec![water]), erase: false, cursor_velocity: Vector::ZERO });
+        update_until(&mut host, &mut client, |host, client| {
+            matches!(particle_at(host, water), Particle::Water { .. }) && particle_at(client, water) == particle_at(host, water)
+        });
+    }
+
+    #[test]
+    fn clients_take_the_topology_and_gravity_of_the_host() {
+        let (mut host, port) = start_host();
+        host.world.insert_resource(Topology::Hexagonal);
+
+        let mut client = join(port);
+        update_until(&mut host, &mut client, |_, client| *client.world.resource::<Topology>() == Topology::Hexagonal);
+
+        host.world.insert_resource(Topology::Moore);
+        host.world.resource_mut::<Gravity>().enabled = false;
+        update_until(&mut host, &mut client, |_, client| {
+            *client.world.resource::<Topology>() == Topology::Moore && !client.world.resource::<Gravity>().enabled
+        });
+    }
+
+    #[test]
+    fn clients_leave_hosts_with_grids_of_another_size() {
+        let (mut host, port) = start_host();
+        *host.world.query::<&mut PropertyGrid<Particle>>().single_mut(&mut host.world) = PropertyGrid::with_dims(Coords::new(40, 24), |_| defualts::VACUUM);
+        *host.world.query::<&mut PropertyGrid<Vector>>().single_mut(&mut host.world) = PropertyGrid::with_dims(Coords::new(40, 24), |_| Vector::ZERO);
+
+        let mut client = join(port);
+        update_until(&mut host, &mut client, |host, _| !host.world.resource::<Host>().clients.is_empty());
+        // the host only notices that the client left when writing to it fails, so it keeps changing a cell
+        update_until(&mut host, &mut client, |host, _| {
+            let mut particles = host.world.query::<&mut PropertyGrid<Particle>>().single_mut(&mut host.world);
+            let cell = particles.get_mut(Coords::new(1, 1));
+            *cell = if *cell == defualts::VACUUM { defualts::WALL_REFLECTIVE } else { defualts::VACUUM };
+            host.world.resource::<Host>().clients.is_empty()
+        });
+        assert_eq!(client.world.query::<&PropertyGrid<Particle>>().single(&client.world).dims(), N_PIXELS);
+    }
+}
@@ -0,0 +1,110 @@
+//! The messages between the host of a shared canvas and the clients that joined it.
+//!
+//! Each message is JSON compressed with deflate, preceded by its compressed length as a big-endian `u32`.
+
+use std::io::{self, Read, Write};
+
+use flate2::read::DeflateDecoder;
+use flate2::write::DeflateEncoder;
+use flate2::Compression;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::draw::Paint;
+use crate::sim::gravity::Gravity;
+use crate::sim::topology::Topology;
+use crate::sim::types::Vector;
+use crate::sim::{Coords, Particle, PropertyGrid};
+
+/// Longest message that will be read, compressed or not, so that a corrupt length or a malicious message can't exhaust memory
+const MAX_MESSAGE_LEN: usize = 64 << 20;
+
+#[derive(Clone, Serialize, Deserialize)]
+pub enum HostMessage {
+    /// The whole canvas and the settings it is simulated with, sent to each client when it joins
+    Snapshot {
+        tick: u64,
+        particles: PropertyGrid<Particle>,
+        force_field: PropertyGrid<Vector>,
+        topology: Topology,
+        gravity: Gravity,
+    },
+    /// The cells that changed since the previous message, along with the topology and gravity if they changed
+    Delta {
+        tick: u64,
+        particles: Vec<(Coords, Particle)>,
+        force_field: Vec<(Coords, Vector)>,
+        topology: Option<Topology>,
+        gravity: Option<Gravity>,
+    },
+}
+
+impl HostMessage {
+    /// Whether the message is a delta that doesn't change anything
+    pub fn is_empty(&self) -> bool {
+        matches!(
+            self,
+            Self::Delta { particles, force_field, topology: None, gravity: None, .. } if particles.is_empty() && force_field.is_empty()
+        )
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub enum ClientMessage {
+    /// A stroke made on the client, along with the selections it was made with. Fans are sent by name.
+    Paint {
+        paint: Paint,
+        particle: Option<Particle>,
+        fan: Option<String>,
+        throw: bool,
+    },
+}
+
+/// The cells of `new` that differ from `old`, along with their new values
+pub fn changes<T: Copy + PartialEq>(old: &PropertyGrid<T>, new: &PropertyGrid<T>) -> Vec<(Coords, T)> {
+    new.coords()
+        .filter(|coords| old.get(*coords) != new.get(*coords))
+        .map(|coords| (coords, *new.get(coords)))
+        .collect()
+}
+
+/// Overwrites the cells listed in `changes`, skipping any that are out of bounds
+pub fn apply_changes<T: Copy>(grid: &mut PropertyGrid<T>, changes: &[(Coords, T)]) {
+    for (coords, value) in changes {
+        if let Some(cell) = grid.try_get_mut(*coords) {
+            *cell = *value;
+        }
+    }
+}
+
+/// The message as it is sent, so that it only has to be compressed once for all of the clients it goes to
+pub fn encode(message: &impl Serialize) -> Vec<u8> {
+    // serialized before compressing, since the encoder is slow with the many small writes that serializing makes
+    let json = serde_json::to_vec(message).expect("messages can always be serialized");
+    let mut encoder = DeflateEncoder::new(vec![0; 4], Compression::fast());
+    encoder.write_all(&json).expect("writing to a vector can't fail");
+    let mut frame = encoder.finish().expect("writing to a vector can't fail");
+    let len = (frame.len() - 4) as u32;
+    frame[..4].copy_from_slice(&len.to_be_bytes());
+    frame
+}
+
+pub fn write_message(writer: &mut impl Write, message: &impl Serialize) -> io::Result<()> {
+    writer.write_all(&encode(message))
+}
+
+/// Reads the next message, blocking until it has all arrived
+pub fn read_message<T: DeserializeOwned>(reader: &mut impl Read) -> io::Result<T> {
+    let mut len = [0; 4];
+    reader.read_exact(&mut len)?;
+    let len = u32::from_be_bytes(len) as usize;
+    if len > MAX_MESSAGE_LEN {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, format!("message of {len} bytes is too long")));
+    }
+
+    let mut compressed = vec![0; len];
+    reader.read_exact(&mut compressed)?;
+    let mut json = vec![];
+    DeflateDecoder::new(&compressed[..]).take(MAX_MESSAGE_LEN as u64).read_to_end(&mut json)?;
+    Ok(serde_json::from_slice(&json)?)
+}
@@ -0,0 +1,376 @@
+//! A game on top of the sandbox: levels with fixed terrain, a budget of cells to paint, and goals checked every tick.
+//!
+//! Levels are RON files, described in `level`, and are picked from a level-select screen.
+//! Walls can't be painted over while solving, so a level's walls stay as they were laid out, and the sandbox's
+//! controls that would make a level trivial, like changing gravity or the topology, rewinding the history,
+//! or dropping a scenario onto the window, are turned off.
+
+pub mod level;
+
+use std::error::Error;
+
+use bevy::prelude::*;
+
+use crate::camera::{cell_size, grid_to_camera};
+use crate::draw::{PaintBudget, StrokeHistory};
+use crate::history::History;
+use crate::recording::UnrecordedChange;
+use crate::schedule::{SimSet, SimState};
+use crate::sim::gravity::Gravity;
+use crate::sim::topology::Topology;
+use crate::sim::types::{Scalar, Vector};
+use crate::sim::{Particle, PropertyGrid, RelCoords};
+use level::{Goal, GoalStatus, Level, Outcome};
+
+const OBJECTIVE_COLOR: Color = Color::GREEN;
+const CONSTRAINT_COLOR: Color = Color::RED;
+
+pub struct PuzzlePlugin {
+    pub levels: Vec<Level>,
+}
+
+impl Plugin for PuzzlePlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_state::<PuzzleState>()
+            .insert_resource(Puzzle {
+                levels: self.levels.clone(),
+                solved: vec![false; self.levels.len()],
+                current: None,
+                ticks: 0,
+                measures: vec![],
+                outcome: None,
+            })
+            .insert_resource(PaintBudget::default())
+            .add_systems(Startup, (setup_level_select, setup_puzzle_display))
+            .add_systems(OnEnter(PuzzleState::Selecting), (show_level_select::<true>, stop_solving))
+            .add_systems(OnExit(PuzzleState::Selecting), show_level_select::<false>)
+            .add_systems(OnEnter(PuzzleState::Over), stop_solving)
+            .add_systems(Update, (
+                // after drawing, so that the click on a level isn't also painted onto it
+                handle_level_buttons.run_if(in_state(PuzzleState::Selecting)).after(SimSet::Draw).before(SimSet::Recolor),
+                handle_puzzle_inputs.before(SimSet::Draw),
+                highlight_level_buttons,
+                update_level_buttons.run_if(resource_changed::<Puzzle>),
+                update_puzzle_display,
+                draw_goal_regions.run_if(not(in_state(PuzzleState::Selecting))),
+            ))
+            .add_systems(
+                FixedUpdate,
+                check_goals
+                    .after(SimSet::Gas)
+                    .run_if(in_state(PuzzleState::Solving))
+                    .run_if(in_state(SimState::Playing).or_else(in_state(SimState::Stepping))),
+            )
+        ;
+    }
+}
+
+/// Loads every `.ron` file in `dir` as a level, in the order of their file names
+pub fn load_levels(dir: &str) -> Result<Vec<Level>, Box<dyn Error>> {
+    let mut paths = std::fs::read_dir(dir)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<Vec<_>, _>>()?;
+    paths.retain(|path| path.extension().is_some_and(|extension| extension == "ron"));
+    paths.sort();
+
+    paths.iter()
+        .map(|path| {
+            let path = path.to_string_lossy();
+            Level::load(&path).map_err(|err| format!("couldn't load level {path}: {err}").into())
+        })
+        .collect()
+}
+
+#[derive(States, Debug, Default, Hash, PartialEq, Eq, Clone, Copy)]
+pub enum PuzzleState {
+    /// Picking a level
+    #[default]
+    Selecting,
+    Solving,
+    /// The level was won or lost
+    Over,
+}
+
+/// Run condition for the sandbox's controls that are turned off while solving a level. Always false outside of puzzle mode.
+pub(crate) fn is_solving(state: Option<Res<State<PuzzleState>>>) -> bool {
+    state.is_some_and(|state| *state.get() == PuzzleState::Solving)
+}
+
+#[derive(Resource)]
+struct Puzzle {
+    levels: Vec<Level>,
+    /// Whether each level has been won this session
+    solved: Vec<bool>,
+    /// Index of the level being played, if any
+    current: Option<usize>,
+    /// Ticks simulated since the current level started
+    ticks: u64,
+    /// The latest measure of each goal of the current level
+    measures: Vec<Scalar>,
+    outcome: Option<Outcome>,
+}
+
+impl Puzzle {
+    fn level(&self) -> Option<&Level> {
+        self.current.map(|current| &self.levels[current])
+    }
+}
+
+#[derive(Component)]
+struct LevelSelect;
+
+#[derive(Component)]
+struct LevelButton(usize);
+
+#[derive(Component)]
+struct PuzzleText;
+
+fn get_style() -> TextStyle {
+    TextStyle {
+        font_size: 16.0,
+        color: Color::WHITE,
+        ..default()
+    }
+}
+
+fn setup_level_select(mut commands: Commands, puzzle: Res<Puzzle>) {
+    commands.spawn((
+        LevelSelect,
+        NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                left: Val::Percent(30.0),
+                right: Val::Percent(30.0),
+                top: Val::Percent(15.0),
+                padding: UiRect::all(Val::Px(8.0)),
+                row_gap: Val::Px(6.0),
+                flex_direction: FlexDirection::Column,
+                ..default()
+            },
+            background_color: BackgroundColor(Color::DARK_GRAY),
+            z_index: ZIndex::Global(1),
+            ..default()
+        },
+    )).with_children(|parent| {
+        parent.spawn(TextBundle::from_section("SELECT A LEVEL", get_style()));
+        if puzzle.levels.is_empty() {
+            parent.spawn(TextBundle::from_section("no levels found", get_style()));
+        }
+
+        for (i, level) in puzzle.levels.iter().enumerate() {
+            parent.spawn((
+                LevelButton(i),
+                ButtonBundle {
+                    background_color: BackgroundColor(Color::DARK_GRAY),
+                    style: Style {
+                        border: UiRect::all(Val::Px(2.0)),
+                        padding: UiRect::all(Val::Px(4.0)),
+                        ..default()
+                    },
+                    border_color: BorderColor(Color::GRAY),
+                    ..default()
+                },
+            )).with_children(|button| {
+                button.spawn(TextBundle::from_sections([
+                    TextSection::new(format!("{}. {}\n", i + 1, level.name), get_style()),
+                    TextSection::new(level.description.clone(), TextStyle { color: Color::SILVER, ..get_style() }),
+                ]));
+            });
+        }
+    });
+}
+
+fn setup_puzzle_display(mut commands: Commands) {
+    commands.spawn((
+        PuzzleText,
+        TextBundle {
+            text: Text::from_section("", get_style()),
+            style: Style {
+                position_type: PositionType::Absolute,
+                left: Val::Percent(30.0),
+                top: Val::Percent(1.0),
+                ..default()
+            },
+            ..default()
+        },
+    ));
+}
+
+fn show_level_select<const VISIBLE: bool>(mut level_select: Query<&mut Visibility, With<LevelSelect>>) {
+    for mut visibility in &mut level_select {
+        *visibility = if VISIBLE { Visibility::Visible } else { Visibility::Hidden };
+    }
+}
+
+/// Pauses, and takes away the budget so that nothing can be painted until a level is (re)started
+fn stop_solving(mut next_state: ResMut<NextState<SimState>>, mut budget: ResMut<PaintBudget>) {
+    next_state.set(SimState::Paused);
+    *budget = PaintBudget::default();
+}
+
+fn highlight_level_buttons(
+    mut buttons: Query<(&Interaction, &mut BackgroundColor), (Changed<Interaction>, With<LevelButton>)>,
+) {
+    for (interaction, mut background_color) in &mut buttons {
+        background_color.0 = match *interaction {
+            Interaction::None => Color::DARK_GRAY,
+            Interaction::Hovered | Interaction::Pressed => Color::GRAY,
+        };
+    }
+}
+
+/// Marks the levels that have been solved
+fn update_level_buttons(
+    puzzle: Res<Puzzle>,
+    buttons: Query<(&LevelButton, &Children)>,
+    mut texts: Query<&mut Text>,
+) {
+    for (LevelButton(i), children) in &buttons {
+        for child in children {
+            if let Ok(mut text) = texts.get_mut(*child) {
+                let solved = if puzzle.solved[*i] { " [solved]" } else { "" };
+                text.sections[0].value = format!("{}. {}{solved}\n", i + 1, puzzle.levels[*i].name);
+            }
+        }
+    }
+}
+
+fn handle_level_buttons(world: &mut World) {
+    let pressed = world.query_filtered::<(&Interaction, &LevelButton), Changed<Interaction>>()
+        .iter(world)
+        .find(|(interaction, _)| **interaction == Interaction::Pressed)
+        .map(|(_, LevelButton(i))| *i);
+    if let Some(i) = pressed {
+        start_level(world, i);
+    }
+}
+
+/// - `Escape` goes back to the level select
+/// - `F5` restarts the level
+fn handle_puzzle_inputs(world: &mut World) {
+    let keys = world.resource::<ButtonInput<KeyCode>>();
+    let (escape, restart) = (keys.just_pressed(KeyCode::Escape), keys.just_pressed(KeyCode::F5));
+    let current = world.resource::<Puzzle>().current;
+
+    if escape {
+        world.resource_mut::<Puzzle>().current = None;
+        world.resource_mut::<NextState<PuzzleState>>().set(PuzzleState::Selecting);
+    } else if let (true, Some(current)) = (restart, current) {
+        start_level(world, current);
+    }
+}
+
+/// Lays out the level and its budget with the default gravity and topology, and starts solving it paused, so that the first cells can be painted before it runs
+fn start_level(world: &mut World, i: usize) {
+    let level = world.resource::<Puzzle>().levels[i].clone();
+    let (particles, force_field) = level.grids();
+
+    let mut puzzle = world.resource_mut::<Puzzle>();
+    puzzle.current = Some(i);
+    puzzle.ticks = 0;
+    puzzle.measures = level.goals.iter().map(|goal| goal.measure(&particles)).collect();
+    puzzle.outcome = None;
+
+    *world.query::<&mut PropertyGrid<Particle>>().single_mut(world) = particles;
+    *world.query::<&mut PropertyGrid<Vector>>().single_mut(world) = force_field;
+    world.query::<&mut StrokeHistory>().single_mut(world).clear();
+    if let Some(mut history) = world.get_resource_mut::<History>() {
+        history.clear();
+    }
+    world.insert_resource(PaintBudget::new(&level.budget));
+    world.insert_resource(Gravity::default());
+    world.insert_resource(Topology::default());
+    world.send_event(UnrecordedChange("starting a level"));
+    world.resource_mut::<NextState<SimState>>().set(SimState::Paused);
+    world.resource_mut::<NextState<PuzzleState>>().set(PuzzleState::Solving);
+}
+
+fn check_goals(
+    mut puzzle: ResMut<Puzzle>,
+    particles: Query<&PropertyGrid<Particle>>,
+    mut next_state: ResMut<NextState<PuzzleState>>,
+) {
+    let Some(level) = puzzle.level() else {
+        return;
+    };
+    let particles = particles.single();
+    let measures = level.goals.iter().map(|goal| goal.measure(particles)).collect::<Vec<_>>();
+    let statuses = level.goals.iter().zip(&measures).map(|(goal, measure)| goal.status(*measure)).collect::<Vec<_>>();
+    let outcome = level.outcome(&statuses, puzzle.ticks + 1);
+
+    puzzle.ticks += 1;
+    puzzle.measures = measures;
+    if let Some(outcome) = outcome {
+        if outcome == Outcome::Won {
+            let current = puzzle.current.expect("a level is being played");
+            puzzle.solved[current] = true;
+        }
+        puzzle.outcome = Some(outcome);
+        next_state.set(PuzzleState::Over);
+    }
+}
+
+fn update_puzzle_display(
+    puzzle: Res<Puzzle>,
+    budget: Res<PaintBudget>,
+    mut text: Query<&mut Text, With<PuzzleText>>,
+) {
+    if !puzzle.is_changed() && !budget.is_changed() {
+        return;
+    }
+
+    let Some(level) = puzzle.level() else {
+        text.single_mut().sections[0].value = String::new();
+        return;
+    };
+
+    let mut lines = vec![level.name.to_uppercase()];
+    lines.extend(level.goals.iter().zip(&puzzle.measures).map(|(goal, measure)| {
+        let mark = match goal.status(*measure) {
+            GoalStatus::Met => "[x]",
+            GoalStatus::Unmet => "[ ]",
+            GoalStatus::Broken => "[!]",
+        };
+        let value = match goal {
+            Goal::Fill { mass, .. } => format!("{measure:.1}/{mass:.1} kg"),
+            Goal::KeepBelow { .. } => format!("{measure:.1} K"),
+            Goal::KeepOut { .. } => format!("{measure:.1} kg"),
+        };
+        format!("{mark} {} ({value})", goal.describe())
+    }));
+
+    let ticks = match level.ticks {
+        Some(limit) => format!("tick {}/{limit}", puzzle.ticks),
+        None => format!("tick {}", puzzle.ticks),
+    };
+    let budget = budget.iter().map(|(name, cells)| format!("{name}: {cells}")).collect::<Vec<_>>();
+    lines.push(format!("{ticks}    budget: {}", budget.join(", ")));
+
+    lines.push(match &puzzle.outcome {
+        Some(Outcome::Won) => "SOLVED! F5 to replay, Esc for levels".into(),
+        Some(Outcome::Lost(reason)) => format!("FAILED: {reason}. F5 to retry, Esc for levels"),
+        None => "Space to run, F5 to restart, Esc for levels".into(),
+    });
+    text.single_mut().sections[0].value = lines.join("\n");
+}
+
+fn draw_goal_regions(
+    puzzle: Res<Puzzle>,
+    topology: Res<Topology>,
+    mut gizmos: Gizmos,
+) {
+    let Some(level) = puzzle.level() else {
+        return;
+    };
+
+    for goal in &level.goals {
+        let region = goal.region();
+        // centers of the lower-left and upper-right cells
+        let lower = grid_to_camera(RelCoords::from(region.lower()), *topology).xy();
+        let upper = grid_to_camera(RelCoords::from(region.upper()) - RelCoords::new(1, 1), *topology).xy();
+        let size = (upper - lower).abs() + cell_size(*topology);
+        let color = if goal.is_objective() { OBJECTIVE_COLOR } else { CONSTRAINT_COLOR };
+        gizmos.rect_2d((lower + upper) / 2.0, 0.0, size, color);
+    }
+}
@@ -0,0 +1,282 @@
+use std::collections::BTreeMap;
+use std::error::Error;
+
+use serde::{Deserialize, Serialize};
+
+use crate::sim::force_field::fans;
+use crate::sim::types::{Scalar, Vector};
+use crate::sim::{Coords, Particle, PropertyGrid};
+use crate::zero::Zero;
+
+/// A puzzle, as loaded from a RON file, e.g.
+///
+/// ```text
+/// (
+///     name: "Basin",
+///     description: "Fill the basin with water",
+///     terrain: [(region: (x: 40, y: 10, width: 48, height: 2), particle: "Reflective Wall")],
+///     budget: {"Water": 600},
+///     goals: [Fill(region: (x: 42, y: 12, width: 44, height: 8), material: "Water", mass: 300.0)],
+///     ticks: Some(1200),
+/// )
+/// ```
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Level {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    /// Painted in order onto a grid of vacuum, so later regions cover earlier ones
+    pub terrain: Vec<Terrain>,
+    /// Cells that can be painted of each palette entry, by name. Entries that aren't listed can't be painted.
+    pub budget: BTreeMap<String, usize>,
+    pub goals: Vec<Goal>,
+    /// Ticks the level lasts at most. Running out of them loses the level if it has objectives, and wins it otherwise.
+    #[serde(default)]
+    pub ticks: Option<u64>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Terrain {
+    pub region: Region,
+    /// "Reflective Wall", "Absorptive Wall", or the name of a particle to fill the region with at its default properties
+    pub particle: String,
+    /// Temperature of the particle, if not its default one
+    #[serde(default)]
+    pub temperature: Option<Scalar>,
+}
+
+impl Terrain {
+    pub fn resolve(&self) -> Option<Particle> {
+        let mut particle = Particle::from_name_or_wall(&self.particle)?;
+        if let (Some(temperature), Some(physical_properties)) = (self.temperature, particle.physical_properties_mut()) {
+            physical_properties.set_temperature(temperature);
+        }
+        Some(particle)
+    }
+}
+
+/// A rectangle of cells whose lower-left cell is at `x`, `y`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Region {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl Region {
+    pub fn lower(&self) -> Coords {
+        Coords::new(self.x, self.y)
+    }
+
+    /// The corner past the upper-right cell
+    pub fn upper(&self) -> Coords {
+        Coords::new(self.x.saturating_add(self.width), self.y.saturating_add(self.height))
+    }
+
+    /// Whether the region is nonempty and fits in a grid of size `dims`
+    pub fn fits(&self, dims: Coords) -> bool {
+        let upper = self.upper();
+        self.width > 0 && self.height > 0 && upper.x <= dims.x && upper.y <= dims.y
+    }
+
+    pub fn coords(&self) -> impl Iterator<Item = Coords> {
+        self.lower().to(self.upper())
+    }
+}
+
+/// Objectives have to be met at the same time to win, and breaking any constraint loses
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum Goal {
+    /// Objective: at least `mass` of `material` in the region
+    Fill { region: Region, material: String, mass: Scalar },
+    /// Constraint: no cell in the region gets as hot as `temperature`
+    KeepBelow { region: Region, temperature: Scalar },
+    /// Constraint: no `material` ever enters the region
+    KeepOut { region: Region, material: String },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GoalStatus {
+    Met,
+    /// An objective that isn't met yet
+    Unmet,
+    /// A constraint that was broken
+    Broken,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Outcome {
+    Won,
+    /// Lost for the given reason
+    Lost(String),
+}
+
+impl Level {
+    pub fn load(path: &str) -> Result<Self, Box<dyn Error>> {
+        let level: Self = ron::from_str(&std::fs::read_to_string(path)?)?;
+        level.validate(crate::sim::N_PIXELS)?;
+        Ok(level)
+    }
+
+    /// Checks that every region fits in a grid of size `dims` and that every name refers to something
+    pub fn validate(&self, dims: Coords) -> Result<(), String> {
+        let check_region = |region: &Region| match region.fits(dims) {
+            true => Ok(()),
+            false => Err(format!("the region {region:?} isn't within the {}x{} grid", dims.x, dims.y)),
+        };
+        let check_material = |material: &str| match Particle::from_name(material) {
+            Some(_) => Ok(()),
+            None => Err(format!("unknown material {material:?}")),
+        };
+
+        for terrain in &self.terrain {
+            check_region(&terrain.region)?;
+            terrain.resolve().ok_or_else(|| format!("unknown particle {:?}", terrain.particle))?;
+        }
+        for name in self.budget.keys() {
+            let is_fan = fans::ALL.iter().any(|fan| fan.name.eq_ignore_ascii_case(name));
+            if !is_fan && Particle::from_name(name).is_none() {
+                return Err(format!("the budget has an unknown palette entry {name:?}"));
+            }
+        }
+        for goal in &self.goals {
+            check_region(goal.region())?;
+            match goal {
+                Goal::Fill { material, .. } | Goal::KeepOut { material, .. } => check_material(material)?,
+                Goal::KeepBelow { .. } => (),
+            }
+        }
+        Ok(())
+    }
+
+    /// The grids the level starts with
+    pub fn grids(&self) -> (PropertyGrid<Particle>, PropertyGrid<Vector>) {
+        let mut particles = PropertyGrid::new(|_| Particle::Vacuum);
+        for terrain in &self.terrain {
+            let particle = terrain.resolve().expect("levels are validated when loaded");
+            for coords in terrain.region.coords() {
+                *particles.get_mut(coords) = particle;
+            }
+        }
+        (particles, PropertyGrid::new(|_| Vector::zero()))
+    }
+
+    /// The outcome after `ticks` ticks with the goals in `statuses`, if the level is over
+    pub fn outcome(&self, statuses: &[GoalStatus], ticks: u64) -> Option<Outcome> {
+        if let Some(broken) = statuses.iter().position(|status| *status == GoalStatus::Broken) {
+            return Some(Outcome::Lost(format!("broke \"{}\"", self.goals[broken].describe())));
+        }
+
+        let has_objectives = self.goals.iter().any(Goal::is_objective);
+        if has_objectives && statuses.iter().all(|status| *status == GoalStatus::Met) {
+            return Some(Outcome::Won);
+        }
+        match self.ticks {
+            Some(limit) if ticks >= limit && has_objectives => Some(Outcome::Lost("ran out of time".into())),
+            Some(limit) if ticks >= limit => Some(Outcome::Won),
+            _ => None,
+        }
+    }
+}
+
+impl Goal {
+    pub fn region(&self) -> &Region {
+        match self {
+            Self::Fill { region, .. } | Self::KeepBelow { region, .. } | Self::KeepOut { region, .. } => region,
+        }
+    }
+
+    pub fn is_objective(&self) -> bool {
+        matches!(self, Self::Fill { .. })
+    }
+
+    /// The quantity the goal is about: the mass of the material for `Fill` and `KeepOut`,
+    /// and the highest temperature for `KeepBelow`
+    pub fn measure(&self, particles: &PropertyGrid<Particle>) -> Scalar {
+        let cells = self.region().coords().map(|coords| particles.get(coords));
+        match self {
+            Self::Fill { material, .. } | Self::KeepOut { material, .. } => cells
+                .filter(|particle| particle.name().eq_ignore_ascii_case(material))
+                .filter_map(Particle::physical_properties)
+                .fold(0.0, |mass, physical_properties| mass + physical_properties.mass),
+            Self::KeepBelow { .. } => cells
+                .filter_map(Particle::physical_properties)
+                .map(|physical_properties| physical_properties.temperature())
+                .fold(0.0, Scalar::max),
+        }
+    }
+
+    pub fn status(&self, measure: Scalar) -> GoalStatus {
+        match self {
+            Self::Fill { mass, .. } if measure >= *mass => GoalStatus::Met,
+            Self::Fill { .. } => GoalStatus::Unmet,
+            Self::KeepBelow { temperature, .. } if measure >= *temperature => GoalStatus::Broken,
+            Self::KeepOut { .. } if measure > 0.0 => GoalStatus::Broken,
+            Self::KeepBelow { .. } | Self::KeepOut { .. } => GoalStatus::Met,
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        let Region { x, y, width, height } = *self.region();
+        match self {
+            Self::Fill { material, mass, .. } => format!("fill the {width}x{height} zone at ({x}, {y}) with {mass:.1} kg of {material}"),
+            Self::KeepBelow { temperature, .. } => format!("keep the {width}x{height} zone at ({x}, {y}) below {temperature:.1} K"),
+            Self::KeepOut { material, .. } => format!("keep {material} out of the {width}x{height} zone at ({x}, {y})"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sim::particle::defualts;
+
+    const LEVEL: &str = r#"(
+        name: "Basin",
+        terrain: [
+            (region: (x: 0, y: 0, width: 8, height: 1), particle: "reflective wall"),
+            (region: (x: 0, y: 1, width: 2, height: 2), particle: "water"),
+            (region: (x: 4, y: 4, width: 1, height: 1), particle: "air", temperature: Some(2.0)),
+        ],
+        budget: {"Water": 10, "Fan Up": 4},
+        goals: [
+            Fill(region: (x: 0, y: 1, width: 8, height: 2), material: "water", mass: 1.0),
+            KeepOut(region: (x: 6, y: 6, width: 2, height: 2), material: "air"),
+        ],
+        ticks: Some(100),
+    )"#;
+
+    #[test]
+    fn levels_are_loaded_and_laid_out() {
+        let level: Level = ron::from_str(LEVEL).unwrap();
+        assert_eq!(level.validate(Coords::new(8, 8)), Ok(()));
+        assert!(level.validate(Coords::new(4, 8)).is_err());
+
+        let (particles, _) = level.grids();
+        assert_eq!(*particles.get(Coords::new(7, 0)), defualts::WALL_REFLECTIVE);
+        assert_eq!(*particles.get(Coords::new(1, 2)), defualts::WATER);
+        assert_eq!(*particles.get(Coords::new(2, 2)), defualts::VACUUM);
+        let hot_air = particles.get(Coords::new(4, 4)).physical_properties().unwrap();
+        assert!((hot_air.temperature() - 2.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn objectives_win_and_constraints_lose() {
+        let level: Level = ron::from_str(LEVEL).unwrap();
+        let (mut particles, _) = level.grids();
+        let statuses = |particles: &PropertyGrid<Particle>| level.goals.iter()
+            .map(|goal| goal.status(goal.measure(particles)))
+            .collect::<Vec<_>>();
+
+        assert_eq!(statuses(&particles), [GoalStatus::Met, GoalStatus::Met]);
+        assert_eq!(level.outcome(&statuses(&particles), 0), Some(Outcome::Won));
+
+        *particles.get_mut(Coords::new(7, 7)) = defualts::AIR;
+        assert_eq!(statuses(&particles), [GoalStatus::Met, GoalStatus::Broken]);
+        assert!(matches!(level.outcome(&statuses(&particles), 0), Some(Outcome::Lost(_))));
+
+        assert_eq!(level.outcome(&[GoalStatus::Unmet, GoalStatus::Met], 99), None);
+        assert!(matches!(level.outcome(&[GoalStatus::Unmet, GoalStatus::Met], 100), Some(Outcome::Lost(_))));
+    }
+}
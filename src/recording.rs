@@ -0,0 +1,516 @@
+//! Recording a session to a file and replaying it, so that whatever happened in it can be reproduced exactly.
+//!
+//! A recording holds the grids and the seed of the `DrawRng` at the start of the session,
+//! and every input that changed the simulation, stamped with the number of ticks simulated before it.
+//! Replaying it runs those ticks with the inputs in between, either in the GUI or headlessly.
+//!
+//! Rewinding with the history, loading scenarios, starting levels, writing cells through the `control` server,
+//! and sharing a canvas over the network aren't recorded, so doing any of them stops the recording.
+
+use std::error::Error;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use bevy::ecs::event::Events;
+use bevy::ecs::schedule::ScheduleLabel;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::capture::FrameCapture;
+use crate::draw::{self, Brush, DrawRng, FanToDraw, Paint, ParticleToDraw, StrokeEvent, StrokeHistory};
+use crate::export::ExportSettings;
+use crate::schedule::{self, SimSet, SimState, TickCount, TickRate};
+use crate::sim::force_field::fans;
+use crate::sim::gravity::Gravity;
+use crate::sim::topology::Topology;
+use crate::sim::types::Vector;
+use crate::sim::{self, Particle, PropertyGrid};
+use crate::zero::Zero;
+
+/// Replays `replay` when set, in addition to letting the session be recorded
+pub struct RecordingPlugin {
+    pub replay: Option<Recording>,
+}
+
+impl Plugin for RecordingPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<Recorder>()
+            .add_event::<UnrecordedChange>()
+            .add_systems(Update, (stop_on_unrecorded_changes, handle_recording_inputs, record_inputs)
+                .chain()
+                .after(SimSet::Draw)
+                .before(schedule::step_once))
+            // again once the frame is over, for changes made after the inputs were recorded and before the next tick
+            .add_systems(PostUpdate, stop_on_unrecorded_changes)
+        ;
+
+        if let Some(recording) = &self.replay {
+            app
+                .insert_resource(Replay { replayer: Replayer::new(recording.clone()), budget: 0.0 })
+                .add_systems(ReplayTick, (sim::tick_systems(), schedule::count_tick).chain())
+                .add_systems(PostStartup, start_replay)
+                .add_systems(Update, (
+                    apply_replay_inputs.before(SimSet::Draw),
+                    run_replay_ticks.after(SimSet::Draw).before(SimSet::Recolor),
+                    hold_paused.run_if(not(in_state(SimState::Paused))),
+                ).run_if(resource_exists::<Replay>))
+            ;
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Recording {
+    /// Seed the `DrawRng` was reset to when the recording started
+    pub seed: u64,
+    pub particles: PropertyGrid<Particle>,
+    pub force_field: PropertyGrid<Vector>,
+    /// Number of ticks simulated while recording
+    pub ticks: u64,
+    /// Each input along with the number of ticks simulated before it, in the order they were made
+    pub inputs: Vec<(u64, Input)>,
+}
+
+/// Anything done during a session that affects the simulation
+#[derive(Clone, Serialize, Deserialize)]
+pub enum Input {
+    SelectParticle(Option<Particle>),
+    /// Fans are recorded by name
+    SelectFan(Option<String>),
+    SetThrow(bool),
+    /// The ticks each input is stamped with already say when the simulation ran,
+    /// so state changes are recorded for reference and are not replayed
+    SetState(SimState),
+    SetTopology(Topology),
+    SetGravity(Gravity),
+    Paint(Paint),
+    Stroke(StrokeEvent),
+}
+
+impl Recording {
+    pub fn load(path: &str) -> Result<Self, Box<dyn Error>> {
+        Ok(ron::from_str(&std::fs::read_to_string(path)?)?)
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), Box<dyn Error>> {
+        std::fs::write(path, ron::to_string(self)?)?;
+        Ok(())
+    }
+}
+
+/// Sent when the grids are changed in a way that isn't recorded, naming what changed them
+#[derive(Event, Clone, Copy, Debug)]
+pub struct UnrecordedChange(pub &'static str);
+
+/// The selections and settings that affect what inputs do, recorded whenever they change
+#[derive(Clone, PartialEq)]
+struct Settings {
+    particle: Option<Particle>,
+    fan: Option<&'static str>,
+    throw: bool,
+    state: SimState,
+    topology: Topology,
+    gravity: Gravity,
+}
+
+impl Settings {
+    /// The inputs that change `previous` into `self`, or that set all of `self` if there is nothing previous
+    fn changes_since(&self, previous: Option<&Self>) -> Vec<Input> {
+        let mut inputs = vec![];
+        if previous.is_none_or(|previous| previous.particle != self.particle) {
+            inputs.push(Input::SelectParticle(self.particle));
+        }
+        if previous.is_none_or(|previous| previous.fan != self.fan) {
+            inputs.push(Input::SelectFan(self.fan.map(String::from)));
+        }
+        if previous.is_none_or(|previous| previous.throw != self.throw) {
+            inputs.push(Input::SetThrow(self.throw));
+        }
+        if previous.is_none_or(|previous| previous.state != self.state) {
+            inputs.push(Input::SetState(self.state));
+        }
+        if previous.is_none_or(|previous| previous.topology != self.topology) {
+            inputs.push(Input::SetTopology(self.topology));
+        }
+        if previous.is_none_or(|previous| previous.gravity != self.gravity) {
+            inputs.push(Input::SetGravity(self.gravity.clone()));
+        }
+        inputs
+    }
+}
+
+#[derive(Resource, Default)]
+struct Recorder {
+    recording: Option<Recording>,
+    /// Ticks simulated before the recording started
+    start_tick: u64,
+    /// The settings as of the last input recorded, or `None` if nothing has been recorded yet
+    settings: Option<Settings>,
+}
+
+/// `F9` starts recording the session, and stops recording and saves it to a `.ron` file.
+///
+/// Starting a recording clears the undo history, since undoing strokes from before the recording couldn't be replayed.
+fn handle_recording_inputs(
+    mut recorder: ResMut<Recorder>,
+    mut rng: ResMut<DrawRng>,
+    mut stroke_history: Query<&mut StrokeHistory>,
+    particles: Query<&PropertyGrid<Particle>>,
+    force_field: Query<&PropertyGrid<Vector>>,
+    tick_count: Res<TickCount>,
+    keys: Res<ButtonInput<KeyCode>>,
+) {
+    if !keys.just_pressed(KeyCode::F9) {
+        return;
+    }
+
+    if let Some(recording) = recorder.recording.take() {
+        save_recording(recording, tick_count.0 - recorder.start_tick);
+        return;
+    }
+
+    let seed = rand::random();
+    *rng = DrawRng::from_seed(seed);
+    stroke_history.single_mut().clear();
+    *recorder = Recorder {
+        recording: Some(Recording {
+            seed,
+            particles: particles.single().clone(),
+            force_field: force_field.single().clone(),
+            ticks: 0,
+            inputs: vec![],
+        }),
+        start_tick: tick_count.0,
+        settings: None,
+    };
+    info!("started recording");
+}
+
+/// Stops recording when the grids are changed in a way that isn't recorded, and saves what was recorded up to then,
+/// since the rest of the session couldn't be replayed
+fn stop_on_unrecorded_changes(
+    mut recorder: ResMut<Recorder>,
+    tick_count: Res<TickCount>,
+    mut unrecorded_changes: EventReader<UnrecordedChange>,
+) {
+    let Some(UnrecordedChange(change)) = unrecorded_changes.read().last().copied() else {
+        return;
+    };
+    if let Some(recording) = recorder.recording.take() {
+        warn!("stopped recording, since {change} can't be recorded");
+        save_recording(recording, tick_count.0 - recorder.start_tick);
+    }
+}
+
+fn save_recording(mut recording: Recording, ticks: u64) {
+    recording.ticks = ticks;
+    let seconds = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+    let path = format!("recording-{seconds}.ron");
+    match recording.save(&path) {
+        Ok(()) => info!("saved a recording of {} ticks to {path}", recording.ticks),
+        Err(err) => error!("couldn't save recording to {path}: {err}"),
+    }
+}
+
+/// Records the settings that changed and the strokes made this frame, after they have been applied
+/// and before any tick that runs in the same frame
+fn record_inputs(
+    mut recorder: ResMut<Recorder>,
+    particle_to_draw: Query<&ParticleToDraw>,
+    fan_to_draw: Query<&FanToDraw>,
+    brush: Query<&Brush>,
+    state: Res<State<SimState>>,
+    topology: Res<Topology>,
+    gravity: Res<Gravity>,
+    tick_count: Res<TickCount>,
+    mut paints: EventReader<Paint>,
+    mut stroke_events: EventReader<StrokeEvent>,
+) {
+    let strokes = paints.read()
+        .map(|paint| Input::Paint(paint.clone()))
+        .chain(stroke_events.read().map(|stroke_event| Input::Stroke(*stroke_event)))
+        .collect::<Vec<_>>();
+
+    let recorder = &mut *recorder;
+    let Some(recording) = &mut recorder.recording else {
+        return;
+    };
+
+    let settings = Settings {
+        particle: particle_to_draw.single().0,
+        fan: fan_to_draw.single().0.map(|fan| fan.name),
+        throw: brush.single().throw,
+        state: *state.get(),
+        topology: *topology,
+        gravity: gravity.clone(),
+    };
+    let tick = tick_count.0 - recorder.start_tick;
+    let mut inputs = settings.changes_since(recorder.settings.as_ref());
+    // the strokes made in the frame the recording started are already in its grids
+    if recorder.settings.is_some() {
+        inputs.extend(strokes);
+    }
+
+    recording.inputs.extend(inputs.into_iter().map(|input| (tick, input)));
+    recorder.settings = Some(settings);
+}
+
+#[derive(ScheduleLabel, Debug, Clone, PartialEq, Eq, Hash)]
+struct ReplayTick;
+
+#[derive(ScheduleLabel, Debug, Clone, PartialEq, Eq, Hash)]
+struct ReplayPaint;
+
+/// Steps through a recording, applying its inputs to a world as their ticks come up
+pub struct Replayer {
+    recording: Recording,
+    /// Ticks replayed so far
+    tick: u64,
+    /// Index of the first input that hasn't been applied
+    next_input: usize,
+}
+
+impl Replayer {
+    pub fn new(recording: Recording) -> Self {
+        Self { recording, tick: 0, next_input: 0 }
+    }
+
+    /// Resets the world to the start of the recording
+    pub fn start(&self, world: &mut World) {
+        *world.query::<&mut PropertyGrid<Particle>>().single_mut(world) = self.recording.particles.clone();
+        *world.query::<&mut PropertyGrid<Vector>>().single_mut(world) = self.recording.force_field.clone();
+        world.query::<&mut StrokeHistory>().single_mut(world).clear();
+        world.insert_resource(DrawRng::from_seed(self.recording.seed));
+    }
+
+    /// Applies the inputs made before the next tick. Strokes are sent as events, to be painted by `draw::paint_systems`.
+    pub fn apply_inputs(&mut self, world: &mut World) {
+        while let Some((tick, input)) = self.recording.inputs.get(self.next_input) {
+            if *tick > self.tick {
+                break;
+            }
+            apply_input(world, input);
+            self.next_input += 1;
+        }
+    }
+
+    /// Number of ticks that can be run before more inputs have to be applied
+    pub fn ticks_until_next_input(&self) -> u64 {
+        let next_tick = self.recording.inputs.get(self.next_input).map_or(self.recording.ticks, |(tick, _)| *tick);
+        next_tick.saturating_sub(self.tick)
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.tick >= self.recording.ticks && self.next_input >= self.recording.inputs.len()
+    }
+}
+
+fn apply_input(world: &mut World, input: &Input) {
+    match input {
+        Input::SelectParticle(particle) => world.query::<&mut ParticleToDraw>().single_mut(world).0 = *particle,
+        Input::SelectFan(name) => {
+            let fan = name.as_ref().and_then(|name| fans::ALL.into_iter().find(|fan| fan.name == name));
+            world.query::<&mut FanToDraw>().single_mut(world).0 = fan;
+        },
+        Input::SetThrow(throw) => world.query::<&mut Brush>().single_mut(world).throw = *throw,
+        Input::SetState(_) => (),
+        Input::SetTopology(topology) => *world.resource_mut::<Topology>() = *topology,
+        Input::SetGravity(gravity) => *world.resource_mut::<Gravity>() = gravity.clone(),
+        Input::Paint(paint) => {
+            world.send_event(paint.clone());
+        },
+        Input::Stroke(stroke_event) => {
+            world.send_event(*stroke_event);
+        },
+    }
+}
+
+/// Replays the recording without a window, and returns the world as it was at the end of the recording.
+/// `each_tick` is called with the world as of each tick, starting with the start of the recording.
+pub fn replay_headless(recording: Recording, mut each_tick: impl FnMut(&mut World)) -> World {
+    let mut world = World::new();
+    world.init_resource::<Topology>();
+    world.init_resource::<Gravity>();
+    world.init_resource::<Events<Paint>>();
+    world.init_resource::<Events<StrokeEvent>>();
+    world.spawn(PropertyGrid::<Particle>::default());
+    world.spawn(PropertyGrid::<Vector>::zero());
+    world.spawn(ParticleToDraw(None));
+    world.spawn(FanToDraw(None));
+    world.spawn(Brush::default());
+    world.spawn(StrokeHistory::default());
+
+    let mut paint_schedule = Schedule::new(ReplayPaint);
+    paint_schedule.add_systems(draw::paint_systems());
+    let mut tick_schedule = Schedule::new(ReplayTick);
+    tick_schedule.add_systems(sim::tick_systems());
+
+    let mut replayer = Replayer::new(recording);
+    replayer.start(&mut world);
+    each_tick(&mut world);
+    loop {
+        replayer.apply_inputs(&mut world);
+        paint_schedule.run(&mut world);
+        world.resource_mut::<Events<Paint>>().update();
+        world.resource_mut::<Events<StrokeEvent>>().update();
+
+        if replayer.is_finished() {
+            return world;
+        }
+        tick_schedule.run(&mut world);
+        replayer.tick += 1;
+        each_tick(&mut world);
+    }
+}
+
+/// Replays the recording without a window and prints what the grid ended up holding, capturing frames of it along the way if `capture` is set.
+/// The fields are exported with `export` if set, either every few ticks or once the replay ends.
+pub fn print_headless_replay(recording: Recording, mut capture: Option<FrameCapture>, mut export: Option<ExportSettings>) {
+    let ticks = recording.ticks;
+    let mut tick = 0;
+    let mut exports = 0;
+    let mut world = replay_headless(recording, |world| {
+        let topology = *world.resource::<Topology>();
+        let particles = world.query::<&PropertyGrid<Particle>>().single(world);
+        if let Some(frame_capture) = &mut capture {
+            if let Err(err) = frame_capture.observe(particles, topology) {
+                eprintln!("stopped capturing frames to {}: {err}", frame_capture.path().display());
+                capture = None;
+            }
+        }
+        if let Some(settings) = export.as_ref().filter(|settings| settings.is_due(tick)) {
+            match settings.export(particles, topology, tick) {
+                Ok(_) => exports += 1,
+                Err(err) => {
+                    eprintln!("stopped exporting the fields to {}: {err}", settings.dir.display());
+                    export = None;
+                },
+            }
+        }
+        tick += 1;
+    });
+    let topology = *world.resource::<Topology>();
+    let particles = world.query::<&PropertyGrid<Particle>>().single(&world);
+    let [vacuum, air, water, wall] = sim::cell_counts(particles);
+    println!("replayed {ticks} ticks, ending with {vacuum} vacuum, {air} air, {water} water, and {wall} wall cells");
+    if let Some(capture) = capture {
+        println!("captured {} frames to {}", capture.frames(), capture.path().display());
+    }
+    if let Some(settings) = export {
+        if settings.every.is_none() {
+            match settings.export(particles, topology, ticks) {
+                Ok(dir) => println!("exported the fields to {}", dir.display()),
+                Err(err) => eprintln!("couldn't export the fields to {}: {err}", settings.dir.display()),
+            }
+        } else {
+            println!("exported the fields {exports} times to {}", settings.dir.display());
+        }
+    }
+}
+
+/// A recording being replayed in the GUI
+#[derive(Resource)]
+struct Replay {
+    replayer: Replayer,
+    /// Ticks that real time has made room for but that haven't been run
+    budget: f64,
+}
+
+fn start_replay(world: &mut World) {
+    world.resource_scope(|world, replay: Mut<Replay>| replay.replayer.start(world));
+    world.resource_mut::<NextState<SimState>>().set(SimState::Paused);
+}
+
+fn apply_replay_inputs(world: &mut World) {
+    world.resource_scope(|world, mut replay: Mut<Replay>| replay.replayer.apply_inputs(world));
+}
+
+/// Runs as many ticks as real time allows at the current speed, stopping at the next input.
+/// The ticks are run here instead of in `FixedMain` so that they line up exactly with the inputs.
+fn run_replay_ticks(world: &mut World) {
+    let delta_seconds = world.resource::<Time<Real>>().delta_seconds_f64();
+    let tick_rate = *world.resource::<TickRate>();
+
+    world.resource_scope(|world, mut replay: Mut<Replay>| {
+        replay.budget = (replay.budget + delta_seconds * tick_rate.target_ticks_per_second())
+            .min(tick_rate.max_ticks_per_frame as f64);
+        while replay.budget >= 1.0 && replay.replayer.ticks_until_next_input() > 0 {
+            world.run_schedule(ReplayTick);
+            replay.replayer.tick += 1;
+            replay.budget -= 1.0;
+        }
+    });
+
+    if world.resource::<Replay>().replayer.is_finished() {
+        let replay = world.remove_resource::<Replay>().unwrap();
+        info!("finished replaying {} ticks", replay.replayer.tick);
+    }
+}
+
+/// The replay runs the ticks itself, so the simulation stays paused while it does
+fn hold_paused(mut next_state: ResMut<NextState<SimState>>) {
+    next_state.set(SimState::Paused);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::draw::PaintCells;
+    use crate::sim::particle::defualts;
+    use crate::sim::Coords;
+
+    fn splash() -> Recording {
+        let paint = |cells, erase| Input::Paint(Paint { cells, erase, cursor_velocity: Vector::new(0.5, 0.0) });
+        Recording {
+            seed: 7,
+            particles: PropertyGrid::new(|coords| if coords.y < 8 { defualts::WATER } else { defualts::VACUUM }),
+            force_field: PropertyGrid::zero(),
+            ticks: 30,
+            inputs: vec![
+                (0, Input::SelectParticle(Some(defualts::WATER))),
+                (0, Input::SetThrow(true)),
+                (0, paint(PaintCells::Cells((40..60).map(|x| Coords::new(x, 40)).collect()), false)),
+                (0, Input::Stroke(StrokeEvent::Finish)),
+                (10, Input::SetTopology(Topology::Hexagonal)),
+                (10, paint(PaintCells::Cells((20..30).map(|x| Coords::new(x, 60)).collect()), false)),
+                (20, paint(PaintCells::FloodFill(Coords::new(0, 100)), true)),
+                (25, Input::Stroke(StrokeEvent::Undo)),
+            ],
+        }
+    }
+
+    fn final_particles(recording: Recording) -> PropertyGrid<Particle> {
+        let mut world = replay_headless(recording, |_| ());
+        world.query::<&PropertyGrid<Particle>>().single(&world).clone()
+    }
+
+    #[test]
+    fn replays_are_identical() {
+        let recording = splash();
+        let saved = ron::to_string(&recording).unwrap();
+        let loaded = ron::from_str(&saved).unwrap();
+
+        let particles = final_particles(recording.clone());
+        assert!(particles != recording.particles);
+        assert!(final_particles(recording) == particles);
+        assert!(final_particles(loaded) == particles);
+    }
+
+    #[test]
+    fn only_changed_settings_are_recorded() {
+        let settings = Settings {
+            particle: Some(defualts::AIR),
+            fan: None,
+            throw: false,
+            state: SimState::Playing,
+            topology: Topology::VonNeumann,
+            gravity: Gravity::default(),
+        };
+        assert_eq!(settings.changes_since(None).len(), 6);
+        assert!(settings.changes_since(Some(&settings)).is_empty());
+
+        let paused = Settings { state: SimState::Paused, ..settings.clone() };
+        let changes = paused.changes_since(Some(&settings));
+        assert!(matches!(changes[..], [Input::SetState(SimState::Paused)]));
+    }
+}
@@ -0,0 +1,121 @@
+//! Setting up the grids and gravity from a scenario, either on startup or when its file is dropped onto the window.
+//!
+//! Scenarios are RON files, described in `description`.
+//! The window only shows grids of `N_PIXELS`, so the scenarios loaded into it have to be that size,
+//! while headless apps, e.g. with `--headless --control`, take scenarios of any size.
+
+pub mod description;
+
+use bevy::prelude::*;
+
+use crate::draw::StrokeHistory;
+use crate::history::History;
+use crate::recording::UnrecordedChange;
+use crate::sim::gravity::Gravity;
+use crate::sim::types::Vector;
+use crate::sim::{Particle, PropertyGrid, N_PIXELS};
+use description::Scenario;
+
+/// Loads `scenario` on startup if set, and loads the scenarios dropped onto the window, if there is one
+pub struct ScenarioPlugin {
+    pub scenario: Option<Scenario>,
+}
+
+impl Plugin for ScenarioPlugin {
+    fn build(&self, app: &mut App) {
+        if let Some(scenario) = &self.scenario {
+            app
+                .insert_resource(InitialScenario(scenario.clone()))
+                // after the grids are spawned
+                .add_systems(PostStartup, load_initial_scenario)
+            ;
+        }
+        if app.world.contains_resource::<Events<FileDragAndDrop>>() {
+            app.add_systems(Update, load_dropped_scenarios.run_if(not(crate::puzzle::is_solving)));
+        }
+    }
+}
+
+#[derive(Resource)]
+struct InitialScenario(Scenario);
+
+/// Checks that the window can show the scenario's grid
+pub fn check_fits_window(scenario: &Scenario) -> Result<(), String> {
+    match scenario.size == N_PIXELS {
+        true => Ok(()),
+        false => Err(format!(
+            "the window only shows a {}x{} grid, but the scenario's is {}x{}, which can only be run with --headless",
+            N_PIXELS.x, N_PIXELS.y, scenario.size.x, scenario.size.y,
+        )),
+    }
+}
+
+pub(crate) fn load_initial_scenario(world: &mut World) {
+    if let Some(InitialScenario(scenario)) = world.remove_resource::<InitialScenario>() {
+        load_scenario(world, &scenario);
+    }
+}
+
+fn load_dropped_scenarios(
+    mut drops: EventReader<FileDragAndDrop>,
+    mut commands: Commands,
+) {
+    for drop in drops.read() {
+        let FileDragAndDrop::DroppedFile { path_buf, .. } = drop else {
+            continue;
+        };
+        let scenario = Scenario::load(path_buf).and_then(|scenario| {
+            check_fits_window(&scenario).map_err(|err| format!("{}: {err}", path_buf.display()))?;
+            Ok(scenario)
+        });
+        match scenario {
+            Ok(scenario) => {
+                info!("loaded scenario {}", path_buf.display());
+                commands.add(move |world: &mut World| {
+                    load_scenario(world, &scenario);
+                    world.send_event(UnrecordedChange("loading a scenario"));
+                });
+            },
+            Err(err) => error!("couldn't load scenario {err}"),
+        }
+    }
+}
+
+/// Replaces the grids and gravity with the scenario's, and forgets the strokes and ticks from before
+fn load_scenario(world: &mut World, scenario: &Scenario) {
+    let (particles, force_field) = scenario.grids();
+    *world.query::<&mut PropertyGrid<Particle>>().single_mut(world) = particles;
+    *world.query::<&mut PropertyGrid<Vector>>().single_mut(world) = force_field;
+    *world.resource_mut::<Gravity>() = scenario.gravity.clone();
+    for mut stroke_history in world.query::<&mut StrokeHistory>().iter_mut(world) {
+        stroke_history.clear();
+    }
+    if let Some(mut history) = world.get_resource_mut::<History>() {
+        history.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sim::{Coords, Particle};
+
+    #[test]
+    fn headless_apps_load_scenarios_of_any_size() {
+        let scenario = Scenario::parse(r#"(
+            size: (x: 40, y: 24),
+            shapes: [(shape: Rect(x: 2, y: 2, width: 4, height: 4), material: "Water")],
+        )"#).unwrap();
+        assert!(check_fits_window(&scenario).is_err());
+
+        let mut app = crate::headless_app();
+        app.add_plugins(ScenarioPlugin { scenario: Some(scenario) });
+        app.update();
+
+        let particles = app.world.query::<&PropertyGrid<Particle>>().single(&app.world);
+        assert_eq!(particles.dims(), Coords::new(40, 24));
+        assert!(matches!(particles.get(Coords::new(3, 3)), Particle::Water { .. }));
+        let force_field = app.world.query::<&PropertyGrid<Vector>>().single(&app.world);
+        assert_eq!(force_field.dims(), Coords::new(40, 24));
+    }
+}
@@ -0,0 +1,327 @@
+use std::error::Error;
+use std::path::Path;
+
+use ron::extensions::Extensions;
+use serde::de::Error as _;
+use serde::{Deserialize, Deserializer};
+
+use crate::sim::gravity::Gravity;
+use crate::sim::particle::defualts;
+use crate::sim::path;
+use crate::sim::types::{Scalar, Vector};
+use crate::sim::{Coords, Particle, PropertyGrid, RelCoords, N_PIXELS};
+use crate::zero::Zero;
+
+/// A starting state for the simulation, as loaded from a RON file, e.g.
+///
+/// ```text
+/// (
+///     size: (x: 128, y: 128),
+///     boundary: (left: Reflective, right: Reflective, bottom: Absorptive),
+///     gravity: (uniform: (0.0, -0.02)),
+///     shapes: [
+///         (shape: Rect(x: 8, y: 1, width: 30, height: 60), material: "Water", temperature: 1.2),
+///         (shape: Circle(center: (90.0, 40.0), radius: 12.0), material: "Air", velocity: (-0.5, 0.0)),
+///         (shape: Outline([(60.0, 1.0), (70.0, 20.0), (80.0, 1.0)]), material: "Reflective Wall"),
+///     ],
+/// )
+/// ```
+///
+/// Positions are in cells, with `(x, y)` being the center of the cell at `x`, `y`.
+/// Every top-level field can be left out, and optional values can be written without `Some`.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Scenario {
+    /// Dimensions of the grid, which is `N_PIXELS` if left out.
+    /// The window can only show grids of `N_PIXELS`, so other sizes only work headless.
+    #[serde(default = "default_size", deserialize_with = "nonempty_size")]
+    pub size: Coords,
+    #[serde(default)]
+    pub boundary: Boundary,
+    /// The default gravity, with any of its fields replaced
+    #[serde(default)]
+    pub gravity: Gravity,
+    /// Filled in order onto a grid of vacuum, so later shapes cover earlier ones
+    #[serde(default)]
+    pub shapes: Vec<Fill>,
+}
+
+/// What each edge of the grid is lined with. Edges that are left out are left as they are.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Boundary {
+    pub left: Edge,
+    pub right: Edge,
+    pub bottom: Edge,
+    pub top: Edge,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+pub enum Edge {
+    /// The bare edge of the grid, which gas bounces off and liquid stops at
+    #[default]
+    Bare,
+    /// A line of reflective wall along the edge
+    Reflective,
+    /// A line of absorptive wall along the edge
+    Absorptive,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Fill {
+    pub shape: Shape,
+    /// "Reflective Wall", "Absorptive Wall", or the name of a particle
+    #[serde(deserialize_with = "particle_by_name")]
+    pub material: Particle,
+    /// Temperature of the material, if not its default one
+    #[serde(default, deserialize_with = "valid_temperature")]
+    pub temperature: Option<Scalar>,
+    /// Velocity of the material in cells per tick, if it isn't at rest
+    #[serde(default, deserialize_with = "finite_velocity")]
+    pub velocity: Option<Vector>,
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub enum Shape {
+    /// The cells from the one at `x`, `y` up to but not including the one at `x + width`, `y + height`
+    Rect { x: usize, y: usize, width: usize, height: usize },
+    /// The cells whose centers are within `radius` of `center`
+    Circle { center: Vector, radius: Scalar },
+    /// The cells whose centers are inside the polygon with the given vertices
+    Polygon(Vec<Vector>),
+    /// The cells along the edges of the polygon with the given vertices, without any diagonal gaps,
+    /// so that an outline of walls holds in what it surrounds
+    Outline(Vec<Vector>),
+}
+
+fn default_size() -> Coords {
+    N_PIXELS
+}
+
+fn nonempty_size<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Coords, D::Error> {
+    let size = Coords::deserialize(deserializer)?;
+    match size.x > 0 && size.y > 0 {
+        true => Ok(size),
+        false => Err(D::Error::custom(format!("the grid has to have cells, but its size is {}x{}", size.x, size.y))),
+    }
+}
+
+/// Checked while parsing, like the velocity, so that values the simulation can't run with are reported where they are
+fn valid_temperature<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Scalar>, D::Error> {
+    let temperature = Option::<Scalar>::deserialize(deserializer)?;
+    match temperature {
+        Some(temperature) if !(temperature.is_finite() && temperature >= 0.0) => {
+            Err(D::Error::custom(format!("the temperature has to be finite and at least 0, but it is {temperature}")))
+        },
+        _ => Ok(temperature),
+    }
+}
+
+fn finite_velocity<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Vector>, D::Error> {
+    let velocity = Option::<Vector>::deserialize(deserializer)?;
+    match velocity {
+        Some(velocity) if !velocity.is_finite() => Err(D::Error::custom(format!("the velocity has to be finite, but it is {velocity}"))),
+        _ => Ok(velocity),
+    }
+}
+
+/// Resolved while parsing, so that unknown names are reported where they are
+fn particle_by_name<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Particle, D::Error> {
+    let name = String::deserialize(deserializer)?;
+    Particle::from_name_or_wall(&name).ok_or_else(|| D::Error::custom(format!("unknown material {name:?}")))
+}
+
+impl Scenario {
+    /// Loads the scenario at `path`, with any errors in it reported as `path:line:column: error`
+    pub fn load(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let text = std::fs::read_to_string(path).map_err(|err| format!("{}: {err}", path.display()))?;
+        Ok(Self::parse(&text).map_err(|err| format!("{}:{err}", path.display()))?)
+    }
+
+    pub fn parse(text: &str) -> Result<Self, ron::error::SpannedError> {
+        ron::Options::default()
+            .with_default_extension(Extensions::IMPLICIT_SOME)
+            .from_str(text)
+    }
+
+    /// The grids the scenario starts with
+    pub fn grids(&self) -> (PropertyGrid<Particle>, PropertyGrid<Vector>) {
+        let mut particles = PropertyGrid::with_dims(self.size, |_| Particle::Vacuum);
+        for fill in &self.shapes {
+            let particle = fill.particle();
+            for coords in fill.shape.cells(self.size) {
+                *particles.get_mut(coords) = particle;
+            }
+        }
+        for coords in particles.coords() {
+            if let Some(wall) = self.boundary.wall_at(coords, self.size) {
+                *particles.get_mut(coords) = wall;
+            }
+        }
+        (particles, PropertyGrid::with_dims(self.size, |_| Vector::zero()))
+    }
+}
+
+impl Boundary {
+    /// The wall lining the edge that the cell at `coords` is on, if any
+    fn wall_at(&self, coords: Coords, dims: Coords) -> Option<Particle> {
+        [
+            (coords.x == 0, self.left),
+            (coords.x == dims.x - 1, self.right),
+            (coords.y == 0, self.bottom),
+            (coords.y == dims.y - 1, self.top),
+        ]
+            .into_iter()
+            .filter(|(on_edge, _)| *on_edge)
+            .find_map(|(_, edge)| edge.wall())
+    }
+}
+
+impl Edge {
+    pub fn wall(&self) -> Option<Particle> {
+        match self {
+            Self::Bare => None,
+            Self::Reflective => Some(defualts::WALL_REFLECTIVE),
+            Self::Absorptive => Some(defualts::WALL_ABSORPTIVE),
+        }
+    }
+}
+
+impl Fill {
+    /// The material with the fill's temperature and velocity
+    pub fn particle(&self) -> Particle {
+        let mut particle = self.material;
+        if let Some(physical_properties) = particle.physical_properties_mut() {
+            if let Some(temperature) = self.temperature {
+                physical_properties.set_temperature(temperature);
+            }
+            if let Some(velocity) = self.velocity {
+                physical_properties.set_velocity(velocity);
+            }
+        }
+        particle
+    }
+}
+
+impl Shape {
+    /// The cells of a grid of size `dims` that the shape covers, leaving out any parts that are outside of it
+    pub fn cells(&self, dims: Coords) -> Vec<Coords> {
+        let center = |coords: Coords| Vector::new(coords.x as Scalar, coords.y as Scalar);
+        match self {
+            Self::Rect { x, y, width, height } => {
+                let upper = Coords::new(x.saturating_add(*width).min(dims.x), y.saturating_add(*height).min(dims.y));
+                if *x >= upper.x || *y >= upper.y {
+                    return vec![];
+                }
+                Coords::new(*x, *y).to(upper).collect()
+            },
+            Self::Circle { center: circle_center, radius } => Coords::ZERO.to(dims)
+                .filter(|coords| center(*coords).distance(*circle_center) <= *radius)
+                .collect(),
+            Self::Polygon(vertices) => Coords::ZERO.to(dims)
+                .filter(|coords| polygon_contains(vertices, center(*coords)))
+                .collect(),
+            Self::Outline(vertices) => {
+                let edges = vertices.iter().zip(vertices.iter().cycle().skip(1));
+                // offset so that the path runs between the centers of the cells rather than their corners
+                let offset = Vector::new(0.5, 0.5);
+                edges
+                    .flat_map(|(start, end)| path::get_path(*start + offset, *end + offset))
+                    .filter_map(|coords: RelCoords| Coords::try_from(coords).ok())
+                    .filter(|coords| coords.x < dims.x && coords.y < dims.y)
+                    .collect()
+            },
+        }
+    }
+}
+
+/// Whether `point` is inside the polygon with the given vertices, counting the crossings of a ray to its right
+fn polygon_contains(vertices: &[Vector], point: Vector) -> bool {
+    let edges = vertices.iter().zip(vertices.iter().cycle().skip(1));
+    edges
+        .filter(|(start, end)| (start.y > point.y) != (end.y > point.y))
+        .filter(|(start, end)| point.x < start.x + (point.y - start.y) / (end.y - start.y) * (end.x - start.x))
+        .count() % 2 == 1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SCENARIO: &str = r#"(
+        size: (x: 16, y: 12),
+        boundary: (left: Reflective, bottom: Absorptive),
+        gravity: (uniform: (0.0, -0.02)),
+        shapes: [
+            (shape: Rect(x: 1, y: 1, width: 4, height: 2), material: "water", temperature: 2.0),
+            (shape: Circle(center: (10.0, 6.0), radius: 2.0), material: "Air", velocity: (1.0, 0.0)),
+            (shape: Polygon([(1.0, 6.0), (5.0, 6.0), (1.0, 10.0)]), material: "Water"),
+            (shape: Outline([(8.0, 1.0), (14.0, 1.0), (14.0, 3.0)]), material: "reflective wall"),
+        ],
+    )"#;
+
+    #[test]
+    fn shapes_are_filled_in_order() {
+        let scenario = Scenario::parse(SCENARIO).unwrap();
+        assert_eq!(scenario.gravity, Gravity { uniform: Vector::new(0.0, -0.02), ..Gravity::default() });
+        let (particles, force_field) = scenario.grids();
+        assert_eq!(particles.dims(), Coords::new(16, 12));
+        assert_eq!(force_field.dims(), Coords::new(16, 12));
+
+        // the boundary covers the shapes, and the left edge comes before the bottom one
+        assert_eq!(*particles.get(Coords::new(0, 0)), defualts::WALL_REFLECTIVE);
+        assert_eq!(*particles.get(Coords::new(1, 0)), defualts::WALL_ABSORPTIVE);
+        assert_eq!(*particles.get(Coords::new(15, 11)), defualts::VACUUM);
+
+        let warm_water = particles.get(Coords::new(4, 2)).physical_properties().unwrap();
+        assert!((warm_water.temperature() - 2.0).abs() < 1e-4);
+        assert_eq!(*particles.get(Coords::new(5, 2)), defualts::VACUUM);
+
+        let moving_air = particles.get(Coords::new(12, 6)).physical_properties().unwrap();
+        assert_eq!(moving_air.velocity(), Vector::new(1.0, 0.0));
+        assert_eq!(*particles.get(Coords::new(12, 7)), defualts::VACUUM);
+
+        assert_eq!(*particles.get(Coords::new(2, 7)), defualts::WATER);
+        assert_eq!(*particles.get(Coords::new(4, 9)), defualts::VACUUM);
+    }
+
+    #[test]
+    fn outlines_have_no_diagonal_gaps() {
+        let outline = Shape::Outline(vec![Vector::new(1.0, 1.0), Vector::new(6.0, 4.0), Vector::new(1.0, 4.0)]);
+        let cells = outline.cells(Coords::new(8, 8));
+        for coords in &cells {
+            let neighbors = cells.iter().filter(|other| coords.x.abs_diff(other.x) + coords.y.abs_diff(other.y) == 1).count();
+            assert!(neighbors >= 2, "{coords:?} is only connected to {neighbors} cells");
+        }
+        assert!(cells.contains(&Coords::new(6, 4)));
+    }
+
+    #[test]
+    fn errors_say_where_they_are() {
+        let err = Scenario::parse("(\n    shapes: [\n        (shape: Rect(x: 0, y: 0, width: 1, height: 1), material: \"Lava\"),\n    ],\n)").unwrap_err();
+        assert_eq!(err.position.line, 3);
+        assert!(err.to_string().contains("unknown material \"Lava\""), "{err}");
+
+        let err = Scenario::parse("(\n    size: (x: 0, y: 8),\n)").unwrap_err();
+        assert_eq!(err.position.line, 2);
+
+        let err = Scenario::parse("(\n    shapes: [],\n    gravty: (),\n)").unwrap_err();
+        assert_eq!(err.position.line, 3);
+
+        // values the simulation can't run with
+        let fill = |values| format!("(\n    shapes: [\n        (shape: Rect(x: 0, y: 0, width: 1, height: 1), material: \"Air\"),\n        (shape: Rect(x: 0, y: 0, width: 1, height: 1), material: \"Water\", {values}),\n    ],\n)");
+
+        for values in ["temperature: -1.0", "temperature: inf", "temperature: NaN"] {
+            let err = Scenario::parse(&fill(values)).unwrap_err();
+            assert_eq!(err.position.line, 4, "{values}");
+            assert!(err.to_string().contains("temperature"), "{err}");
+        }
+
+        let err = Scenario::parse(&fill("velocity: (NaN, 0.0)")).unwrap_err();
+        assert_eq!(err.position.line, 4);
+        assert!(err.to_string().contains("velocity"), "{err}");
+
+        assert!(Scenario::parse(&fill("temperature: 0.0, velocity: (-1.0, 2.0)")).is_ok());
+    }
+}
@@ -1,45 +1,120 @@
+mod timing;
+
+use std::time::Duration;
+
+use bevy::app::FixedMain;
 use bevy::prelude::*;
+use bevy::diagnostic::{Diagnostic, DiagnosticPath, Diagnostics, RegisterDiagnostic};
+use serde::{Deserialize, Serialize};
+
+pub use timing::TimingPlugin;
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash, SystemSet)]
 pub enum SimSet {
     Gravity,
+    Force,
     Gas,
     Liquid,
     Draw,
     Recolor,
 }
 
-#[derive(States, Debug, Hash, PartialEq, Eq, Clone, Copy)]
+#[derive(States, Debug, Hash, PartialEq, Eq, Clone, Copy, Serialize, Deserialize)]
 pub enum SimState {
     Playing,
     Paused,
     Stepping,
 }
 
+/// How often the simulation ticks, independent of the frame rate
+#[derive(Resource, Debug, Clone, Copy)]
+pub struct TickRate {
+    /// Ticks per second at normal speed
+    pub ticks_per_second: f64,
+    /// Multiplier on `ticks_per_second`, e.g. 0.25 for slow motion or 4.0 for fast-forward
+    pub speed: f64,
+    /// When the simulation falls behind, at most this many ticks are run to catch up in a single frame
+    pub max_ticks_per_frame: u32,
+}
+
+impl Default for TickRate {
+    fn default() -> Self {
+        Self {
+            ticks_per_second: 60.0,
+            speed: 1.0,
+            max_ticks_per_frame: 8,
+        }
+    }
+}
+
+impl TickRate {
+    #[cfg(feature = "gui")]
+    pub fn target_ticks_per_second(&self) -> f64 {
+        self.ticks_per_second * self.speed
+    }
+}
+
+/// Number of ticks simulated so far
+#[derive(Resource, Debug, Default, Clone, Copy)]
+pub struct TickCount(pub u64);
+
+/// Simulated time elapsed so far, i.e., the sum of the timesteps of the ticks simulated so far
+#[derive(Resource, Debug, Default, Clone, Copy)]
+pub struct SimulatedTime(pub Duration);
+
+const SPEED_STEP: f64 = 2.0;
+const MIN_SPEED: f64 = 1.0 / 64.0;
+const MAX_SPEED: f64 = 64.0;
+
 pub struct SchedulePlugin;
 
 impl Plugin for SchedulePlugin {
     fn build(&self, app: &mut App) {
         app
             .insert_state(SimState::Playing)
+            .init_resource::<TickRate>()
+            .init_resource::<TickCount>()
+            .init_resource::<SimulatedTime>()
+            .add_plugins(TimingPlugin)
+            .register_diagnostic(Diagnostic::new(Self::TPS))
             .add_systems(Update, (
                 handle_state_inputs,
-                stop_stepping.run_if(in_state(SimState::Stepping)),
+                handle_speed_inputs,
+                apply_tick_rate.run_if(resource_changed::<TickRate>),
+                measure_tps,
+                (step_once, stop_stepping)
+                    .chain()
+                    .run_if(in_state(SimState::Stepping))
+                    .after(SimSet::Draw)
+                    .before(SimSet::Recolor),
             ))
+            .add_systems(OnEnter(SimState::Playing), unpause_virtual_time)
+            .add_systems(OnExit(SimState::Playing), pause_virtual_time)
+            .add_systems(
+                FixedUpdate,
+                count_tick
+                    .after(SimSet::Gas)
+                    .run_if(in_state(SimState::Playing).or_else(in_state(SimState::Stepping))),
+            )
             .configure_sets(
                 Update,
-                (
-                    SimSet::Draw,
-                    (SimSet::Gravity, SimSet::Liquid, SimSet::Gas)
-                        .chain()
-                        .run_if(in_state(SimState::Playing).or_else(in_state(SimState::Stepping))),
-                    SimSet::Recolor,
-                ).chain()
+                (SimSet::Draw, SimSet::Recolor).chain(),
+            )
+            .configure_sets(
+                FixedUpdate,
+                (SimSet::Gravity, SimSet::Force, SimSet::Liquid, SimSet::Gas)
+                    .chain()
+                    .run_if(in_state(SimState::Playing).or_else(in_state(SimState::Stepping))),
             )
         ;
     }
 }
 
+impl SchedulePlugin {
+    /// Ticks actually simulated per second of real time
+    pub const TPS: DiagnosticPath = DiagnosticPath::const_new("tps");
+}
+
 fn handle_state_inputs(
     mut next_state: ResMut<NextState<SimState>>,
     state: Res<State<SimState>>,
@@ -59,8 +134,129 @@ fn handle_state_inputs(
     }
 }
 
+/// `PageUp`/`PageDown` double/halve the simulation speed, and `Home` resets it
+fn handle_speed_inputs(
+    mut tick_rate: ResMut<TickRate>,
+    inputs: Res<ButtonInput<KeyCode>>,
+) {
+    if inputs.just_pressed(KeyCode::PageUp) {
+        tick_rate.speed = (tick_rate.speed * SPEED_STEP).min(MAX_SPEED);
+    } else if inputs.just_pressed(KeyCode::PageDown) {
+        tick_rate.speed = (tick_rate.speed / SPEED_STEP).max(MIN_SPEED);
+    } else if inputs.just_pressed(KeyCode::Home) {
+        tick_rate.speed = 1.0;
+    }
+}
+
+fn apply_tick_rate(
+    tick_rate: Res<TickRate>,
+    mut fixed_time: ResMut<Time<Fixed>>,
+    mut virtual_time: ResMut<Time<Virtual>>,
+) {
+    let timestep = Duration::from_secs_f64(1.0 / tick_rate.ticks_per_second);
+    fixed_time.set_timestep(timestep);
+    virtual_time.set_relative_speed_f64(tick_rate.speed);
+
+    // Virtual time is clamped before it is scaled by the speed, so this bounds the ticks per frame
+    virtual_time.set_max_delta(timestep.mul_f64(tick_rate.max_ticks_per_frame as f64 / tick_rate.speed));
+}
+
+/// The fixed-timestep loop only runs while virtual time advances, i.e., while playing
+fn unpause_virtual_time(mut virtual_time: ResMut<Time<Virtual>>) {
+    virtual_time.unpause();
+}
+
+fn pause_virtual_time(mut virtual_time: ResMut<Time<Virtual>>) {
+    virtual_time.pause();
+}
+
+pub(crate) fn count_tick(
+    mut tick_count: ResMut<TickCount>,
+    mut simulated_time: ResMut<SimulatedTime>,
+    tick_rate: Res<TickRate>,
+) {
+    tick_count.0 += 1;
+    simulated_time.0 += Duration::from_secs_f64(1.0 / tick_rate.ticks_per_second);
+}
+
+fn measure_tps(
+    mut diagnostics: Diagnostics,
+    time: Res<Time<Real>>,
+    tick_count: Res<TickCount>,
+    mut last_tick_count: Local<u64>,
+) {
+    let delta_seconds = time.delta_seconds_f64();
+    if delta_seconds == 0.0 {
+        return;
+    }
+
+    let ticks = tick_count.0 - *last_tick_count;
+    *last_tick_count = tick_count.0;
+    diagnostics.add_measurement(&SchedulePlugin::TPS, || ticks as f64 / delta_seconds);
+}
+
+/// Runs exactly one tick, regardless of how much time has accumulated
+pub(crate) fn step_once(world: &mut World) {
+    world.run_schedule(FixedMain);
+}
+
 fn stop_stepping(
     mut next_state: ResMut<NextState<SimState>>,
 ) {
     next_state.set(SimState::Paused)
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::input::keyboard::{Key, KeyboardInput};
+    use bevy::input::ButtonState;
+    use bevy::time::TimeUpdateStrategy;
+
+    use super::*;
+
+    fn tap(app: &mut App, key_code: KeyCode, logical_key: Key) {
+        for state in [ButtonState::Pressed, ButtonState::Released] {
+            app.world.send_event(KeyboardInput { key_code, logical_key: logical_key.clone(), state, window: Entity::PLACEHOLDER });
+            app.update();
+        }
+    }
+
+    #[test]
+    fn speed_changes_are_clamped() {
+        let mut app = crate::headless_app();
+        app.update();
+
+        for _ in 0..10 {
+            tap(&mut app, KeyCode::PageUp, Key::PageUp);
+        }
+        assert_eq!(app.world.resource::<TickRate>().speed, MAX_SPEED);
+
+        for _ in 0..20 {
+            tap(&mut app, KeyCode::PageDown, Key::PageDown);
+        }
+        assert_eq!(app.world.resource::<TickRate>().speed, MIN_SPEED);
+
+        tap(&mut app, KeyCode::Home, Key::Home);
+        assert_eq!(app.world.resource::<TickRate>().speed, 1.0);
+    }
+
+    #[test]
+    fn slow_frames_run_at_most_max_ticks_per_frame() {
+        let mut app = crate::headless_app();
+        // every frame takes a whole second, which is 60 ticks at normal speed
+        app.insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_secs(1)));
+        app.update();
+
+        for speed in [1.0, MAX_SPEED] {
+            app.world.resource_mut::<TickRate>().speed = speed;
+            app.update();
+
+            let ticks_before = app.world.resource::<TickCount>().0;
+            app.update();
+            let ticks = app.world.resource::<TickCount>().0 - ticks_before;
+            // rounding the clamped time to whole nanoseconds can leave it just short of the last tick
+            let max_ticks = TickRate::default().max_ticks_per_frame as u64;
+            assert!((max_ticks - 1..=max_ticks).contains(&ticks), "ran {ticks} ticks at speed {speed}");
+        }
+    }
+}
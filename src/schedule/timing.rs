@@ -0,0 +1,100 @@
+use std::collections::HashMap;
+use std::time::Instant;
+
+use bevy::ecs::schedule::SystemConfigs;
+use bevy::prelude::*;
+use bevy::diagnostic::{Diagnostic, DiagnosticPath, Diagnostics, RegisterDiagnostic};
+
+use super::{SimSet, SimState, SimulatedTime, TickCount};
+
+const TIMED_SETS: [SimSet; 6] = [
+    SimSet::Gravity,
+    SimSet::Force,
+    SimSet::Liquid,
+    SimSet::Gas,
+    SimSet::Draw,
+    SimSet::Recolor,
+];
+
+/// Measures the time spent in each `SimSet`, along with the progress of the simulation.
+///
+/// A set is timed by recording the time before and after it, so the timing is only accurate
+/// when nothing else runs in parallel with it.
+pub struct TimingPlugin;
+
+impl Plugin for TimingPlugin {
+    fn build(&self, app: &mut App) {
+        for set in TIMED_SETS {
+            app.register_diagnostic(Diagnostic::new(Self::set_time(&set)).with_suffix("ms"));
+        }
+
+        app
+            .init_resource::<SetTimers>()
+            .register_diagnostic(Diagnostic::new(Self::TICKS))
+            .register_diagnostic(Diagnostic::new(Self::SIMULATED_TIME).with_suffix("s"))
+            .add_systems(Update, measure_progress)
+            .add_systems(FixedUpdate, (
+                time_set(SimSet::Gravity),
+                time_set(SimSet::Force).after(SimSet::Gravity),
+                time_set(SimSet::Liquid).after(SimSet::Force),
+                time_set(SimSet::Gas).after(SimSet::Liquid),
+            ).run_if(in_state(SimState::Playing).or_else(in_state(SimState::Stepping))))
+            .add_systems(Update, (
+                time_set(SimSet::Draw),
+                // stepping runs a whole tick between drawing and recoloring, which isn't part of recoloring
+                time_set(SimSet::Recolor).after(super::step_once),
+            ))
+        ;
+    }
+}
+
+impl TimingPlugin {
+    /// Number of ticks simulated so far
+    pub const TICKS: DiagnosticPath = DiagnosticPath::const_new("ticks");
+    /// Simulated time elapsed so far
+    pub const SIMULATED_TIME: DiagnosticPath = DiagnosticPath::const_new("simulated_time");
+
+    /// Milliseconds spent in `set`, per tick for the sets in `FixedUpdate` and per frame for the rest
+    pub fn set_time(set: &SimSet) -> DiagnosticPath {
+        let name = match set {
+            SimSet::Gravity => "gravity",
+            SimSet::Force => "force",
+            SimSet::Gas => "gas",
+            SimSet::Liquid => "liquid",
+            SimSet::Draw => "draw",
+            SimSet::Recolor => "recolor",
+        };
+        DiagnosticPath::from_components(["sim_set", name])
+    }
+}
+
+/// When each set currently being timed started
+#[derive(Resource, Default)]
+struct SetTimers(HashMap<SimSet, Instant>);
+
+/// Systems that record the time right before `set` and measure the time elapsed right after it
+fn time_set(set: SimSet) -> SystemConfigs {
+    let path = TimingPlugin::set_time(&set);
+    let start_set = set.clone();
+    let stop_set = set.clone();
+
+    let start = move |mut timers: ResMut<SetTimers>| {
+        timers.0.insert(start_set.clone(), Instant::now());
+    };
+    let stop = move |mut timers: ResMut<SetTimers>, mut diagnostics: Diagnostics| {
+        if let Some(start) = timers.0.remove(&stop_set) {
+            diagnostics.add_measurement(&path, || start.elapsed().as_secs_f64() * 1000.0);
+        }
+    };
+
+    (start.before(set.clone()), stop.after(set)).into_configs()
+}
+
+fn measure_progress(
+    mut diagnostics: Diagnostics,
+    tick_count: Res<TickCount>,
+    simulated_time: Res<SimulatedTime>,
+) {
+    diagnostics.add_measurement(&TimingPlugin::TICKS, || tick_count.0 as f64);
+    diagnostics.add_measurement(&TimingPlugin::SIMULATED_TIME, || simulated_time.0.as_secs_f64());
+}
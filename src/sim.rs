@@ -1,24 +1,32 @@
 mod coords;
 mod dir;
+pub mod force_field;
 pub mod gas;
 pub mod gravity;
+pub mod hex;
 pub mod liquid;
-mod movement;
+pub mod movement;
 pub mod particle;
 pub mod path;
 pub mod physical_properties;
 mod property_grid;
+pub mod simulation;
+pub mod topology;
 pub mod types;
 
 
 use bevy::prelude::*;
+use bevy::diagnostic::{Diagnostic, DiagnosticPath, Diagnostics, RegisterDiagnostic};
+use bevy::ecs::schedule::SystemConfigs;
 
-use crate::camera::grid_to_camera;
+use crate::schedule::SimSet;
 
 pub use particle::Particle;
 pub use property_grid::PropertyGrid;
 pub use coords::{Coords, RelCoords};
+pub use hex::AxialCoords;
 pub use physical_properties::PhysicalProperties;
+pub use simulation::Simulation;
 
 
 pub const N_PIXELS: Coords = Coords::new(128, 128);
@@ -28,40 +36,92 @@ pub const GRID_CORNER: Vec2 = Vec2::new(
     PIXEL_SIZE.y / 2.0 - PIXEL_SIZE.y * N_PIXELS.y as f32 / 2.0,
 );
 
-/// Maximum number of neighbors possible given the grid topology,
-/// where a "neighbor" is a cell sharing an edge with a given cell
-pub const MAX_NEIGHBORS: usize = 4;
-
 pub struct SimPlugin;
 
+#[cfg(feature = "gui")]
 impl Plugin for SimPlugin {
     fn build(&self, app: &mut App) {
         app
-            .add_systems(Startup, (spawn_particle_grid, spawn_sprites).chain())
+            .add_plugins(SimCorePlugin)
+            .add_plugins(topology::TopologyPlugin)
             .add_plugins(gravity::GravityPlugin)
-            .add_plugins(movement::MovementPlugin)
-            .add_plugins(gas::GasPlugin)
+            .add_plugins(force_field::ForceFieldPlugin)
             .add_plugins(liquid::LiquidPlugin)
         ;
     }
 }
 
+/// The grids, the settings, and the ticks, without any of the displays or inputs that need a window
+pub struct SimCorePlugin;
+
+impl Plugin for SimCorePlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<topology::Topology>()
+            .init_resource::<gravity::Gravity>()
+            .add_systems(Startup, (spawn_particle_grid, force_field::spawn_force_field))
+            .add_systems(FixedUpdate, tick_systems())
+            .add_systems(Update, measure_cell_counts)
+        ;
+
+        for path in SimCorePlugin::CELL_COUNTS {
+            app.register_diagnostic(Diagnostic::new(path));
+        }
+    }
+}
+
+impl SimCorePlugin {
+    /// Number of cells holding each kind of particle, in the same order as the variants of `Particle`
+    pub const CELL_COUNTS: [DiagnosticPath; 4] = [
+        DiagnosticPath::const_new("cells/vacuum"),
+        DiagnosticPath::const_new("cells/air"),
+        DiagnosticPath::const_new("cells/water"),
+        DiagnosticPath::const_new("cells/wall"),
+    ];
+}
+
+/// The systems that advance the simulation by one tick, in the order they run
+pub(crate) fn tick_systems() -> SystemConfigs {
+    (
+        gravity::apply_gravity.in_set(SimSet::Gravity),
+        force_field::apply_force_field.in_set(SimSet::Force),
+        movement::liquid_bulk_flow.in_set(SimSet::Liquid),
+        (gas::gas_dispersion, gas::gas_bulk_flow).chain().in_set(SimSet::Gas),
+    ).chain()
+}
+
+/// Number of cells holding each kind of particle, in the same order as the variants of `Particle`
+pub fn cell_counts(particle_grid: &PropertyGrid<Particle>) -> [usize; 4] {
+    let mut counts = [0; 4];
+    for coords in particle_grid.coords() {
+        counts[material_id(particle_grid.get(coords))] += 1;
+    }
+    counts
+}
+
+/// Index of the kind of particle, in the same order as the variants of `Particle`
+pub fn material_id(particle: &Particle) -> usize {
+    match particle {
+        Particle::Vacuum => 0,
+        Particle::Air { .. } => 1,
+        Particle::Water { .. } => 2,
+        Particle::Wall(_) => 3,
+    }
+}
+
 fn spawn_particle_grid(mut commands: Commands) {
     commands.spawn(PropertyGrid::<Particle>::default());
 }
 
-fn spawn_sprites(mut commands: Commands) {
-    for coords in Coords::ZERO.to(N_PIXELS) {
-        commands.spawn((
-            coords,
-            SpriteBundle {
-                transform: Transform {
-                    translation: grid_to_camera(coords),
-                    scale: PIXEL_SIZE.extend(0.0),
-                    ..default()
-                },
-                ..default()
-            },
-        ));
+fn measure_cell_counts(
+    mut diagnostics: Diagnostics,
+    particle_grid: Query<&PropertyGrid<Particle>>,
+) {
+    let Ok(particle_grid) = particle_grid.get_single() else {
+        return;
+    };
+
+    for (path, count) in SimCorePlugin::CELL_COUNTS.iter().zip(cell_counts(particle_grid)) {
+        diagnostics.add_measurement(path, || count as f64);
     }
 }
@@ -1,8 +1,8 @@
-use bevy::prelude::Component;
+use serde::{Deserialize, Serialize};
 
 use super::types::Vector;
 
-#[derive(Clone, Copy, PartialEq, Eq, Debug, Component)]
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
 pub struct Coords {
     pub x: usize,
     pub y: usize,
@@ -6,6 +6,10 @@ pub enum Dir {
     Down,
     Left,
     Right,
+    UpLeft,
+    UpRight,
+    DownLeft,
+    DownRight,
     Zero,
 }
 
@@ -19,6 +23,10 @@ impl Dir {
             Self::Down => RelCoords::new(0, -1),
             Self::Left => RelCoords::new(-1, 0),
             Self::Right => RelCoords::new(1, 0),
+            Self::UpLeft => RelCoords::new(-1, 1),
+            Self::UpRight => RelCoords::new(1, 1),
+            Self::DownLeft => RelCoords::new(-1, -1),
+            Self::DownRight => RelCoords::new(1, -1),
             Self::Zero => RelCoords::new(0, 0),
         }
     }
@@ -31,6 +39,11 @@ impl From<RelCoords> for Dir {
             (0, -1) => Self::Down,
             (1, 0) => Self::Right,
             (-1, 0) => Self::Left,
+            (-1, 1) => Self::UpLeft,
+            (1, 1) => Self::UpRight,
+            (-1, -1) => Self::DownLeft,
+            (1, -1) => Self::DownRight,
+            (0, 0) => Self::Zero,
             _ => panic!(),
         }
     }
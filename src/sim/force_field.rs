@@ -0,0 +1,87 @@
+use bevy::prelude::*;
+
+#[cfg(feature = "gui")]
+use crate::color::arrows::BlockArrows;
+use crate::zero::Zero;
+use super::types::{Scalar, Vector};
+use super::{Particle, PropertyGrid};
+#[cfg(feature = "gui")]
+use super::topology::Topology;
+
+/// Impulse applied each tick by a single fan cell
+pub const FAN_IMPULSE: Scalar = 0.05;
+
+/// Side length, in cells, of the blocks averaged into a single arrow of the overlay
+#[cfg(feature = "gui")]
+const ARROW_SPACING: usize = 4;
+
+#[cfg(feature = "gui")]
+const ARROW_COLOR: Color = Color::ORANGE;
+
+#[cfg(feature = "gui")]
+pub struct ForceFieldPlugin;
+
+#[cfg(feature = "gui")]
+impl Plugin for ForceFieldPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, draw_force_field_arrows);
+    }
+}
+
+/// A paintable, non-material cell that pushes whatever air or water passes through it
+#[derive(Clone, Copy, Component)]
+pub struct Fan {
+    pub name: &'static str,
+    pub impulse: Vector,
+}
+
+pub mod fans {
+    use super::{Fan, Vector, FAN_IMPULSE};
+
+    pub const NONE: Fan = Fan { name: "No Fan", impulse: Vector::ZERO };
+    pub const UP: Fan = Fan { name: "Fan Up", impulse: Vector::new(0.0, FAN_IMPULSE) };
+    pub const DOWN: Fan = Fan { name: "Fan Down", impulse: Vector::new(0.0, -FAN_IMPULSE) };
+    pub const LEFT: Fan = Fan { name: "Fan Left", impulse: Vector::new(-FAN_IMPULSE, 0.0) };
+    pub const RIGHT: Fan = Fan { name: "Fan Right", impulse: Vector::new(FAN_IMPULSE, 0.0) };
+
+    pub const ALL: [Fan; 5] = [NONE, UP, DOWN, LEFT, RIGHT];
+}
+
+pub(super) fn spawn_force_field(mut commands: Commands) {
+    commands.spawn(PropertyGrid::<Vector>::zero());
+}
+
+/// Each cell of the force field applies its impulse to the air or water occupying it, once per tick.
+pub fn apply_force_field(
+    mut particles: Query<&mut PropertyGrid<Particle>>,
+    force_field: Query<&PropertyGrid<Vector>>,
+) {
+    let mut particles = particles.single_mut();
+    let force_field = force_field.single();
+
+    for coords in particles.coords() {
+        let impulse = *force_field.get(coords);
+        if impulse == Vector::ZERO {
+            continue;
+        }
+
+        match particles.get_mut(coords) {
+            Particle::Vacuum | Particle::Wall(_) => (),
+            Particle::Air { physical_properties } => physical_properties.apply_impulse(impulse),
+            Particle::Water { physical_properties } => physical_properties.apply_impulse(impulse),
+        }
+    }
+}
+
+/// Draws one arrow per `ARROW_SPACING`x`ARROW_SPACING` block of cells, showing the average impulse of the block.
+/// An arrow spans the whole block when the average impulse is `FAN_IMPULSE`.
+#[cfg(feature = "gui")]
+fn draw_force_field_arrows(
+    force_field: Query<&PropertyGrid<Vector>>,
+    topology: Res<Topology>,
+    mut gizmos: Gizmos,
+) {
+    let force_field = force_field.single();
+    let arrows = BlockArrows { spacing: ARROW_SPACING, scale: ARROW_SPACING as Scalar / FAN_IMPULSE, color: ARROW_COLOR };
+    arrows.draw(&mut gizmos, force_field.dims(), *topology, |coords| (*force_field.get(coords), 1.0));
+}
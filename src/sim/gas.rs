@@ -1,23 +1,15 @@
 use bevy::prelude::*;
 
 use super::{Coords, Particle, PhysicalProperties, PropertyGrid, RelCoords};
-use super::types::{Scalar, Vector};
-use crate::schedule::SimSet;
+use super::particle::Wall;
+use super::types::Scalar;
 use crate::zero::Zero;
-use super::path;
-
-pub struct GasPlugin;
-
-impl Plugin for GasPlugin {
-    fn build(&self, app: &mut App) {
-        app.add_systems(Update, (gas_dispersion, gas_bulk_flow).chain().in_set(SimSet::Gas));
-    }
-}
+use super::topology::Topology;
 
 
 const MINIMUM_DISPERSION_MASS: Scalar = 1e-3;
 
-/// Air disperses to orthogonally adjacent `Vacuum` and `Air` cells.
+/// Air disperses to adjacent `Vacuum` and `Air` cells, where adjacency is determined by the `Topology`.
 /// 
 /// The rate of dispersion is determined by `DISPERSION_RATE`, with 0.0 corresponding to no dispersion and 1.0 corresponding to complete dispersion,
 /// i.e., a cell of gas will evenly spread itself out across itself and its neighbors in a single tick.
@@ -25,11 +17,13 @@ const MINIMUM_DISPERSION_MASS: Scalar = 1e-3;
 /// Dispersion conserves mass, momentum, and total energy, converting some heat to kinetic energy.
 ///
 /// Air will not disperse if its mass is less than `MINIMUM_DISPERSION_MASS`.
-fn gas_dispersion(mut particles: Query<&mut PropertyGrid<Particle>>) {
+pub fn gas_dispersion(
+    mut particles: Query<&mut PropertyGrid<Particle>>,
+    topology: Res<Topology>,
+) {
     let mut particles = particles.single_mut();
 
-    let mut prop_deltas = PropertyGrid::<PhysicalProperties>::zero();
-    let dirs = [RelCoords::new(-1, 0), RelCoords::new(1, 0), RelCoords::new(0, -1), RelCoords::new(0, 1)];
+    let mut prop_deltas = PropertyGrid::with_dims(particles.dims(), |_| PhysicalProperties::zero());
 
     for coords in particles.coords() {
         if let Particle::Air { physical_properties } = particles.get(coords) {
@@ -38,7 +32,7 @@ fn gas_dispersion(mut particles: Query<&mut PropertyGrid<Particle>>) {
             }
 
             let mut neighbor_dirs = vec![];
-            for dir in dirs {
+            for &dir in topology.neighbor_dirs(coords) {
                 match particles.try_get(coords + dir) {
                     Some(
                         | Particle::Vacuum
@@ -49,7 +43,7 @@ fn gas_dispersion(mut particles: Query<&mut PropertyGrid<Particle>>) {
             }
             
             let Particle::Air { physical_properties } = particles.get_mut(coords) else { panic!() };
-            let dispersed_props = physical_properties.disperse(neighbor_dirs.iter().map(|dir| Vector::from(*dir)).collect());
+            let dispersed_props = physical_properties.disperse(neighbor_dirs.iter().map(|dir| topology.dir_vector(coords, *dir)).collect(), *topology);
             for (dir, props) in std::iter::zip(neighbor_dirs, dispersed_props) {
                 prop_deltas.try_get_mut(coords + dir).unwrap().merge(props);
             }
@@ -70,13 +64,16 @@ fn gas_dispersion(mut particles: Query<&mut PropertyGrid<Particle>>) {
     }
 }
 
-fn gas_bulk_flow(mut particles: Query<&mut PropertyGrid<Particle>>) {
+pub fn gas_bulk_flow(
+    mut particles: Query<&mut PropertyGrid<Particle>>,
+    topology: Res<Topology>,
+) {
     let mut particles = particles.single_mut();
     let mut moved_gases = Vec::<(Coords, PhysicalProperties)>::new();
     
     for coords in particles.coords() {
         if let Particle::Air { physical_properties } = particles.get(coords) {
-            let velocity = physical_properties.velocity();
+            let velocity = topology.displacement(physical_properties.velocity());
             let new_pos = physical_properties.internal_position + velocity;
 
             if 0.0 <= new_pos.x && new_pos.x < 1.0
@@ -87,34 +84,38 @@ fn gas_bulk_flow(mut particles: Query<&mut PropertyGrid<Particle>>) {
             }
 
             let mut net_reflect = RelCoords::new(1, 1);
+            let mut net_absorb = RelCoords::new(1, 1);
             let mut end_coords = coords;
 
-            for delta in path::get_path_deltas(physical_properties.internal_position, new_pos) {
-                let delta = delta * net_reflect;
+            for delta in topology.get_path_deltas(coords, physical_properties.internal_position, new_pos) {
+                let delta = topology.mirror(coords, delta, net_reflect);
                 let next_coords = coords + delta;
 
                 match particles.try_get(next_coords) {
                     Some(Particle::Vacuum | Particle::Air {..}) => {
                         end_coords = next_coords.try_into().unwrap()
                     },
-                    None | Some(Particle::Water {..}) => {
-                        let reflect = RelCoords::ONE - 2 * delta.abs();
-                        net_reflect *= reflect;
+                    None | Some(Particle::Water {..} | Particle::Wall(Wall::Reflective)) => {
+                        net_reflect *= topology.reflection(coords, delta);
+                    },
+                    // absorptive walls take the momentum toward them, like in `Wall::collide`
+                    Some(Particle::Wall(Wall::Absorptive)) => {
+                        let reflection = topology.reflection(coords, delta);
+                        net_absorb *= RelCoords::new(reflection.x.max(0), reflection.y.max(0));
                     },
-                    Some(Particle::Wall(_)) => unimplemented!(),
                 }
             }
 
             let Particle::Air { mut physical_properties } = particles.swap(coords, Particle::Vacuum) else { panic!() };
-            physical_properties.momentum *= net_reflect;
+            physical_properties.momentum *= net_reflect * net_absorb;
             if net_reflect.x < 0 {
                 physical_properties.internal_position.x = 1.0 - physical_properties.internal_position.x;
             }
             if net_reflect.y < 0 {
                 physical_properties.internal_position.y = 1.0 - physical_properties.internal_position.y;
             }
-            physical_properties.internal_position += velocity * net_reflect;
-            physical_properties.internal_position = physical_properties.internal_position.fract(); // note Vec2::fract behaves differently from f32::fract
+            physical_properties.internal_position += velocity * (net_reflect * net_absorb);
+            physical_properties.internal_position = topology.rebase(coords.into(), end_coords.into(), physical_properties.internal_position).fract(); // note Vec2::fract behaves differently from f32::fract
             moved_gases.push((end_coords, physical_properties));
         }
     }
@@ -127,3 +128,52 @@ fn gas_bulk_flow(mut particles: Query<&mut PropertyGrid<Particle>>) {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+
+    use super::*;
+    use crate::sim::particle::defualts;
+    use crate::sim::types::Vector;
+
+    /// Runs one tick of air flowing into a wall on its right, and returns where the air ends up and its momentum
+    fn flow_into(wall: Wall) -> (Coords, Vector) {
+        let mut particles = PropertyGrid::with_dims(Coords::new(3, 3), |_| defualts::VACUUM);
+        *particles.get_mut(Coords::new(2, 1)) = Particle::Wall(wall);
+        let mut air = defualts::AIR;
+        air.physical_properties_mut().unwrap().set_velocity(Vector::new(1.0, 0.3));
+        *particles.get_mut(Coords::new(1, 1)) = air;
+
+        let mut world = World::new();
+        world.insert_resource(Topology::VonNeumann);
+        world.spawn(particles);
+        world.run_system_once(gas_bulk_flow);
+
+        let particles = world.query::<&PropertyGrid<Particle>>().single(&world);
+        particles.coords()
+            .find_map(|coords| match particles.get(coords) {
+                Particle::Air { physical_properties } => Some((coords, physical_properties.momentum)),
+                _ => None,
+            })
+            .unwrap()
+    }
+
+    #[test]
+    fn absorptive_walls_take_normal_momentum() {
+        let (coords, momentum) = flow_into(Wall::Absorptive);
+
+        assert_eq!(coords, Coords::new(1, 1));
+        assert_eq!(momentum.x, 0.0);
+        assert!(momentum.y > 0.0);
+    }
+
+    #[test]
+    fn reflective_walls_reverse_normal_momentum() {
+        let (coords, momentum) = flow_into(Wall::Reflective);
+
+        assert_eq!(coords, Coords::new(1, 1));
+        assert!(momentum.x < 0.0);
+        assert!(momentum.y > 0.0);
+    }
+}
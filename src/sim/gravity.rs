@@ -1,26 +1,294 @@
 use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
 
-use crate::schedule::SimSet;
-use super::{types::Vector, Particle, PropertyGrid};
+#[cfg(feature = "gui")]
+use crate::camera::{camera_to_grid, window_to_camera};
+#[cfg(feature = "gui")]
+use super::RelCoords;
+use super::topology::Topology;
+use super::{types::{Scalar, Vector}, Coords, Particle, PropertyGrid};
 
 
+/// Default uniform gravitational acceleration
 pub const GRAVITY_ACCELERATION: Vector = Vector::new(0.0, -0.01);
 
+/// Factor by which the uniform acceleration grows or shrinks per key press
+#[cfg(feature = "gui")]
+const MAGNITUDE_STEP: Scalar = 2.0;
+
+/// Attractors closer than this many cells are treated as being this far away, so point attractors don't blow up
+const MIN_ATTRACTOR_DISTANCE: Scalar = 1.0;
+
+#[cfg(feature = "gui")]
 pub struct GravityPlugin;
 
+#[cfg(feature = "gui")]
 impl Plugin for GravityPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(Update, apply_gravity.in_set(SimSet::Gravity));
+        app
+            .add_systems(Startup, setup_gravity_display)
+            .add_systems(Update, (handle_gravity_inputs.run_if(not(crate::puzzle::is_solving)).run_if(not(crate::net::is_joined)), update_gravity_display).chain())
+        ;
     }
 }
 
-fn apply_gravity(mut particles: Query<&mut PropertyGrid<Particle>>) {
+/// Gravity is the sum of a uniform acceleration and the pull of any attractors.
+#[derive(Resource, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Gravity {
+    pub enabled: bool,
+    pub uniform: Vector,
+    pub attractors: Vec<Attractor>,
+    pub affects_air: bool,
+    pub affects_water: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Attractor {
+    /// Pulls toward `center` with an acceleration of `strength / distance^2`
+    Point { center: Coords, strength: Scalar },
+    /// Pulls toward `center` with an acceleration of `strength`, regardless of distance
+    Radial { center: Coords, strength: Scalar },
+}
+
+impl Default for Gravity {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            uniform: GRAVITY_ACCELERATION,
+            attractors: vec![],
+            affects_air: true,
+            affects_water: true,
+        }
+    }
+}
+
+impl Gravity {
+    pub fn acceleration_at(&self, coords: Coords, topology: Topology) -> Vector {
+        if !self.enabled {
+            return Vector::ZERO;
+        }
+        self.uniform + self.attractors.iter().map(|attractor| attractor.acceleration_at(coords, topology)).sum::<Vector>()
+    }
+}
+
+impl Attractor {
+    /// The pull on the cell at `coords`, toward the center of the cell at `center` wherever `topology` puts it
+    pub fn acceleration_at(&self, coords: Coords, topology: Topology) -> Vector {
+        let (center, strength) = match *self {
+            Self::Point { center, strength } | Self::Radial { center, strength } => (center, strength),
+        };
+        let offset = topology.cell_position(center.into()) - topology.cell_position(coords.into());
+        if offset == Vector::ZERO {
+            return Vector::ZERO;
+        }
+        let distance = offset.length().max(MIN_ATTRACTOR_DISTANCE);
+        match self {
+            Self::Point { .. } => offset.normalize() * strength / (distance * distance),
+            Self::Radial { .. } => offset.normalize() * strength,
+        }
+    }
+}
+
+pub fn apply_gravity(
+    mut particles: Query<&mut PropertyGrid<Particle>>,
+    gravity: Res<Gravity>,
+    topology: Res<Topology>,
+) {
     let mut particles = particles.single_mut();
     for coords in particles.coords() {
         match particles.get_mut(coords) {
             Particle::Vacuum | Particle::Wall(_) => (),
-            Particle::Air { physical_properties } => physical_properties.apply_impulse(GRAVITY_ACCELERATION * physical_properties.mass),
-            Particle::Water { physical_properties } => physical_properties.apply_impulse(GRAVITY_ACCELERATION * physical_properties.mass),
+            Particle::Air { physical_properties } => if gravity.affects_air {
+                physical_properties.apply_impulse(gravity.acceleration_at(coords, *topology) * physical_properties.mass)
+            },
+            Particle::Water { physical_properties } => if gravity.affects_water {
+                physical_properties.apply_impulse(gravity.acceleration_at(coords, *topology) * physical_properties.mass)
+            },
         }
     }
-}
\ No newline at end of file
+}
+
+/// - `G` turns gravity on and off
+/// - `R` rotates the uniform acceleration a quarter turn counterclockwise
+/// - `=`/`-` strengthen/weaken the uniform acceleration
+/// - `0` sets the uniform acceleration to zero, and `1` restores the default
+/// - `P`/`O` add a point/radial attractor at the cursor, and `C` clears all attractors
+/// - `J`/`K` toggle whether gravity affects air/water
+#[cfg(feature = "gui")]
+fn handle_gravity_inputs(
+    mut gravity: ResMut<Gravity>,
+    keys: Res<ButtonInput<KeyCode>>,
+    window: Query<&Window>,
+    camera: Query<&Transform, With<Camera>>,
+    topology: Res<Topology>,
+) {
+    if keys.just_pressed(KeyCode::KeyG) {
+        gravity.enabled = !gravity.enabled;
+    }
+    if keys.just_pressed(KeyCode::KeyR) {
+        gravity.uniform = gravity.uniform.perp();
+    }
+    if keys.just_pressed(KeyCode::Equal) {
+        gravity.uniform *= MAGNITUDE_STEP;
+    }
+    if keys.just_pressed(KeyCode::Minus) {
+        gravity.uniform /= MAGNITUDE_STEP;
+    }
+    if keys.just_pressed(KeyCode::Digit0) {
+        gravity.uniform = Vector::ZERO;
+    }
+    if keys.just_pressed(KeyCode::Digit1) {
+        gravity.uniform = GRAVITY_ACCELERATION;
+    }
+    if keys.just_pressed(KeyCode::KeyJ) {
+        gravity.affects_air = !gravity.affects_air;
+    }
+    if keys.just_pressed(KeyCode::KeyK) {
+        gravity.affects_water = !gravity.affects_water;
+    }
+    if keys.just_pressed(KeyCode::KeyC) {
+        gravity.attractors.clear();
+    }
+
+    let add_point = keys.just_pressed(KeyCode::KeyP);
+    let add_radial = keys.just_pressed(KeyCode::KeyO);
+    if !add_point && !add_radial {
+        return;
+    }
+
+    let window = window.single();
+    let Some(cursor_position) = window.cursor_position() else {
+        return;
+    };
+    let grid_pos = RelCoords::from(camera_to_grid(window_to_camera(cursor_position, window, camera.single()), *topology));
+    let Ok(center) = Coords::try_from(grid_pos) else {
+        return;
+    };
+
+    // point attractors match default gravity at a distance of 10 cells, radial attractors match it everywhere
+    let strength = GRAVITY_ACCELERATION.length();
+    if add_point {
+        gravity.attractors.push(Attractor::Point { center, strength: strength * 10.0 * 10.0 });
+    }
+    if add_radial {
+        gravity.attractors.push(Attractor::Radial { center, strength });
+    }
+}
+
+#[cfg(feature = "gui")]
+#[derive(Component)]
+struct GravityText;
+
+#[cfg(feature = "gui")]
+fn setup_gravity_display(mut commands: Commands) {
+    commands.spawn((
+        GravityText,
+        TextBundle {
+            text: Text::from_section("", TextStyle {
+                font_size: 16.0,
+                color: Color::WHITE,
+                ..default()
+            }),
+            style: Style {
+                position_type: PositionType::Absolute,
+                left: Val::Percent(1.0),
+                top: Val::Percent(1.0),
+                ..default()
+            },
+            ..default()
+        },
+    ));
+}
+
+#[cfg(feature = "gui")]
+fn update_gravity_display(
+    gravity: Res<Gravity>,
+    mut text: Query<&mut Text, With<GravityText>>,
+) {
+    if !gravity.is_changed() {
+        return;
+    }
+
+    let on_off = |b: bool| if b { "on" } else { "off" };
+    let gx = gravity.uniform.x;
+    let gy = gravity.uniform.y;
+    let n_attractors = gravity.attractors.len();
+    text.single_mut().sections[0].value = format!("\
+GRAVITY {}
+  - uniform:    ({gx:.3}, {gy:.3}) m/s^2
+  - attractors: {n_attractors}
+  - air: {}, water: {}\
+",
+        on_off(gravity.enabled),
+        on_off(gravity.affects_air),
+        on_off(gravity.affects_water),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_float_eq::*;
+
+    #[test]
+    fn disabled_is_zero() {
+        let gravity = Gravity {
+            enabled: false,
+            attractors: vec![Attractor::Radial { center: Coords::new(0, 0), strength: 1.0 }],
+            ..default()
+        };
+
+        assert_eq!(gravity.acceleration_at(Coords::new(5, 5), Topology::VonNeumann), Vector::ZERO);
+    }
+
+    #[test]
+    fn default_is_uniform() {
+        let gravity = Gravity::default();
+
+        assert_eq!(gravity.acceleration_at(Coords::new(0, 0), Topology::VonNeumann), GRAVITY_ACCELERATION);
+        assert_eq!(gravity.acceleration_at(Coords::new(7, 3), Topology::VonNeumann), GRAVITY_ACCELERATION);
+    }
+
+    #[test]
+    fn point_attractor_inverse_square() {
+        let attractor = Attractor::Point { center: Coords::new(10, 10), strength: 4.0 };
+
+        let near = attractor.acceleration_at(Coords::new(8, 10), Topology::VonNeumann);
+        let far = attractor.acceleration_at(Coords::new(10, 14), Topology::VonNeumann);
+
+        assert_f32_near!(near.x, 1.0);
+        assert_f32_near!(near.y, 0.0);
+        assert_f32_near!(far.x, 0.0);
+        assert_f32_near!(far.y, -0.25);
+    }
+
+    #[test]
+    fn radial_attractor_constant() {
+        let attractor = Attractor::Radial { center: Coords::new(10, 10), strength: 2.0 };
+
+        let near = attractor.acceleration_at(Coords::new(9, 10), Topology::VonNeumann);
+        let far = attractor.acceleration_at(Coords::new(10, 0), Topology::VonNeumann);
+
+        assert_f32_near!(near.length(), 2.0);
+        assert_f32_near!(far.length(), 2.0);
+        assert_f32_near!(far.y, 2.0);
+    }
+
+    #[test]
+    fn attractors_pull_toward_shifted_rows_on_hexagonal_grids() {
+        // the cell at (4, 3) is half a cell right of the cell at (4, 2), in the next row up
+        let attractor = Attractor::Radial { center: Coords::new(4, 3), strength: 1.0 };
+        let pull = attractor.acceleration_at(Coords::new(4, 2), Topology::Hexagonal);
+
+        assert_f32_near!(pull.x, 0.5);
+        assert_f32_near!(pull.y, crate::sim::hex::ROW_HEIGHT);
+    }
+
+    #[test]
+    fn attractor_at_center_is_zero() {
+        let attractor = Attractor::Point { center: Coords::new(3, 3), strength: 1.0 };
+
+        assert_eq!(attractor.acceleration_at(Coords::new(3, 3), Topology::VonNeumann), Vector::ZERO);
+    }
+}
@@ -0,0 +1,197 @@
+//! The hexagonal layout stores cells in the same rows and columns as the square one,
+//! with odd rows shifted right by half a cell, so that each cell touches 2 cells in its own row
+//! and 2 in each of the rows above and below it.
+//! Rows are `ROW_HEIGHT` apart, so that the centers of neighboring cells are all 1 apart.
+
+use super::types::{Scalar, Vector};
+use super::RelCoords;
+
+/// Distance between the centers of adjacent rows, in cell widths
+pub const ROW_HEIGHT: Scalar = 0.866_025_4;
+
+/// Coordinates along two of the three axes of a hexagonal grid, which unlike row/column coordinates
+/// make each neighbor the same offset away regardless of the row
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct AxialCoords {
+    pub q: isize,
+    pub r: isize,
+}
+
+impl AxialCoords {
+    pub const ZERO: Self = Self::new(0, 0);
+
+    pub const DIRS: [Self; 6] = [
+        Self::new(1, 0),
+        Self::new(1, -1),
+        Self::new(0, -1),
+        Self::new(-1, 0),
+        Self::new(-1, 1),
+        Self::new(0, 1),
+    ];
+
+    pub const fn new(q: isize, r: isize) -> Self {
+        Self { q, r }
+    }
+
+    /// Number of steps between neighbors needed to get from `self` to `other`
+    pub fn distance(self, other: Self) -> usize {
+        let delta = other - self;
+        (delta.q.unsigned_abs() + delta.r.unsigned_abs() + (delta.q + delta.r).unsigned_abs()) / 2
+    }
+
+    /// The cells along the straight line from the center of `self` to the center of `end`, both included,
+    /// with each cell a neighbor of the one before it
+    pub fn line_to(self, end: Self) -> Vec<Self> {
+        let n = self.distance(end);
+        // nudged so that the line never runs exactly along an edge, where rounding would be ambiguous
+        let nudge = 1e-4;
+        let (q0, r0) = (self.q as Scalar + nudge, self.r as Scalar + nudge);
+        let (q1, r1) = (end.q as Scalar + nudge, end.r as Scalar + nudge);
+
+        (0..=n)
+            .map(|i| {
+                let t = if n == 0 { 0.0 } else { i as Scalar / n as Scalar };
+                Self::round(q0 * (1.0 - t) + q1 * t, r0 * (1.0 - t) + r1 * t)
+            })
+            .collect()
+    }
+
+    /// The cell containing the fractional axial coordinates
+    fn round(q: Scalar, r: Scalar) -> Self {
+        let s = -q - r;
+        let (mut rq, mut rr, rs) = (q.round(), r.round(), s.round());
+        let (dq, dr, ds) = ((rq - q).abs(), (rr - r).abs(), (rs - s).abs());
+
+        // the three coordinates must sum to 0, so recompute the one that was rounded the furthest
+        if dq > dr && dq > ds {
+            rq = -rr - rs;
+        } else if dr > ds {
+            rr = -rq - rs;
+        }
+        Self::new(rq as isize, rr as isize)
+    }
+}
+
+impl From<RelCoords> for AxialCoords {
+    fn from(value: RelCoords) -> Self {
+        Self::new(value.x - value.y.div_euclid(2), value.y)
+    }
+}
+
+impl From<AxialCoords> for RelCoords {
+    fn from(value: AxialCoords) -> Self {
+        Self::new(value.q + value.r.div_euclid(2), value.r)
+    }
+}
+
+impl std::ops::Add for AxialCoords {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self::Output {
+        Self::new(self.q + rhs.q, self.r + rhs.r)
+    }
+}
+
+impl std::ops::Sub for AxialCoords {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self::Output {
+        Self::new(self.q - rhs.q, self.r - rhs.r)
+    }
+}
+
+/// Offsets to the neighbors of a cell in an even row, in the same order as `AxialCoords::DIRS`
+pub const EVEN_ROW_DIRS: [RelCoords; 6] = [
+    RelCoords::new(1, 0),
+    RelCoords::new(0, -1),
+    RelCoords::new(-1, -1),
+    RelCoords::new(-1, 0),
+    RelCoords::new(-1, 1),
+    RelCoords::new(0, 1),
+];
+
+/// Offsets to the neighbors of a cell in an odd row, in the same order as `AxialCoords::DIRS`
+pub const ODD_ROW_DIRS: [RelCoords; 6] = [
+    RelCoords::new(1, 0),
+    RelCoords::new(1, -1),
+    RelCoords::new(0, -1),
+    RelCoords::new(-1, 0),
+    RelCoords::new(0, 1),
+    RelCoords::new(1, 1),
+];
+
+pub fn neighbor_dirs(y: isize) -> &'static [RelCoords] {
+    if is_odd_row(y) { &ODD_ROW_DIRS } else { &EVEN_ROW_DIRS }
+}
+
+/// How far right the cells in row `y` are shifted, in cell widths
+pub fn row_offset(y: isize) -> Scalar {
+    if is_odd_row(y) { 0.5 } else { 0.0 }
+}
+
+fn is_odd_row(y: isize) -> bool {
+    y.rem_euclid(2) == 1
+}
+
+/// The steps from cell to cell taken when moving from within the cell at `coords` to `end`, which is a position
+/// relative to the bounding box of the cell, with each side of the box being 1 long.
+///
+/// The cell containing `end` is found by the row and shifted column it lies in, and the steps follow
+/// the line between the centers of the cells.
+pub fn get_path_deltas(coords: RelCoords, end: Vector) -> Vec<RelCoords> {
+    let end_y = coords.y + end.y.floor() as isize;
+    let end_x = (coords.x as Scalar + row_offset(coords.y) + end.x - row_offset(end_y)).floor() as isize;
+
+    let path = AxialCoords::from(coords).line_to(AxialCoords::from(RelCoords::new(end_x, end_y)));
+    path.windows(2)
+        .map(|pair| RelCoords::from(pair[1]) - RelCoords::from(pair[0]))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn axial_roundtrip() {
+        for x in -3..3 {
+            for y in -3..3 {
+                let coords = RelCoords::new(x, y);
+                assert_eq!(RelCoords::from(AxialCoords::from(coords)), coords);
+            }
+        }
+    }
+
+    #[test]
+    fn neighbor_dirs_match_axial_dirs() {
+        for y in -2..2 {
+            let coords = RelCoords::new(3, y);
+            for (axial_dir, dir) in std::iter::zip(AxialCoords::DIRS, neighbor_dirs(y)) {
+                assert_eq!(RelCoords::from(AxialCoords::from(coords) + axial_dir), coords + *dir);
+            }
+        }
+    }
+
+    #[test]
+    fn line_steps_between_neighbors() {
+        let start = AxialCoords::new(0, 0);
+        let end = AxialCoords::new(4, -7);
+        let line = start.line_to(end);
+
+        assert_eq!(line.len(), start.distance(end) + 1);
+        assert_eq!(line.first(), Some(&start));
+        assert_eq!(line.last(), Some(&end));
+        assert!(line.windows(2).all(|pair| pair[0].distance(pair[1]) == 1));
+    }
+
+    #[test]
+    fn path_ends_in_cell_containing_end() {
+        // the row above an even row is shifted right, so a point just left of the box above is in the cell up-left
+        let deltas = get_path_deltas(RelCoords::new(2, 0), Vector::new(0.25, 1.5));
+        assert_eq!(deltas, vec![RelCoords::new(-1, 1)]);
+
+        let deltas = get_path_deltas(RelCoords::new(2, 1), Vector::new(3.5, -1.5));
+        assert!(deltas.iter().all(|delta| neighbor_dirs(0).contains(delta) || neighbor_dirs(1).contains(delta)));
+        assert_eq!(deltas.iter().fold(RelCoords::new(2, 1), |sum, delta| sum + *delta), RelCoords::new(5, -1));
+    }
+}
@@ -1,25 +1,17 @@
 use bevy::prelude::*;
 
-use crate::schedule::SimSet;
 use crate::sim::{Coords, Particle, PropertyGrid, RelCoords};
-use crate::sim::path;
+use crate::sim::topology::Topology;
 use crate::sim::types::Vector;
 use crate::sim::dir::{Steps, Dir};
 
 
-pub struct MovementPlugin;
-
-impl Plugin for MovementPlugin {
-    fn build(&self, app: &mut App) {
-        app
-            .add_systems(Update, liquid_bulk_flow.in_set(SimSet::Liquid))
-        ;
-    }
-}
-
-fn liquid_bulk_flow(mut particles: Query<&mut PropertyGrid<Particle>>) {
+pub fn liquid_bulk_flow(
+    mut particles: Query<&mut PropertyGrid<Particle>>,
+    topology: Res<Topology>,
+) {
     let mut particles = particles.single_mut();
-    let mut moving_particles_next = PropertyGrid::new(|_| MovingParticle::None);
+    let mut moving_particles_next = PropertyGrid::with_dims(particles.dims(), |_| MovingParticle::None);
     let mut moving_coords_next = Vec::<Coords>::new();
 
     // 1. Lift particles that will move to a different cell
@@ -29,16 +21,31 @@ fn liquid_bulk_flow(mut particles: Query<&mut PropertyGrid<Particle>>) {
             continue;
         }
         if let Some(physical_properties) = particle.physical_properties_mut() {
-            let new_pos = physical_properties.internal_position + physical_properties.velocity();
+            let new_pos = physical_properties.internal_position + topology.displacement(physical_properties.velocity());
             if is_in_cell(&new_pos) {
                 physical_properties.internal_position = new_pos;
                 continue;
             } 
             
-            physical_properties.internal_position = new_pos.fract();
+            let deltas = match *topology {
+                Topology::VonNeumann | Topology::Moore => {
+                    physical_properties.internal_position = new_pos.fract();
+                    topology.get_path_deltas(coords, physical_properties.internal_position, new_pos)
+                },
+                Topology::Hexagonal => {
+                    let deltas = topology.get_path_deltas(coords, physical_properties.internal_position, new_pos);
+                    if deltas.is_empty() {
+                        // rounding can leave the new position just outside the cell's bounding box, but still in the cell
+                        physical_properties.internal_position = new_pos;
+                        continue;
+                    }
+                    let end_coords = deltas.iter().fold(RelCoords::from(coords), |sum, delta| sum + *delta);
+                    physical_properties.internal_position = topology.rebase(coords.into(), end_coords, new_pos);
+                    deltas
+                },
+            };
 
-            let steps = path::get_path_deltas(physical_properties.internal_position, new_pos)
-                .into_iter()
+            let steps = deltas.into_iter()
                 .map(Dir::from)
                 .collect::<Vec<_>>();
 
@@ -50,7 +57,7 @@ fn liquid_bulk_flow(mut particles: Query<&mut PropertyGrid<Particle>>) {
     // 2. Push each lifted particle 1 cell at a time
     for i in 0.. {
         let moving_coords_this = std::mem::replace(&mut moving_coords_next, Vec::new());
-        let mut moving_particles_this = std::mem::replace(&mut moving_particles_next, PropertyGrid::new(|_| MovingParticle::None));
+        let mut moving_particles_this = std::mem::replace(&mut moving_particles_next, PropertyGrid::with_dims(particles.dims(), |_| MovingParticle::None));
 
         // Only stop when no moving coords remain
         if moving_coords_this.is_empty() {
@@ -81,16 +88,17 @@ fn liquid_bulk_flow(mut particles: Query<&mut PropertyGrid<Particle>>) {
             }
         };
 
-        // 2a. Move each lifted particle to the next cell
+        // 2a. Put down each lifted particle with no steps left, before anything else can move into its cell
+        for &coords in &moving_coords_this {
+            if matches!(moving_particles_this.get(coords), MovingParticle::Some((steps, _)) if steps.len() <= i) {
+                let MovingParticle::Some((_, particle)) = moving_particles_this.swap(coords, MovingParticle::None) else { panic!() };
+                *particles.get_mut(coords) = particle;
+            }
+        }
+
+        // 2b. Move each remaining lifted particle to the next cell
         for coords in moving_coords_this {
             if let MovingParticle::Some((mut steps, mut particle)) = moving_particles_this.swap(coords, MovingParticle::None) {
-
-                // If lifted particle has no steps left, put it down
-                if steps.len() <= i {
-                    *particles.get_mut(coords) = particle;
-                    continue;
-                }
-                
                 let next_coords = coords + steps[i].get();
                 match particles.try_get_mut(next_coords) {
 
@@ -108,7 +116,7 @@ fn liquid_bulk_flow(mut particles: Query<&mut PropertyGrid<Particle>>) {
                     
                     // If unlifted particle would go over the edge of the grid, stop moving
                     None => {
-                        particle.physical_properties_mut().unwrap().momentum *= RelCoords::ONE - steps[i].get().abs(); // zero out the bad momentum
+                        particle.physical_properties_mut().unwrap().momentum *= RelCoords::ONE - off_grid_axes(&particles, coords, steps[i]); // zero out the bad momentum
                         steps[i] = Dir::Zero;
                         move_into(coords, coords, steps, particle);
                     },
@@ -116,7 +124,7 @@ fn liquid_bulk_flow(mut particles: Query<&mut PropertyGrid<Particle>>) {
             }
         }
 
-        // 2b. Resolve conflicts
+        // 2c. Resolve conflicts
         while let Some(coords) = conflict_coords.pop() {
             let MovingParticle::Conflict(mut v) = moving_particles_next.swap(coords, MovingParticle::None) else { 
                 dbg!(coords);
@@ -194,6 +202,161 @@ impl MovingParticle {
     }
 }
 
+/// The axes along which stepping from `coords` in `dir` leaves the grid, as 1s
+fn off_grid_axes(particles: &PropertyGrid<Particle>, coords: Coords, dir: Dir) -> RelCoords {
+    let delta = dir.get();
+    let is_off_grid = |x: usize, dx: isize, len: usize| {
+        x.checked_add_signed(dx).is_none_or(|x| x >= len) as isize
+    };
+    let dims = particles.dims();
+    RelCoords::new(is_off_grid(coords.x, delta.x, dims.x), is_off_grid(coords.y, delta.y, dims.y))
+}
+
 fn is_in_cell(internal_position: &Vector) -> bool {
     0.0 <= internal_position.x && internal_position.x < 1.0 && 0.0 <= internal_position.y && internal_position.y < 1.0
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+
+    use super::*;
+    use crate::sim::particle::defualts;
+
+    fn water_with_velocity(velocity: Vector) -> Particle {
+        let mut water = defualts::WATER;
+        water.physical_properties_mut().unwrap().set_velocity(velocity);
+        water
+    }
+
+    /// Runs a tick of liquid movement on `particles`, returning the particles afterwards
+    fn flow(topology: Topology, particles: PropertyGrid<Particle>) -> PropertyGrid<Particle> {
+        let mut world = World::new();
+        world.insert_resource(topology);
+        world.spawn(particles);
+        world.run_system_once(liquid_bulk_flow);
+        world.query::<&PropertyGrid<Particle>>().single(&world).clone()
+    }
+
+    /// The number of water cells, the number of wall cells, and the total mass of the water
+    fn census(particles: &PropertyGrid<Particle>) -> (usize, usize, f32) {
+        particles.coords().fold((0, 0, 0.0), |(n_water, n_wall, water_mass), coords| match particles.get(coords) {
+            Particle::Water { physical_properties } => (n_water + 1, n_wall, water_mass + physical_properties.mass),
+            Particle::Wall(_) => (n_water, n_wall + 1, water_mass),
+            _ => (n_water, n_wall, water_mass),
+        })
+    }
+
+    #[test]
+    fn flowing_water_is_conserved_and_stays_in_its_cells() {
+        // water moving every which way, into a wall, the edges of the grid, and other water
+        let mut particles = PropertyGrid::with_dims(Coords::new(8, 8), |_| defualts::VACUUM);
+        *particles.get_mut(Coords::new(4, 4)) = defualts::WALL_REFLECTIVE;
+        let velocities = [
+            ((1, 1), (1.7, 0.0)),
+            ((6, 1), (-1.3, 0.4)),
+            ((3, 6), (0.6, -2.2)),
+            ((4, 2), (0.0, 1.5)),
+            ((0, 7), (-0.4, 0.9)),
+            ((6, 6), (1.2, 1.2)),
+            ((2, 3), (2.4, 0.3)),
+            ((5, 3), (-0.8, 0.6)),
+        ];
+        for ((x, y), (vx, vy)) in velocities {
+            *particles.get_mut(Coords::new(x, y)) = water_with_velocity(Vector::new(vx, vy));
+        }
+        let (n_water, n_wall, water_mass) = census(&particles);
+
+        for topology in [Topology::VonNeumann, Topology::Moore, Topology::Hexagonal] {
+            let mut particles = particles.clone();
+            for _ in 0..4 {
+                particles = flow(topology, particles);
+
+                // no particle overwrote another, so the cells of each kind and the mass they hold are unchanged
+                let (n_water_now, n_wall_now, water_mass_now) = census(&particles);
+                assert_eq!((n_water_now, n_wall_now), (n_water, n_wall), "particles were lost with {topology:?}");
+                assert!((water_mass_now - water_mass).abs() < 1e-4, "water mass changed with {topology:?}");
+
+                for coords in particles.coords() {
+                    if let Some(physical_properties) = particles.get(coords).physical_properties() {
+                        assert!(is_in_cell(&physical_properties.internal_position), "water at {coords:?} left its cell with {topology:?}");
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn water_finishing_in_the_same_cell_is_not_lost() {
+        // the left drop takes two steps to reach the cell that the right drop stops in after one
+        for topology in [Topology::VonNeumann, Topology::Moore, Topology::Hexagonal] {
+            let mut particles = PropertyGrid::with_dims(Coords::new(5, 1), |_| defualts::VACUUM);
+            *particles.get_mut(Coords::new(0, 0)) = water_with_velocity(Vector::new(2.0, 0.0));
+            *particles.get_mut(Coords::new(3, 0)) = water_with_velocity(Vector::new(-1.0, 0.0));
+
+            let particles = flow(topology, particles);
+            assert_eq!(census(&particles).0, 2, "water was lost with {topology:?}");
+        }
+    }
+
+    #[test]
+    fn unobstructed_water_moves_by_its_velocity_on_square_grids() {
+        for topology in [Topology::VonNeumann, Topology::Moore] {
+            for velocity in [Vector::new(1.7, 0.0), Vector::new(-1.3, 0.4), Vector::new(1.2, 1.2), Vector::new(0.6, -2.2)] {
+                let start = Coords::new(4, 4);
+                let mut particles = PropertyGrid::with_dims(Coords::new(9, 9), |_| defualts::VACUUM);
+                let water = water_with_velocity(velocity);
+                *particles.get_mut(start) = water;
+
+                let particles = flow(topology, particles);
+
+                // the drop starts in the middle of its cell, and ends up wherever its velocity takes it
+                let end = Vector::new(start.x as f32, start.y as f32) + water.physical_properties().unwrap().internal_position + velocity;
+                let Particle::Water { physical_properties } = particles.get(Coords::new(end.x as usize, end.y as usize)) else {
+                    panic!("expected the water to move by {velocity} with {topology:?}");
+                };
+                assert!((physical_properties.internal_position - end.fract()).length() < 1e-4);
+            }
+        }
+    }
+
+    #[test]
+    fn water_stops_in_front_of_walls_on_von_neumann_grids() {
+        // the drop would move two cells right, but the second cell is a wall
+        let mut particles = PropertyGrid::with_dims(Coords::new(4, 1), |_| defualts::VACUUM);
+        *particles.get_mut(Coords::new(0, 0)) = water_with_velocity(Vector::new(2.0, 0.0));
+        *particles.get_mut(Coords::new(2, 0)) = defualts::WALL_REFLECTIVE;
+
+        let particles = flow(Topology::VonNeumann, particles);
+        assert!(matches!(particles.get(Coords::new(1, 0)), Particle::Water { .. }));
+        assert!(matches!(particles.get(Coords::new(2, 0)), Particle::Wall(_)));
+    }
+
+    #[test]
+    fn water_moves_diagonally_between_walls_on_moore_grids() {
+        // the walls block both of the drop's orthogonal neighbors toward its destination, but not the corner between them
+        let mut particles = PropertyGrid::with_dims(Coords::new(3, 3), |_| defualts::VACUUM);
+        *particles.get_mut(Coords::new(1, 1)) = water_with_velocity(Vector::new(1.0, 1.0));
+        *particles.get_mut(Coords::new(2, 1)) = defualts::WALL_REFLECTIVE;
+        *particles.get_mut(Coords::new(1, 2)) = defualts::WALL_REFLECTIVE;
+
+        let particles = flow(Topology::Moore, particles);
+        assert!(matches!(particles.get(Coords::new(2, 2)), Particle::Water { .. }));
+    }
+
+    #[test]
+    fn water_moves_to_neighbors_in_shifted_rows_on_hexagonal_grids() {
+        // the cell above and to the right of (2, 2) is (2, 3), since odd rows are shifted right by half a cell
+        let topology = Topology::Hexagonal;
+        let start = Coords::new(2, 2);
+        let mut particles = PropertyGrid::with_dims(Coords::new(5, 5), |_| defualts::VACUUM);
+        *particles.get_mut(start) = water_with_velocity(topology.dir_vector(start, RelCoords::new(0, 1)));
+
+        let particles = flow(topology, particles);
+        let Particle::Water { physical_properties } = particles.get(Coords::new(2, 3)) else {
+            panic!("expected the water to move up and to the right");
+        };
+        // moving one cell width toward the center of a neighbor ends at its center
+        assert!((physical_properties.internal_position - Vector::splat(0.5)).length() < 1e-4);
+    }
+}
@@ -1,11 +1,12 @@
 mod wall;
 
 use bevy::prelude::Component;
+use serde::{Deserialize, Serialize};
 
 use super::{dir::Dir, PhysicalProperties, RelCoords};
 pub use wall::Wall;
 
-#[derive(Clone, Copy, Component)]
+#[derive(Clone, Copy, Debug, PartialEq, Component, Serialize, Deserialize)]
 pub enum Particle {
     Vacuum,
     Air {
@@ -33,6 +34,14 @@ impl Particle {
         }
     }
 
+    pub fn physical_properties(&self) -> Option<&PhysicalProperties> {
+        match self {
+            Self::Air { physical_properties } => Some(physical_properties),
+            Self::Water { physical_properties } => Some(physical_properties),
+            _ => None,
+        }
+    }
+
     pub fn physical_properties_mut(&mut self) -> Option<&mut PhysicalProperties> {
         match self {
             Self::Air { physical_properties } => Some(physical_properties),
@@ -41,6 +50,36 @@ impl Particle {
         }
     }
 
+    /// Whether the particle can be simulated, i.e., has no physical properties or valid ones
+    pub fn is_valid(&self) -> bool {
+        self.physical_properties().is_none_or(PhysicalProperties::is_valid)
+    }
+
+    /// Returns the vacuum, air, or water particle with the given name and its default physical properties
+    pub fn from_name(name: &str) -> Option<Self> {
+        [defualts::VACUUM, defualts::AIR, defualts::WATER]
+            .into_iter()
+            .find(|particle| particle.name().eq_ignore_ascii_case(name))
+    }
+
+    /// Like `from_name`, but also accepts the walls by kind, e.g. "Reflective Wall"
+    pub fn from_name_or_wall(name: &str) -> Option<Self> {
+        [(names::WALL_REFLECTIVE, defualts::WALL_REFLECTIVE), (names::WALL_ABSORPTIVE, defualts::WALL_ABSORPTIVE)]
+            .into_iter()
+            .find(|(wall, _)| wall.eq_ignore_ascii_case(name))
+            .map(|(_, wall)| wall)
+            .or_else(|| Self::from_name(name))
+    }
+
+    /// Returns a particle of the same kind with its default physical properties
+    pub fn with_default_properties(&self) -> Self {
+        match self {
+            Self::Air { .. } => defualts::AIR,
+            Self::Water { .. } => defualts::WATER,
+            particle => *particle,
+        }
+    }
+
     pub fn collide(&mut self, other: &mut Self, delta_cell: RelCoords) {
         match (self, other) {
             (
@@ -60,11 +99,13 @@ impl Particle {
 }
 
 pub mod names {
-    pub const VACUUM: &'static str = "Vacuum";
-    pub const AIR: &'static str = "Air";
-    pub const WATER: &'static str = "Water";
+    pub const VACUUM: &str = "Vacuum";
+    pub const AIR: &str = "Air";
+    pub const WATER: &str = "Water";
 
-    pub const WALL: &'static str = "Wall";
+    pub const WALL: &str = "Wall";
+    pub const WALL_REFLECTIVE: &str = "Reflective Wall";
+    pub const WALL_ABSORPTIVE: &str = "Absorptive Wall";
 }
 
 pub mod defualts {
@@ -1,6 +1,8 @@
-use crate::sim::{dir::Dir, PhysicalProperties};
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, Copy)]
+use crate::sim::{dir::Dir, PhysicalProperties, RelCoords};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum Wall {
     Absorptive,
     Reflective,
@@ -8,12 +10,10 @@ pub enum Wall {
 
 impl Wall {
     pub fn collide(&self, physical_properties: &mut PhysicalProperties, delta_cell: Dir) {
-        match (self, delta_cell) {
-            (Self::Absorptive, Dir::Left | Dir::Right) => physical_properties.momentum.x = 0.0,
-            (Self::Reflective, Dir::Left | Dir::Right) => physical_properties.momentum.x *= -1.0,
-            (Self::Absorptive, Dir::Up | Dir::Down) => physical_properties.momentum.y = 0.0,
-            (Self::Reflective, Dir::Up | Dir::Down) => physical_properties.momentum.y *= -1.0,
-            (_, Dir::Zero) => (),
+        let normal = delta_cell.get().abs();
+        match self {
+            Self::Absorptive => physical_properties.momentum *= RelCoords::ONE - normal,
+            Self::Reflective => physical_properties.momentum *= RelCoords::ONE - 2 * normal,
         }
     }
 }
\ No newline at end of file
@@ -10,6 +10,19 @@ pub fn get_path_deltas(start: Vector, end: Vector) -> impl IntoIterator<Item = R
     })
 }
 
+/// Like `get_path_deltas`, but with each horizontal step directly followed by a vertical step,
+/// or vice versa, combined into one diagonal step
+pub fn get_path_deltas_with_diagonals(start: Vector, end: Vector) -> Vec<RelCoords> {
+    let mut deltas = Vec::<RelCoords>::new();
+    for delta in get_path_deltas(start, end) {
+        match deltas.last_mut() {
+            Some(last) if last.x.abs() + last.y.abs() == 1 && (last.x == 0) != (delta.x == 0) => *last = *last + delta,
+            _ => deltas.push(delta),
+        }
+    }
+    deltas
+}
+
 pub fn get_path(start: Vector, end: Vector) -> Vec<RelCoords> {
     let start_coords = RelCoords::from(start);
     let end_coords = RelCoords::from(end);
@@ -59,3 +72,22 @@ pub fn fract_above(f: f32) -> f32 {
 pub fn fract_below(f: f32) -> f32 {
     f - f.floor()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn diagonal_path_combines_steps() {
+        let deltas = get_path_deltas_with_diagonals(Vector::new(0.5, 0.5), Vector::new(2.5, 2.5));
+        assert!(deltas.iter().all(|delta| delta.x.abs() <= 1 && delta.y.abs() <= 1));
+        assert_eq!(deltas.iter().fold(RelCoords::new(0, 0), |sum, delta| sum + *delta), RelCoords::new(2, 2));
+        assert_eq!(deltas.len(), 2);
+    }
+
+    #[test]
+    fn straight_path_is_unchanged() {
+        let deltas = get_path_deltas_with_diagonals(Vector::new(0.5, 0.5), Vector::new(-2.5, 0.5));
+        assert_eq!(deltas, vec![RelCoords::new(-1, 0); 3]);
+    }
+}
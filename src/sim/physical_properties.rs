@@ -1,11 +1,13 @@
 pub mod calc;
 pub mod defaults;
 
+use serde::{Deserialize, Serialize};
+
 use crate::sim::types::{Scalar, Vector};
-use crate::sim::MAX_NEIGHBORS;
+use crate::sim::topology::Topology;
 use crate::zero::Zero;
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
 pub struct PhysicalProperties {
     pub mass: Scalar,
     pub momentum: Vector,
@@ -49,6 +51,38 @@ impl PhysicalProperties {
         calc::kinetic_energy(self.momentum, self.mass)
     }
 
+    pub fn pressure(&self) -> Scalar {
+        calc::pressure(self.heat)
+    }
+
+    /// Whether these properties can be simulated: all finite, with a positive mass and specific heat,
+    /// heat that isn't negative, and the internal position within the cell's bounding box
+    pub fn is_valid(&self) -> bool {
+        let in_cell = |component: Scalar| (0.0..1.0).contains(&component);
+        self.momentum.is_finite()
+            && self.mass.is_finite() && self.mass > 0.0
+            && self.heat.is_finite() && self.heat >= 0.0
+            && self.specific_heat.is_finite() && self.specific_heat > 0.0
+            && in_cell(self.internal_position.x) && in_cell(self.internal_position.y)
+    }
+
+    /// Changes the mass while keeping the velocity and temperature the same
+    pub fn set_mass(&mut self, mass: Scalar) {
+        let velocity = self.velocity();
+        let temperature = self.temperature();
+        self.mass = mass;
+        self.set_velocity(velocity);
+        self.set_temperature(temperature);
+    }
+
+    pub fn set_velocity(&mut self, velocity: Vector) {
+        self.momentum = velocity * self.mass;
+    }
+
+    pub fn set_temperature(&mut self, temperature: Scalar) {
+        self.heat = calc::heat(temperature, self.mass, self.specific_heat);
+    }
+
     pub fn apply_impulse(&mut self, delta_momentum: Vector) {
         self.momentum += delta_momentum;
     }
@@ -78,16 +112,22 @@ impl PhysicalProperties {
 
     // from an arcane derivation
     const BOOST_PARAMETER: Scalar = 0.9; // heat is guaranteed to be positive when this is strictly less than 1
-    const BOOST_CONSTANT: Scalar = 2.0 / 14.8323969742; // f32::sqrt((MAX_NEIGHBORS * (MAX_NEIGHBORS + 1) * (2 * MAX_NEIGHBORS + 3)) as f32)
 
-    pub fn disperse(&mut self, dirs: Vec<Vector>) -> Vec<Self> {
-        let dispersed_fraction_per_dir = Self::DISPERSION_RATE / (MAX_NEIGHBORS as f32 + 1.0);
+    /// Equal to `2 / sqrt(N (N + 1) (2N + 3))`, where `N` is the maximum number of neighbors
+    fn boost_constant(topology: Topology) -> Scalar {
+        let n = topology.max_neighbors();
+        2.0 / Scalar::sqrt((n * (n + 1) * (2 * n + 3)) as Scalar)
+    }
+
+    /// Disperses to the neighbors in the given directions, which must be unit vectors
+    pub fn disperse(&mut self, dirs: Vec<Vector>, topology: Topology) -> Vec<Self> {
+        let dispersed_fraction_per_dir = Self::DISPERSION_RATE / (topology.max_neighbors() as f32 + 1.0);
         let n_neighbors = dirs.len() as f32;
         
         let other_mass_after = self.mass * dispersed_fraction_per_dir;
         let my_mass_after = self.mass - n_neighbors * other_mass_after;
         
-        let abs_momentum_boost = Self::BOOST_PARAMETER * Self::BOOST_CONSTANT * Scalar::sqrt(Self::DISPERSION_RATE * self.mass * self.heat);
+        let abs_momentum_boost = Self::BOOST_PARAMETER * Self::boost_constant(topology) * Scalar::sqrt(Self::DISPERSION_RATE * self.mass * self.heat);
         let other_momenta_after = dirs.iter().map(|dir| *dir * abs_momentum_boost + self.momentum * dispersed_fraction_per_dir).collect::<Vec<_>>();
         let my_momentum_after = self.momentum - other_momenta_after.iter().sum::<Vector>();
         
@@ -131,6 +171,7 @@ impl PhysicalProperties {
 mod tests {
     use super::*;
     use assert_float_eq::*;
+    use crate::sim::Coords;
     
     // make sure test properties can be evenly divided
     const TEST_MASS: Scalar = 3.0 * 4.0 * 5.0;
@@ -257,9 +298,43 @@ mod tests {
 
 
 
+    #[test]
+    fn set_mass_keeps_velocity_and_temperature() {
+        let mut a = get_test_properties();
+        let velocity_before = a.velocity();
+        let temperature_before = a.temperature();
+
+        a.set_mass(TEST_MASS / 4.0);
+
+        assert_f32_near!(a.mass, TEST_MASS / 4.0);
+        assert_f32_near!(a.velocity().x, velocity_before.x);
+        assert_f32_near!(a.velocity().y, velocity_before.y);
+        assert_f32_near!(a.temperature(), temperature_before);
+    }
+
+    #[test]
+    fn set_velocity_and_temperature() {
+        let mut a = get_test_properties();
+
+        a.set_velocity(Vector::new(0.5, -2.0));
+        a.set_temperature(7.0);
+
+        assert_f32_near!(a.velocity().x, 0.5);
+        assert_f32_near!(a.velocity().y, -2.0);
+        assert_f32_near!(a.temperature(), 7.0);
+        assert_f32_near!(a.mass, TEST_MASS);
+    }
+
+
+
     fn disperse_4_ways(physical_properties: &mut PhysicalProperties) -> Vec<PhysicalProperties> {
         let dirs = [Vector::new(1.0, 0.0), Vector::new(0.0, 1.0), Vector::new(-1.0, 0.0), Vector::new(0.0, -1.0)];
-        physical_properties.disperse(dirs.into())
+        physical_properties.disperse(dirs.into(), Topology::VonNeumann)
+    }
+
+    fn disperse_to_all_neighbors(physical_properties: &mut PhysicalProperties, topology: Topology) -> Vec<PhysicalProperties> {
+        let dirs = topology.neighbor_dirs(Coords::ZERO).iter().map(|dir| topology.dir_vector(Coords::ZERO, *dir)).collect();
+        physical_properties.disperse(dirs, topology)
     }
 
     #[test]
@@ -316,4 +391,39 @@ mod tests {
             assert_f32_near!(dispersed.heat, heat_after / 5.0);
         }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn boost_constant_matches_derivation() {
+        assert_f32_near!(PhysicalProperties::boost_constant(Topology::VonNeumann), 2.0 / 220.0_f32.sqrt());
+        assert_f32_near!(PhysicalProperties::boost_constant(Topology::Moore), 2.0 / 1368.0_f32.sqrt());
+    }
+
+    #[test]
+    fn disperse_to_all_neighbors_conserves() {
+        for topology in [Topology::Moore, Topology::Hexagonal] {
+            let mut original = get_test_properties();
+            let mass_before = original.mass;
+            let momentum_before = original.momentum;
+            let energy_before = original.heat + original.kinetic_energy();
+
+            let disperseds = disperse_to_all_neighbors(&mut original, topology);
+            let mass_after = original.mass + disperseds.iter().map(|dispersed| dispersed.mass).sum::<Scalar>();
+            let momentum_after = original.momentum + disperseds.iter().map(|dispersed| dispersed.momentum).sum::<Vector>();
+            let energy_after = original.heat + original.kinetic_energy()
+                + disperseds.iter().map(|dispersed| dispersed.heat + dispersed.kinetic_energy()).sum::<Scalar>();
+
+            assert_f32_near!(mass_before, mass_after);
+            assert_f32_near!(momentum_before.x, momentum_after.x);
+            assert_f32_near!(momentum_before.y, momentum_after.y);
+            assert_f32_near!(energy_before, energy_after);
+
+            let n_cells = (topology.max_neighbors() + 1) as Scalar;
+            assert_f32_near!(original.mass, mass_after / n_cells);
+            assert!(original.heat > 0.0);
+            for dispersed in disperseds {
+                assert_f32_near!(dispersed.mass, mass_after / n_cells);
+                assert!(dispersed.heat > 0.0);
+            }
+        }
+    }
+}
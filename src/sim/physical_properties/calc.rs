@@ -9,7 +9,6 @@ pub const fn temperature_const(heat: Scalar, mass: Scalar, specific_heat: Scalar
     SoftF32(heat).div(SoftF32(mass).mul(SoftF32(specific_heat))).to_f32()
 }
 
-#[cfg(test)] // remove this when it is used elsewhere, but for now, it is used to test heat_const
 pub fn heat(temperature: Scalar, mass: Scalar, specific_heat: Scalar) -> Scalar {
     temperature * mass * specific_heat
 }
@@ -29,6 +28,17 @@ pub fn kinetic_energy(momentum: Vector, mass: Scalar) -> Scalar {
     }
 }
 
+/// Ratio of specific heats of air, treated as an ideal diatomic gas
+const ADIABATIC_INDEX: Scalar = 1.4;
+
+/// Pressure of an ideal gas with the given heat in a cell of unit volume
+pub fn pressure(heat: Scalar) -> Scalar {
+    (ADIABATIC_INDEX - 1.0) * heat
+}
+pub const fn pressure_const(heat: Scalar) -> Scalar {
+    SoftF32(ADIABATIC_INDEX).sub(SoftF32(1.0)).mul(SoftF32(heat)).to_f32()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -50,6 +60,12 @@ mod tests {
         assert_f32_near!(heat(temperature, mass, specific_heat), heat_const(temperature, mass, specific_heat));
     }
 
+    #[test]
+    fn pressure_exprs_agree() {
+        let heat = 1.2;
+        assert_f32_near!(pressure(heat), pressure_const(heat));
+    }
+
     #[test]
     fn zero_mass_ke() {
         assert_f32_near!(0.0, kinetic_energy(Vector::ONE, 0.0));
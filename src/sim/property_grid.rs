@@ -1,19 +1,24 @@
 use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
 
 use crate::zero::Zero;
 use super::{Coords, N_PIXELS};
 
-#[derive(Component)]
+#[derive(Component, Clone, PartialEq, Serialize, Deserialize)]
 pub struct PropertyGrid<T> {
     arr: Vec<Vec<T>>,
 }
 
 impl<T> PropertyGrid<T> {
-    pub fn new(mut callback: impl FnMut(Coords) -> T) -> Self {
-        let mut res = Self { arr: Vec::with_capacity(N_PIXELS.x) };
-        for x in 0..N_PIXELS.x {
-            let mut col = Vec::with_capacity(N_PIXELS.y);
-            for y in 0..N_PIXELS.y {
+    pub fn new(callback: impl FnMut(Coords) -> T) -> Self {
+        Self::with_dims(N_PIXELS, callback)
+    }
+
+    pub fn with_dims(dims: Coords, mut callback: impl FnMut(Coords) -> T) -> Self {
+        let mut res = Self { arr: Vec::with_capacity(dims.x) };
+        for x in 0..dims.x {
+            let mut col = Vec::with_capacity(dims.y);
+            for y in 0..dims.y {
                 col.push(callback(Coords::new(x, y)));
             }
             res.arr.push(col);
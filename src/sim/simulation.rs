@@ -0,0 +1,89 @@
+use bevy::ecs::schedule::ScheduleLabel;
+use bevy::prelude::*;
+
+use crate::zero::Zero;
+use super::gravity::Gravity;
+use super::topology::Topology;
+use super::types::Vector;
+use super::{Coords, Particle, PropertyGrid};
+
+#[derive(ScheduleLabel, Debug, Clone, PartialEq, Eq, Hash)]
+struct Tick;
+
+/// The simulation on its own, outside of any app, for running it as part of something else
+pub struct Simulation {
+    world: World,
+    tick: Schedule,
+    particles: Entity,
+    force_field: Entity,
+}
+
+impl Simulation {
+    /// A grid of vacuum with no force field, under the default gravity and topology
+    pub fn new(dims: Coords) -> Self {
+        let mut world = World::new();
+        world.init_resource::<Topology>();
+        world.init_resource::<Gravity>();
+        let particles = world.spawn(PropertyGrid::with_dims(dims, |_| Particle::Vacuum)).id();
+        let force_field = world.spawn(PropertyGrid::with_dims(dims, |_| Vector::zero())).id();
+
+        let mut tick = Schedule::new(Tick);
+        tick.add_systems(super::tick_systems());
+
+        Self { world, tick, particles, force_field }
+    }
+
+    pub fn step(&mut self) {
+        self.tick.run(&mut self.world);
+    }
+
+    pub fn dims(&self) -> Coords {
+        self.particles().dims()
+    }
+
+    pub fn particles(&self) -> &PropertyGrid<Particle> {
+        self.world.get(self.particles).expect("the particle grid is never despawned")
+    }
+
+    pub fn particles_mut(&mut self) -> &mut PropertyGrid<Particle> {
+        self.world.get_mut::<PropertyGrid<Particle>>(self.particles).expect("the particle grid is never despawned").into_inner()
+    }
+
+    pub fn force_field(&self) -> &PropertyGrid<Vector> {
+        self.world.get(self.force_field).expect("the force field is never despawned")
+    }
+
+    pub fn force_field_mut(&mut self) -> &mut PropertyGrid<Vector> {
+        self.world.get_mut::<PropertyGrid<Vector>>(self.force_field).expect("the force field is never despawned").into_inner()
+    }
+
+    pub fn gravity_mut(&mut self) -> &mut Gravity {
+        self.world.resource_mut::<Gravity>().into_inner()
+    }
+
+    pub fn topology_mut(&mut self) -> &mut Topology {
+        self.world.resource_mut::<Topology>().into_inner()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sim::particle::defualts;
+
+    #[test]
+    fn water_falls() {
+        let mut simulation = Simulation::new(Coords::new(4, 8));
+        *simulation.particles_mut().get_mut(Coords::new(1, 6)) = defualts::WATER;
+
+        for _ in 0..200 {
+            simulation.step();
+        }
+        let particles = simulation.particles();
+        let water = particles.coords()
+            .filter(|coords| matches!(particles.get(*coords), Particle::Water { .. }))
+            .collect::<Vec<_>>();
+        assert_eq!(water.len(), 1);
+        assert_eq!(water[0].y, 0);
+    }
+}
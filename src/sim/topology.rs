@@ -0,0 +1,239 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use super::{hex, path, AxialCoords};
+use super::types::{Scalar, Vector};
+use super::{Coords, RelCoords};
+
+#[cfg(feature = "gui")]
+pub struct TopologyPlugin;
+
+#[cfg(feature = "gui")]
+impl Plugin for TopologyPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_systems(Update, handle_topology_inputs.run_if(not(crate::puzzle::is_solving)).run_if(not(crate::net::is_joined)))
+        ;
+    }
+}
+
+/// The layout of the cells and which of them count as the neighbors of a cell, for dispersion and movement.
+///
+/// Positions within a cell are always relative to the cell's bounding box, with each side being 1 long,
+/// while velocities are in cell widths per tick.
+#[derive(Resource, Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Topology {
+    /// Square cells, with the 4 cells sharing an edge with a cell as its neighbors
+    #[default]
+    VonNeumann,
+    /// Square cells, with the 8 cells sharing an edge or a corner with a cell as its neighbors
+    Moore,
+    /// Hexagonal cells, with the 6 cells sharing an edge with a cell as its neighbors
+    Hexagonal,
+}
+
+const VON_NEUMANN_DIRS: [RelCoords; 4] = [
+    RelCoords::new(-1, 0),
+    RelCoords::new(1, 0),
+    RelCoords::new(0, -1),
+    RelCoords::new(0, 1),
+];
+
+const MOORE_DIRS: [RelCoords; 8] = [
+    RelCoords::new(-1, 0),
+    RelCoords::new(1, 0),
+    RelCoords::new(0, -1),
+    RelCoords::new(0, 1),
+    RelCoords::new(-1, -1),
+    RelCoords::new(-1, 1),
+    RelCoords::new(1, -1),
+    RelCoords::new(1, 1),
+];
+
+impl Topology {
+    /// Offsets to the neighbors of the cell at `coords`
+    pub fn neighbor_dirs(&self, coords: Coords) -> &'static [RelCoords] {
+        match self {
+            Self::VonNeumann => &VON_NEUMANN_DIRS,
+            Self::Moore => &MOORE_DIRS,
+            Self::Hexagonal => hex::neighbor_dirs(coords.y as isize),
+        }
+    }
+
+    /// Maximum number of neighbors a cell can have
+    pub fn max_neighbors(&self) -> usize {
+        self.neighbor_dirs(Coords::ZERO).len()
+    }
+
+    /// Distance between the centers of adjacent rows, in cell widths
+    pub fn row_height(&self) -> Scalar {
+        match self {
+            Self::VonNeumann | Self::Moore => 1.0,
+            Self::Hexagonal => hex::ROW_HEIGHT,
+        }
+    }
+
+    /// How far right the cells in row `y` are shifted, in cell widths
+    pub fn row_offset(&self, y: isize) -> Scalar {
+        match self {
+            Self::VonNeumann | Self::Moore => 0.0,
+            Self::Hexagonal => hex::row_offset(y),
+        }
+    }
+
+    /// Position of the center of the cell at `coords` relative to the center of the cell at `(0, 0)`, in cell widths
+    pub fn cell_position(&self, coords: RelCoords) -> Vector {
+        Vector::new(coords.x as Scalar + self.row_offset(coords.y), coords.y as Scalar * self.row_height())
+    }
+
+    /// Inverse of `cell_position`, giving coordinates whose floor is the cell containing `position`,
+    /// so that the center of a cell maps to its coordinates plus a half
+    pub fn grid_position(&self, position: Vector) -> Vector {
+        let y = position.y / self.row_height() + 0.5;
+        Vector::new(position.x + 0.5 - self.row_offset(y.floor() as isize), y)
+    }
+
+    /// Unit vector pointing from the center of the cell at `coords` to the center of the cell at `coords + dir`
+    pub fn dir_vector(&self, coords: Coords, dir: RelCoords) -> Vector {
+        let coords = RelCoords::from(coords);
+        (self.cell_position(coords + dir) - self.cell_position(coords)).normalize()
+    }
+
+    /// How far a velocity moves a position within a cell's bounding box in one tick
+    pub fn displacement(&self, velocity: Vector) -> Vector {
+        Vector::new(velocity.x, velocity.y / self.row_height())
+    }
+
+    /// The same point as `position` within the cell at `from`, but relative to the cell at `to`
+    pub fn rebase(&self, from: RelCoords, to: RelCoords, position: Vector) -> Vector {
+        let shift = self.row_offset(from.y) - self.row_offset(to.y);
+        position + Vector::from(from - to) + Vector::new(shift, 0.0)
+    }
+
+    /// The steps from cell to cell taken when moving from `start` to `end` within the cell at `coords`,
+    /// which are diagonal where the topology allows it
+    pub fn get_path_deltas(&self, coords: Coords, start: Vector, end: Vector) -> Vec<RelCoords> {
+        match self {
+            Self::VonNeumann => path::get_path_deltas(start, end).into_iter().collect(),
+            Self::Moore => path::get_path_deltas_with_diagonals(start, end),
+            Self::Hexagonal => hex::get_path_deltas(coords.into(), end),
+        }
+    }
+
+    /// The cells along the line from `start` to `end`, which are positions as given by `grid_position`,
+    /// with each cell a neighbor of the one before it
+    pub fn get_path(&self, start: Vector, end: Vector) -> Vec<RelCoords> {
+        match self {
+            Self::VonNeumann => path::get_path(start, end),
+            Self::Moore => {
+                let start_coords = RelCoords::from(start);
+                std::iter::once(start_coords)
+                    .chain(path::get_path_deltas_with_diagonals(start, end).into_iter().scan(start_coords, |coords, delta| {
+                        *coords = *coords + delta;
+                        Some(*coords)
+                    }))
+                    .collect()
+            },
+            Self::Hexagonal => AxialCoords::from(RelCoords::from(start))
+                .line_to(AxialCoords::from(RelCoords::from(end)))
+                .into_iter()
+                .map(RelCoords::from)
+                .collect(),
+        }
+    }
+
+    /// The axes to flip, as -1s, to bounce off the cell at `coords + dir`
+    pub fn reflection(&self, coords: Coords, dir: RelCoords) -> RelCoords {
+        let dir = self.dir_vector(coords, dir);
+        let flip = |component: Scalar| if component.abs() > Scalar::EPSILON { -1 } else { 1 };
+        RelCoords::new(flip(dir.x), flip(dir.y))
+    }
+
+    /// The neighbor of the cell at `coords` in the direction closest to `dir` mirrored along the axes in `reflection`
+    pub fn mirror(&self, coords: Coords, dir: RelCoords, reflection: RelCoords) -> RelCoords {
+        let mirrored = self.dir_vector(coords, dir) * Vector::from(reflection);
+        *self.neighbor_dirs(coords).iter()
+            .max_by(|a, b| self.dir_vector(coords, **a).dot(mirrored).total_cmp(&self.dir_vector(coords, **b).dot(mirrored)))
+            .unwrap()
+    }
+
+    pub fn next(self) -> Self {
+        match self {
+            Self::VonNeumann => Self::Moore,
+            Self::Moore => Self::Hexagonal,
+            Self::Hexagonal => Self::VonNeumann,
+        }
+    }
+}
+
+/// `T` cycles through the von Neumann, Moore, and hexagonal topologies
+#[cfg(feature = "gui")]
+fn handle_topology_inputs(
+    mut topology: ResMut<Topology>,
+    keys: Res<ButtonInput<KeyCode>>,
+) {
+    if keys.just_pressed(KeyCode::KeyT) {
+        *topology = topology.next();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_float_eq::*;
+
+    #[test]
+    fn hex_neighbors_are_equidistant() {
+        let topology = Topology::Hexagonal;
+        for coords in [Coords::new(4, 4), Coords::new(4, 5)] {
+            for dir in topology.neighbor_dirs(coords) {
+                let distance = (topology.cell_position(RelCoords::from(coords) + *dir) - topology.cell_position(coords.into())).length();
+                assert_f32_near!(distance, 1.0);
+            }
+        }
+    }
+
+    #[test]
+    fn grid_position_inverts_cell_position() {
+        for topology in [Topology::VonNeumann, Topology::Hexagonal] {
+            for coords in [RelCoords::new(3, 2), RelCoords::new(3, 3)] {
+                let grid_position = topology.grid_position(topology.cell_position(coords));
+                assert_f32_near!(grid_position.x, coords.x as Scalar + 0.5);
+                assert_f32_near!(grid_position.y, coords.y as Scalar + 0.5);
+            }
+        }
+    }
+
+    #[test]
+    fn mirror_maps_neighbors_to_neighbors() {
+        let topology = Topology::Hexagonal;
+        let coords = Coords::new(4, 4);
+        // up-right mirrored horizontally is up-left
+        assert_eq!(topology.mirror(coords, RelCoords::new(0, 1), RelCoords::new(-1, 1)), RelCoords::new(-1, 1));
+        // up-right mirrored vertically is down-right
+        assert_eq!(topology.mirror(coords, RelCoords::new(0, 1), RelCoords::new(1, -1)), RelCoords::new(0, -1));
+
+        let topology = Topology::VonNeumann;
+        assert_eq!(topology.mirror(coords, RelCoords::new(1, 0), RelCoords::new(-1, 1)), RelCoords::new(-1, 0));
+    }
+
+    #[test]
+    fn paths_step_between_neighbors() {
+        let (start, end) = (Vector::new(1.5, 1.5), Vector::new(6.2, 4.7));
+        for topology in [Topology::VonNeumann, Topology::Moore, Topology::Hexagonal] {
+            let path = topology.get_path(start, end);
+            assert_eq!(path.first(), Some(&RelCoords::new(1, 1)));
+            assert_eq!(path.last(), Some(&RelCoords::new(6, 4)));
+            for pair in path.windows(2) {
+                assert!(topology.neighbor_dirs(pair[0].try_into().unwrap()).contains(&(pair[1] - pair[0])), "{topology:?} stepped from {:?} to {:?}", pair[0], pair[1]);
+            }
+        }
+    }
+
+    #[test]
+    fn rebase_accounts_for_shifted_rows() {
+        let position = Topology::Hexagonal.rebase(RelCoords::new(2, 0), RelCoords::new(1, 1), Vector::new(0.25, 1.5));
+        assert_f32_near!(position.x, 0.75);
+        assert_f32_near!(position.y, 0.5);
+    }
+}
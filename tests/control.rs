@@ -0,0 +1,140 @@
+//! Drives a headless app through the control server, the way an external script would.
+
+use std::io::{BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpStream};
+use std::thread;
+use std::time::Duration;
+
+use dust::control::{Command, ControlPlugin, ControlServer, ExportFormat, ParticleSpec, Response};
+use dust::sim::particle::defualts;
+
+struct Client {
+    reader: BufReader<TcpStream>,
+    writer: TcpStream,
+}
+
+impl Client {
+    fn connect(addr: SocketAddr) -> Self {
+        let writer = TcpStream::connect(addr).unwrap();
+        writer.set_read_timeout(Some(Duration::from_secs(10))).unwrap();
+        Self { reader: BufReader::new(writer.try_clone().unwrap()), writer }
+    }
+
+    fn send_line(&mut self, line: &str) -> Response {
+        writeln!(self.writer, "{line}").unwrap();
+        self.receive()
+    }
+
+    fn send(&mut self, command: Command) -> Response {
+        self.send_line(&serde_json::to_string(&command).unwrap())
+    }
+
+    fn receive(&mut self) -> Response {
+        let mut line = String::new();
+        self.reader.read_line(&mut line).unwrap();
+        serde_json::from_str(&line).unwrap()
+    }
+}
+
+/// Runs the app until the client is done with it
+fn with_client(client: impl FnOnce(Client) + Send + 'static) {
+    let mut app = dust::headless_app();
+    app.add_plugins(ControlPlugin::bind(0).unwrap());
+    app.finish();
+    app.cleanup();
+
+    let addr = app.world.resource::<ControlServer>().local_addr();
+    let client = thread::spawn(move || client(Client::connect(addr)));
+    while !client.is_finished() {
+        app.update();
+        thread::sleep(Duration::from_millis(1));
+    }
+    client.join().unwrap();
+}
+
+#[test]
+fn writes_and_reads_cells() {
+    with_client(|mut client| {
+        assert!(matches!(client.send(Command::Pause), Response::Ok));
+
+        let write = Command::Write { x: 4, y: 6, width: 3, height: 2, particle: ParticleSpec::Name("water".into()) };
+        assert!(matches!(client.send(write), Response::Ok));
+
+        let Response::Cells { cells } = client.send(Command::Read { x: 3, y: 6, width: 4, height: 1 }) else {
+            panic!("expected cells");
+        };
+        let expected = [defualts::VACUUM, defualts::WATER, defualts::WATER, defualts::WATER];
+        assert_eq!(cells.iter().map(|column| column[0]).collect::<Vec<_>>(), expected);
+    });
+}
+
+#[test]
+fn subscribers_get_stats_for_each_step() {
+    with_client(|mut client| {
+        assert!(matches!(client.send(Command::Pause), Response::Ok));
+        let write = Command::Write { x: 0, y: 0, width: 10, height: 10, particle: ParticleSpec::Particle(defualts::AIR) };
+        assert!(matches!(client.send(write), Response::Ok));
+        assert!(matches!(client.send(Command::Subscribe), Response::Ok));
+
+        let mut ticks = vec![];
+        for _ in 0..2 {
+            assert!(matches!(client.send(Command::Step), Response::Ok));
+            let Response::Tick(stats) = client.receive() else {
+                panic!("expected the stats of the step");
+            };
+            assert_eq!(stats.cell_counts[1], stats.cell_counts.iter().sum::<usize>() - stats.cell_counts[0]);
+            assert!(stats.air_mass > 0.0);
+            ticks.push(stats.tick);
+        }
+        assert_eq!(ticks[1], ticks[0] + 1);
+    });
+}
+
+#[test]
+fn scenes_roundtrip() {
+    let path = std::env::temp_dir().join(format!("dust-scene-{}.ron", std::process::id()));
+    let path = path.to_str().unwrap().to_owned();
+
+    with_client(move |mut client| {
+        assert!(matches!(client.send(Command::Pause), Response::Ok));
+        let write = Command::Write { x: 1, y: 1, width: 1, height: 1, particle: ParticleSpec::Particle(defualts::WALL_REFLECTIVE) };
+        assert!(matches!(client.send(write), Response::Ok));
+        assert!(matches!(client.send(Command::SaveScene { path: path.clone() }), Response::Ok));
+
+        let erase = Command::Write { x: 1, y: 1, width: 1, height: 1, particle: ParticleSpec::Name("vacuum".into()) };
+        assert!(matches!(client.send(erase), Response::Ok));
+        assert!(matches!(client.send(Command::LoadScene { path: path.clone() }), Response::Ok));
+
+        let Response::Cells { cells } = client.send(Command::Read { x: 1, y: 1, width: 1, height: 1 }) else {
+            panic!("expected cells");
+        };
+        assert_eq!(cells, vec![vec![defualts::WALL_REFLECTIVE]]);
+        std::fs::remove_file(&path).unwrap();
+    });
+}
+
+#[test]
+fn fields_are_exported_on_demand() {
+    let dir = std::env::temp_dir().join(format!("dust-fields-{}", std::process::id()));
+    let path = dir.to_str().unwrap().to_owned();
+
+    with_client(move |mut client| {
+        assert!(matches!(client.send(Command::ExportFields { path, format: ExportFormat::Npy }), Response::Ok));
+        for field in ["mass", "momentum_x", "momentum_y", "heat", "temperature", "material"] {
+            assert!(dir.join(format!("{field}.npy")).is_file());
+        }
+        let metadata = std::fs::read_to_string(dir.join("metadata.json")).unwrap();
+        assert!(metadata.contains("\"tick\""));
+        std::fs::remove_dir_all(&dir).unwrap();
+    });
+}
+
+#[test]
+fn bad_commands_get_errors() {
+    with_client(|mut client| {
+        assert!(matches!(client.send_line(r#"{"command": "explode"}"#), Response::Error { .. }));
+        assert!(matches!(client.send(Command::Read { x: 1000, y: 0, width: 1, height: 1 }), Response::Error { .. }));
+        let unknown = Command::Write { x: 0, y: 0, width: 1, height: 1, particle: ParticleSpec::Name("lava".into()) };
+        assert!(matches!(client.send(unknown), Response::Error { .. }));
+    });
+}